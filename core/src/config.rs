@@ -4,6 +4,7 @@ use std::path::Path;
 use serde::{Deserialize, Serialize};
 
 use crate::input::mapping::InputMapping;
+use crate::json::default_true;
 use crate::Result;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -13,6 +14,8 @@ pub struct Config {
     pub window: WindowConfig,
     #[serde(default)]
     pub input: InputMapping,
+    #[serde(default)]
+    pub accessibility: AccessibilityConfig,
 }
 
 impl Config {
@@ -53,3 +56,20 @@ impl Default for WindowConfig {
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccessibilityConfig {
+    /// If this is `true`, the trajectory of thrown weapons that have a trajectory preview, like
+    /// grenades, is drawn while the fire button is held
+    #[serde(default = "default_true", rename = "trajectory-previews")]
+    pub show_trajectory_previews: bool,
+}
+
+impl Default for AccessibilityConfig {
+    fn default() -> Self {
+        AccessibilityConfig {
+            show_trajectory_previews: true,
+        }
+    }
+}
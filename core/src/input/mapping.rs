@@ -479,6 +479,14 @@ pub struct KeyboardMapping {
     pub pickup: KeyCode,
     pub crouch: KeyCode,
     pub slide: KeyCode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reload: Option<KeyCode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt_fire: Option<KeyCode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aim_up: Option<KeyCode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aim_down: Option<KeyCode>,
 }
 
 impl KeyboardMapping {
@@ -491,6 +499,10 @@ impl KeyboardMapping {
             pickup: KeyCode::K,
             crouch: KeyCode::Down,
             slide: KeyCode::RightControl,
+            reload: Some(KeyCode::J),
+            alt_fire: Some(KeyCode::O),
+            aim_up: Some(KeyCode::I),
+            aim_down: Some(KeyCode::Comma),
         }
     }
 
@@ -503,6 +515,10 @@ impl KeyboardMapping {
             pickup: KeyCode::C,
             crouch: KeyCode::S,
             slide: KeyCode::F,
+            reload: Some(KeyCode::X),
+            alt_fire: Some(KeyCode::B),
+            aim_up: Some(KeyCode::Q),
+            aim_down: Some(KeyCode::Z),
         }
     }
 }
@@ -515,6 +531,10 @@ pub struct GamepadMapping {
     pub jump: Button,
     pub pickup: Button,
     pub slide: Button,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reload: Option<Button>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt_fire: Option<Button>,
 }
 
 impl From<usize> for GamepadMapping {
@@ -525,6 +545,8 @@ impl From<usize> for GamepadMapping {
             jump: Button::A,
             pickup: Button::X,
             slide: Button::Y,
+            reload: Some(Button::RightShoulder),
+            alt_fire: Some(Button::RightTrigger),
         }
     }
 }
@@ -581,7 +603,17 @@ impl InputMapping {
                     keyboard.slide,
                 ];
 
-                for keycode in actions {
+                let optional_actions = [
+                    keyboard.reload,
+                    keyboard.alt_fire,
+                    keyboard.aim_up,
+                    keyboard.aim_down,
+                ];
+
+                for keycode in actions
+                    .into_iter()
+                    .chain(optional_actions.into_iter().flatten())
+                {
                     if used_keys.contains(&keycode) {
                         return Err(formaterr!(
                             ErrorKind::Config,
@@ -601,7 +633,12 @@ impl InputMapping {
             for gamepad in &self.gamepads {
                 let actions = [gamepad.fire, gamepad.jump, gamepad.pickup, gamepad.slide];
 
-                for button in actions {
+                let optional_actions = [gamepad.reload, gamepad.alt_fire];
+
+                for button in actions
+                    .into_iter()
+                    .chain(optional_actions.into_iter().flatten())
+                {
                     if used_buttons.contains(&button) {
                         return Err(formaterr!(
                             ErrorKind::Config,
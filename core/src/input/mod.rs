@@ -13,7 +13,14 @@ pub use fishsticks::GamepadContext;
 
 use crate::{Config, Result};
 
-#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+/// The angle, in degrees, that is aimed at when aiming up or down while moving
+const DIGITAL_AIM_DIAGONAL: i8 = 45;
+/// The angle, in degrees, that is aimed at when aiming up or down while standing still
+const DIGITAL_AIM_VERTICAL: i8 = 90;
+/// The minimum distance that the aim stick must be moved from its center to aim
+const AIM_STICK_DEADZONE: f32 = 0.3;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct PlayerInput {
     pub left: bool,
@@ -24,6 +31,73 @@ pub struct PlayerInput {
     pub float: bool,
     pub crouch: bool,
     pub slide: bool,
+    pub reload: bool,
+    pub alt_fire: bool,
+    /// The vertical aim angle, in degrees, relative to the direction the player is facing.
+    /// Positive values aim upwards.
+    pub aim: i8,
+}
+
+impl PlayerInput {
+    /// Pack the input into bit flags, in field declaration order, starting at the least
+    /// significant bit. The aim angle is stored in the third byte.
+    /// This is used where input has to be stored compactly, like in replays.
+    pub fn to_bits(&self) -> u32 {
+        let flags = [
+            self.left,
+            self.right,
+            self.fire,
+            self.jump,
+            self.pickup,
+            self.float,
+            self.crouch,
+            self.slide,
+            self.reload,
+            self.alt_fire,
+        ]
+        .iter()
+        .enumerate()
+        .fold(
+            0,
+            |bits, (i, &is_set)| if is_set { bits | 1 << i } else { bits },
+        );
+
+        flags | ((self.aim as u8 as u32) << 16)
+    }
+
+    /// Unpack input from bit flags created with `to_bits`
+    pub fn from_bits(bits: u32) -> Self {
+        let is_set = |i: u32| bits & (1 << i) != 0;
+
+        PlayerInput {
+            left: is_set(0),
+            right: is_set(1),
+            fire: is_set(2),
+            jump: is_set(3),
+            pickup: is_set(4),
+            float: is_set(5),
+            crouch: is_set(6),
+            slide: is_set(7),
+            reload: is_set(8),
+            alt_fire: is_set(9),
+            aim: (bits >> 16) as u8 as i8,
+        }
+    }
+}
+
+/// Returns the aim angle for digital aim input. Aiming while moving will aim diagonally.
+fn get_digital_aim(is_aiming_up: bool, is_aiming_down: bool, is_moving: bool) -> i8 {
+    let angle = if is_moving {
+        DIGITAL_AIM_DIAGONAL
+    } else {
+        DIGITAL_AIM_VERTICAL
+    };
+
+    match (is_aiming_up, is_aiming_down) {
+        (true, false) => angle,
+        (false, true) => -angle,
+        _ => 0,
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -107,6 +181,26 @@ pub fn collect_local_input(input_scheme: GameInputScheme) -> PlayerInput {
                 && gamepad
                     .digital_inputs
                     .just_activated(input_mapping.slide.into());
+
+            if let Some(reload) = input_mapping.reload {
+                input.reload = gamepad.digital_inputs.just_activated(reload.into());
+            }
+
+            if let Some(alt_fire) = input_mapping.alt_fire {
+                input.alt_fire = gamepad.digital_inputs.activated(alt_fire.into());
+            }
+
+            let aim_stick = vec2(
+                gamepad.analog_inputs.value(Axis::RightStickX),
+                gamepad.analog_inputs.value(Axis::RightStickY),
+            );
+
+            if aim_stick.length() >= AIM_STICK_DEADZONE {
+                // The stick is mirrored horizontally, so that the angle is relative to the
+                // direction the player is facing
+                let angle = (-aim_stick.y).atan2(aim_stick.x.abs()).to_degrees();
+                input.aim = angle.round() as i8;
+            }
         }
     } else {
         let input_mapping = {
@@ -127,6 +221,26 @@ pub fn collect_local_input(input_scheme: GameInputScheme) -> PlayerInput {
         input.float = is_key_down(input_mapping.jump.into());
         input.crouch = is_key_down(input_mapping.crouch.into());
         input.slide = input.crouch && is_key_pressed(input_mapping.slide.into());
+
+        if let Some(reload) = input_mapping.reload {
+            input.reload = is_key_pressed(reload.into());
+        }
+
+        if let Some(alt_fire) = input_mapping.alt_fire {
+            input.alt_fire = is_key_down(alt_fire.into());
+        }
+
+        let is_aiming_up = input_mapping
+            .aim_up
+            .map(|key| is_key_down(key.into()))
+            .unwrap_or_default();
+
+        let is_aiming_down = input_mapping
+            .aim_down
+            .map(|key| is_key_down(key.into()))
+            .unwrap_or_default();
+
+        input.aim = get_digital_aim(is_aiming_up, is_aiming_down, input.left || input.right);
     }
 
     input
@@ -168,6 +168,125 @@ pub fn color_from_hex_string(str: &str) -> Color {
     )
 }
 
+/// The result of a swept collision test
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SweepHit {
+    /// The fraction of the movement, from `0.0` to `1.0`, at which the collision happens
+    pub time: f32,
+    /// The normal of the side that was hit. This is zero if the colliders were already
+    /// overlapping, at the start of the movement.
+    pub normal: Vec2,
+}
+
+/// Returns the fraction of the segment from `start` to `end` at which the segment first enters
+/// `rect`, or `None` if they do not intersect. If `start` is inside `rect`, this returns `0.0`.
+pub fn segment_rect_intersection(start: Vec2, end: Vec2, rect: &Rect) -> Option<f32> {
+    sweep_point(start, end - start, rect).map(|hit| hit.time)
+}
+
+/// Checks for collision between `rect`, moved by `delta`, and `other`, returning the earliest
+/// hit. Rects that are only touching are not considered to be colliding.
+pub fn sweep_rect(rect: &Rect, delta: Vec2, other: &Rect) -> Option<SweepHit> {
+    let expanded = Rect::new(
+        other.x - rect.w,
+        other.y - rect.h,
+        other.w + rect.w,
+        other.h + rect.h,
+    );
+
+    sweep_point(rect.point(), delta, &expanded)
+}
+
+/// Checks for collision between `rect`, moved by `delta`, and the cells of a grid with the
+/// specified cell size, starting at the origin, returning the earliest hit. Cells are only
+/// checked if `is_solid` returns `true` for their coordinates.
+pub fn sweep_rect_grid<F>(
+    rect: &Rect,
+    delta: Vec2,
+    cell_size: Vec2,
+    is_solid: F,
+) -> Option<SweepHit>
+where
+    F: Fn(u32, u32) -> bool,
+{
+    let min = vec2(rect.x.min(rect.x + delta.x), rect.y.min(rect.y + delta.y));
+    let max = vec2(
+        rect.right().max(rect.right() + delta.x),
+        rect.bottom().max(rect.bottom() + delta.y),
+    );
+
+    let min_cell = (min / cell_size).floor().max(Vec2::ZERO);
+    let max_cell = (max / cell_size).ceil();
+
+    let mut res: Option<SweepHit> = None;
+
+    for y in min_cell.y as u32..max_cell.y.max(0.0) as u32 {
+        for x in min_cell.x as u32..max_cell.x.max(0.0) as u32 {
+            if is_solid(x, y) {
+                let cell = Rect::new(
+                    x as f32 * cell_size.x,
+                    y as f32 * cell_size.y,
+                    cell_size.x,
+                    cell_size.y,
+                );
+
+                if let Some(hit) = sweep_rect(rect, delta, &cell) {
+                    if res.map(|res| hit.time < res.time).unwrap_or(true) {
+                        res = Some(hit);
+                    }
+                }
+            }
+        }
+    }
+
+    res
+}
+
+fn sweep_point(start: Vec2, delta: Vec2, rect: &Rect) -> Option<SweepHit> {
+    let mut t_min = f32::NEG_INFINITY;
+    let mut t_max = 1.0f32;
+
+    let mut normal = Vec2::ZERO;
+
+    for (axis, start, delta, min, max) in [
+        (vec2(1.0, 0.0), start.x, delta.x, rect.x, rect.x + rect.w),
+        (vec2(0.0, 1.0), start.y, delta.y, rect.y, rect.y + rect.h),
+    ] {
+        if delta == 0.0 {
+            if start <= min || start >= max {
+                return None;
+            }
+        } else {
+            let mut t1 = (min - start) / delta;
+            let mut t2 = (max - start) / delta;
+
+            if t1 > t2 {
+                std::mem::swap(&mut t1, &mut t2);
+            }
+
+            if t1 > t_min {
+                t_min = t1;
+                normal = axis * -delta.signum();
+            }
+
+            t_max = t_max.min(t2);
+        }
+    }
+
+    if t_min.max(0.0) >= t_max {
+        return None;
+    }
+
+    if t_min < 0.0 {
+        normal = Vec2::ZERO;
+    }
+
+    Some(SweepHit {
+        time: t_min.max(0.0),
+        normal,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -210,6 +329,110 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_segment_rect_intersection_hit() {
+        let rect = Rect::new(10.0, -5.0, 10.0, 10.0);
+        let res = segment_rect_intersection(vec2(0.0, 0.0), vec2(40.0, 0.0), &rect);
+        assert_eq!(res, Some(0.25));
+    }
+
+    #[test]
+    fn test_segment_rect_intersection_miss() {
+        let rect = Rect::new(10.0, 5.0, 10.0, 10.0);
+        let res = segment_rect_intersection(vec2(0.0, 0.0), vec2(40.0, 0.0), &rect);
+        assert_eq!(res, None);
+
+        let res = segment_rect_intersection(vec2(0.0, 10.0), vec2(5.0, 10.0), &rect);
+        assert_eq!(res, None);
+    }
+
+    #[test]
+    fn test_segment_rect_intersection_inside() {
+        let rect = Rect::new(-5.0, -5.0, 10.0, 10.0);
+        let res = segment_rect_intersection(vec2(0.0, 0.0), vec2(-40.0, 20.0), &rect);
+        assert_eq!(res, Some(0.0));
+    }
+
+    #[test]
+    fn test_sweep_rect_thin_wall() {
+        // The rect is on either side of the wall at the start and the end of the movement, so
+        // only checking for overlap at these positions would miss the wall
+        let rect = Rect::new(0.0, 0.0, 4.0, 4.0);
+        let wall = Rect::new(20.0, -10.0, 2.0, 30.0);
+        let res = sweep_rect(&rect, vec2(40.0, 0.0), &wall);
+        assert_eq!(
+            res,
+            Some(SweepHit {
+                time: 0.4,
+                normal: vec2(-1.0, 0.0),
+            })
+        );
+    }
+
+    #[test]
+    fn test_sweep_rect_miss() {
+        let rect = Rect::new(0.0, 0.0, 4.0, 4.0);
+        let other = Rect::new(20.0, 10.0, 10.0, 10.0);
+        assert_eq!(sweep_rect(&rect, vec2(40.0, 0.0), &other), None);
+        assert_eq!(sweep_rect(&rect, vec2(10.0, 0.0), &other), None);
+    }
+
+    #[test]
+    fn test_sweep_rect_touching() {
+        let rect = Rect::new(0.0, 0.0, 4.0, 4.0);
+        let other = Rect::new(4.0, 0.0, 4.0, 4.0);
+        assert_eq!(sweep_rect(&rect, vec2(-10.0, 0.0), &other), None);
+        assert_eq!(sweep_rect(&rect, vec2(0.0, 10.0), &other), None);
+    }
+
+    #[test]
+    fn test_sweep_rect_overlapping() {
+        let rect = Rect::new(0.0, 0.0, 4.0, 4.0);
+        let other = Rect::new(2.0, 2.0, 4.0, 4.0);
+        assert_eq!(
+            sweep_rect(&rect, vec2(-10.0, 0.0), &other),
+            Some(SweepHit {
+                time: 0.0,
+                normal: Vec2::ZERO,
+            })
+        );
+    }
+
+    #[test]
+    fn test_sweep_rect_grid_earliest_hit() {
+        let rect = Rect::new(0.0, 0.0, 4.0, 4.0);
+        let cell_size = vec2(16.0, 16.0);
+
+        let res = sweep_rect_grid(&rect, vec2(64.0, 0.0), cell_size, |x, y| {
+            y == 0 && (x == 2 || x == 3)
+        });
+        assert_eq!(
+            res,
+            Some(SweepHit {
+                time: 0.4375,
+                normal: vec2(-1.0, 0.0),
+            })
+        );
+
+        let res = sweep_rect_grid(&rect, vec2(0.0, 64.0), cell_size, |x, y| {
+            x == 0 && (y == 2 || y == 3)
+        });
+        assert_eq!(
+            res,
+            Some(SweepHit {
+                time: 0.4375,
+                normal: vec2(0.0, -1.0),
+            })
+        );
+    }
+
+    #[test]
+    fn test_sweep_rect_grid_no_solid_cells() {
+        let rect = Rect::new(-8.0, -8.0, 4.0, 4.0);
+        let res = sweep_rect_grid(&rect, vec2(64.0, 64.0), vec2(16.0, 16.0), |_, _| false);
+        assert_eq!(res, None);
+    }
 }
 
 pub fn rotate_vector(vec: Vec2, rad: f32) -> Vec2 {
@@ -6,51 +6,29 @@ use crate::Result;
 
 use super::NetworkEvent;
 
-static mut API_INSTANCE: Option<Api> = None;
-
+/// This holds a connection to the API backend. It is owned by whatever uses it, like a game, rather
+/// than being a global instance, so that a process can have multiple connections at the same time.
 pub struct Api {
     backend: Box<dyn ApiBackend>,
 }
 
 impl Api {
-    fn try_take_instance() -> Option<Api> {
-        unsafe { API_INSTANCE.take() }
-    }
+    pub async fn init<T: 'static + ApiBackend + ApiBackendConstructor>() -> Result<Api> {
+        let backend = Box::new(T::init().await?);
 
-    #[allow(dead_code)]
-    fn try_get_instance() -> Option<&'static mut Api> {
-        unsafe { API_INSTANCE.as_mut() }
+        Ok(Api { backend })
     }
 
-    #[allow(dead_code)]
-    fn get_instance() -> &'static mut Api {
-        Self::try_get_instance()
-            .unwrap_or_else(|| panic!("Api::get_instance was called before Api::init"))
+    pub async fn close(mut self) -> Result<()> {
+        self.backend.close().await
     }
 
-    pub async fn init<T: 'static + ApiBackend + ApiBackendConstructor>() -> Result<()> {
-        unsafe {
-            if API_INSTANCE.is_none() {
-                let backend = Box::new(T::init().await?);
-
-                API_INSTANCE = Some(Api { backend });
-            } else {
-                #[cfg(debug_assertions)]
-                println!("WARNING: Attempting to initiate api but it is already initiated!");
-            }
-        }
-
-        Ok(())
+    pub fn dispatch_message(&mut self, message: NetworkMessage) -> Result<()> {
+        self.backend.dispatch_message(message)
     }
 
-    pub async fn close() -> Result<()> {
-        if let Some(mut api) = Self::try_take_instance() {
-            api.backend.close().await?;
-
-            drop(api);
-        }
-
-        Ok(())
+    pub fn next_event(&mut self) -> Option<NetworkEvent> {
+        self.backend.next_event()
     }
 }
 
@@ -64,7 +42,7 @@ pub trait ApiBackendConstructor: Sized {
 
 /// This trait should be implemented by all backend implementations
 #[async_trait]
-pub trait ApiBackend {
+pub trait ApiBackend: Send + Sync {
     /// Close API connection
     async fn close(&mut self) -> Result<()>;
     /// Dispatch a network message
@@ -1,17 +1,39 @@
-static mut IS_DEBUG_DRAW_ENABLED: bool = true;
+use macroquad::prelude::*;
+use macroquad::ui::{widgets, Ui};
 
-pub fn is_debug_draw_enabled() -> bool {
-    unsafe { IS_DEBUG_DRAW_ENABLED }
-}
+use crate::ecs::Scheduler;
 
-pub fn enable_debug_draw() {
-    unsafe { IS_DEBUG_DRAW_ENABLED = true };
-}
+const SYSTEM_TIMINGS_WIDTH: f32 = 320.0;
+const SYSTEM_TIMINGS_MARGIN: f32 = 16.0;
+const SYSTEM_TIMINGS_LINE_HEIGHT: f32 = 18.0;
 
-pub fn disable_debug_draw() {
-    unsafe { IS_DEBUG_DRAW_ENABLED = false };
-}
+/// Draw the timings of the systems in the specified schedulers, in milliseconds, from the last
+/// time each scheduler was executed. Systems are listed from slowest to fastest and systems that
+/// were skipped, because of their run criteria, are left out.
+pub fn draw_system_timings(ui: &mut Ui, schedulers: &[(&str, &Scheduler)]) {
+    let x = screen_width() - SYSTEM_TIMINGS_WIDTH - SYSTEM_TIMINGS_MARGIN;
+    let mut y = SYSTEM_TIMINGS_MARGIN;
+
+    let mut draw_line = |text: String| {
+        widgets::Label::new(text).position(vec2(x, y)).ui(ui);
+        y += SYSTEM_TIMINGS_LINE_HEIGHT;
+    };
+
+    for &(label, scheduler) in schedulers {
+        let mut timings = scheduler
+            .timings()
+            .iter()
+            .filter_map(|timing| timing.duration.map(|duration| (timing.name, duration)))
+            .collect::<Vec<_>>();
+
+        timings.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+
+        let total: f64 = timings.iter().map(|&(_, duration)| duration).sum();
+
+        draw_line(format!("{}: {:.3} ms", label, total * 1000.0));
 
-pub fn toggle_debug_draw() {
-    unsafe { IS_DEBUG_DRAW_ENABLED = !IS_DEBUG_DRAW_ENABLED }
+        for (name, duration) in timings {
+            draw_line(format!("  {}: {:.3} ms", name, duration * 1000.0));
+        }
+    }
 }
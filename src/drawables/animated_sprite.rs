@@ -14,6 +14,7 @@ use serde::{Deserialize, Serialize};
 
 use core::Transform;
 
+use crate::game::get_delta_time;
 use crate::{Drawable, DrawableKind, Resources};
 
 #[derive(Debug, Clone)]
@@ -138,6 +139,9 @@ pub struct AnimatedSprite {
     pub offset: Vec2,
     pub pivot: Option<Vec2>,
     pub tint: Color,
+    /// The tint the sprite was created with. Systems that change `tint` temporarily, like status
+    /// effects, apply their changes on top of this and restore it when they are done.
+    pub base_tint: Color,
     pub animations: Vec<Animation>,
     pub current_index: usize,
     pub queued_action: Option<QueuedAnimationAction>,
@@ -210,6 +214,7 @@ impl AnimatedSprite {
             offset: params.offset,
             pivot: params.pivot,
             tint: params.tint,
+            base_tint: params.tint,
             frame_timer: 0.0,
             current_index,
             queued_action: None,
@@ -280,16 +285,18 @@ impl AnimatedSprite {
     }
 }
 
-pub fn update_animated_sprites(world: &mut World) {
-    for (_, drawable) in world.query_mut::<&mut Drawable>() {
+pub fn update_animated_sprites(world: &World) {
+    let dt = get_delta_time(world);
+
+    for (_, drawable) in world.query::<&mut Drawable>().iter() {
         match drawable.kind.borrow_mut() {
             DrawableKind::AnimatedSprite(sprite) => {
-                update_one_animated_sprite(sprite);
+                update_one_animated_sprite(sprite, dt);
             }
             DrawableKind::AnimatedSpriteSet(sprite_set) => {
                 for key in &sprite_set.draw_order {
                     let sprite = sprite_set.map.get_mut(key).unwrap();
-                    update_one_animated_sprite(sprite);
+                    update_one_animated_sprite(sprite, dt);
                 }
             }
             _ => {}
@@ -297,9 +304,7 @@ pub fn update_animated_sprites(world: &mut World) {
     }
 }
 
-pub fn update_one_animated_sprite(sprite: &mut AnimatedSprite) {
-    let dt = get_frame_time();
-
+pub fn update_one_animated_sprite(sprite: &mut AnimatedSprite, dt: f32) {
     if !sprite.is_deactivated && sprite.is_playing {
         let (is_last_frame, is_looping) = {
             let animation = sprite.animations.get(sprite.current_index).unwrap();
@@ -1,51 +1,430 @@
-use hecs::{Entity, World};
+use std::any::TypeId;
+use std::collections::HashMap;
+#[cfg(not(target_family = "wasm"))]
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(not(target_family = "wasm"))]
+use std::sync::mpsc;
+#[cfg(not(target_family = "wasm"))]
+use std::thread;
+
+use hecs::{Component, Entity, Ref, RefMut, World};
+
+use macroquad::miniquad::date;
+
+use core::error::ErrorKind;
+use core::{formaterr, Result};
 
 pub type SystemFn = fn(&mut World);
 
+/// Parallel systems only get shared access to the world, so that they can be executed at the same
+/// time as other parallel systems, as long as the component access they declare does not
+/// conflict. Component access is borrow checked by `hecs` at runtime, so access that is not
+/// declared will cause a panic if it conflicts with another system.
+/// They must not use anything outside of the world, like `storage`, or any of the macroquad
+/// functions that rely on its global context, like `get_frame_time`.
+pub type ParallelSystemFn = fn(&World);
+
+/// Run criteria are checked every time a system is about to be executed, and the system will be
+/// skipped if they return `false`.
+pub type RunCriteriaFn = Box<dyn Fn(&World) -> bool>;
+
 /// This is used as a component to signify ownership
 pub struct Owner(pub Entity);
 
-/// Placeholder until we implement threading
+/// Resources are components that there is only a single instance of in a world, like the map or
+/// the collision world. They are owned by the world, rather than being kept in `storage`, so that
+/// every game has its own instances of them.
+///
+/// This will replace the existing resource of the same type, if there is one.
+pub fn insert_resource<T: Component>(world: &mut World, resource: T) -> Entity {
+    let existing = get_resource_entity::<T>(world);
+
+    if let Some(entity) = existing {
+        world.insert_one(entity, resource).unwrap();
+        entity
+    } else {
+        world.spawn((resource,))
+    }
+}
+
+fn get_resource_entity<T: Component>(world: &World) -> Option<Entity> {
+    world.query::<&T>().iter().next().map(|(entity, _)| entity)
+}
+
+pub fn try_get_resource<T: Component>(world: &World) -> Option<Ref<T>> {
+    get_resource_entity::<T>(world).and_then(|entity| world.get::<T>(entity).ok())
+}
+
+pub fn try_get_resource_mut<T: Component>(world: &World) -> Option<RefMut<T>> {
+    get_resource_entity::<T>(world).and_then(|entity| world.get_mut::<T>(entity).ok())
+}
+
+/// This will panic if the resource has not been inserted, or if it is already borrowed mutably
+pub fn get_resource<T: Component>(world: &World) -> Ref<T> {
+    try_get_resource(world).unwrap_or_else(|| {
+        panic!(
+            "Resource of type '{}' does not exist",
+            std::any::type_name::<T>()
+        )
+    })
+}
+
+/// This will panic if the resource has not been inserted, or if it is already borrowed
+pub fn get_resource_mut<T: Component>(world: &World) -> RefMut<T> {
+    try_get_resource_mut(world).unwrap_or_else(|| {
+        panic!(
+            "Resource of type '{}' does not exist",
+            std::any::type_name::<T>()
+        )
+    })
+}
+
+/// Create a `System` from a function, using the name of the function as the name of the system
+#[macro_export]
+macro_rules! system {
+    ($func:ident) => {
+        $crate::ecs::System::new(stringify!($func), $func)
+    };
+}
+
+/// Create a parallel `System` from a function, using the name of the function as the name of the
+/// system
+#[macro_export]
+macro_rules! parallel_system {
+    ($func:ident) => {
+        $crate::ecs::System::new_parallel(stringify!($func), $func)
+    };
+}
+
+enum SystemKind {
+    Exclusive(SystemFn),
+    Parallel(ParallelSystemFn),
+}
+
+/// A named system, with optional ordering constraints and run criteria.
+/// The name is used to refer to the system in ordering constraints and in timings, so it must be
+/// unique within a scheduler.
+pub struct System {
+    name: &'static str,
+    kind: SystemKind,
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+    before: Vec<&'static str>,
+    after: Vec<&'static str>,
+    run_criteria: Option<RunCriteriaFn>,
+}
+
+impl System {
+    /// Create a system that has exclusive access to the world. It will always run on the main
+    /// thread, with no other systems running at the same time.
+    pub fn new(name: &'static str, func: SystemFn) -> Self {
+        System::with_kind(name, SystemKind::Exclusive(func))
+    }
+
+    /// Create a system that can run in parallel with other parallel systems.
+    /// The components it accesses must be declared with `reads` and `writes`.
+    pub fn new_parallel(name: &'static str, func: ParallelSystemFn) -> Self {
+        System::with_kind(name, SystemKind::Parallel(func))
+    }
+
+    fn with_kind(name: &'static str, kind: SystemKind) -> Self {
+        System {
+            name,
+            kind,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+            run_criteria: None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Declare that this system reads components of type `T`
+    #[must_use]
+    pub fn reads<T: Component>(self) -> Self {
+        let mut reads = self.reads;
+        reads.push(TypeId::of::<T>());
+
+        System { reads, ..self }
+    }
+
+    /// Declare that this system writes to components of type `T`
+    #[must_use]
+    pub fn writes<T: Component>(self) -> Self {
+        let mut writes = self.writes;
+        writes.push(TypeId::of::<T>());
+
+        System { writes, ..self }
+    }
+
+    fn is_parallel(&self) -> bool {
+        matches!(self.kind, SystemKind::Parallel(..))
+    }
+
+    /// Returns `true` if the two systems can not run at the same time
+    fn conflicts_with(&self, other: &System) -> bool {
+        if !self.is_parallel() || !other.is_parallel() {
+            return true;
+        }
+
+        self.writes
+            .iter()
+            .any(|id| other.reads.contains(id) || other.writes.contains(id))
+            || other.writes.iter().any(|id| self.reads.contains(id))
+    }
+
+    /// Run this system before the system with the specified name.
+    /// Building the scheduler will fail if there is no system with the specified name.
+    #[must_use]
+    pub fn before(self, name: &'static str) -> Self {
+        let mut before = self.before;
+        before.push(name);
+
+        System { before, ..self }
+    }
+
+    /// Run this system after the system with the specified name.
+    /// Building the scheduler will fail if there is no system with the specified name.
+    #[must_use]
+    pub fn after(self, name: &'static str) -> Self {
+        let mut after = self.after;
+        after.push(name);
+
+        System { after, ..self }
+    }
+
+    #[must_use]
+    pub fn with_run_criteria<F>(self, run_criteria: F) -> Self
+    where
+        F: Fn(&World) -> bool + 'static,
+    {
+        System {
+            run_criteria: Some(Box::new(run_criteria)),
+            ..self
+        }
+    }
+
+    fn should_run(&self, world: &World) -> bool {
+        self.run_criteria
+            .as_ref()
+            .map(|run_criteria| run_criteria(world))
+            .unwrap_or(true)
+    }
+}
+
+/// The time spent executing a system, the last time its scheduler was executed
+#[derive(Debug, Copy, Clone)]
+pub struct SystemTiming {
+    pub name: &'static str,
+    /// The duration in seconds, or `None` if the system was skipped because of its run criteria
+    pub duration: Option<f64>,
+}
+
+/// Systems are executed in stages. An exclusive system gets a stage of its own, while consecutive
+/// parallel systems, that do not conflict with or depend on each other, are grouped into a stage
+/// where they are executed at the same time.
+enum Stage {
+    Exclusive(usize),
+    Parallel(Vec<usize>),
+}
+
+/// Thread local systems, like the ones that draw, have to be created with `System::new`, so that
+/// they have exclusive access to the world and stay on the main thread.
 #[derive(Default)]
 pub struct SchedulerBuilder {
-    steps: Vec<SystemFn>,
+    systems: Vec<System>,
 }
 
 impl SchedulerBuilder {
     #[must_use]
-    pub fn with_system(self, system: SystemFn) -> Self {
-        let mut steps = self.steps;
-        steps.push(system);
+    pub fn with_system(self, system: System) -> Self {
+        let mut systems = self.systems;
+        systems.push(system);
 
-        SchedulerBuilder { steps }
+        SchedulerBuilder { systems }
     }
 
-    pub fn add_system(&mut self, system: SystemFn) -> &mut Self {
-        self.steps.push(system);
+    pub fn add_system(&mut self, system: System) -> &mut Self {
+        self.systems.push(system);
         self
     }
 
     #[must_use]
-    pub fn with_thread_local(self, system: SystemFn) -> Self {
-        let mut steps = self.steps;
-        steps.push(system);
+    pub fn with_thread_local(self, system: System) -> Self {
+        debug_assert!(
+            !system.is_parallel(),
+            "Scheduler: Thread local system '{}' can not be a parallel system",
+            system.name
+        );
+
+        let mut systems = self.systems;
+        systems.push(system);
 
-        SchedulerBuilder { steps }
+        SchedulerBuilder { systems }
     }
 
-    pub fn add_thread_local(&mut self, system: SystemFn) -> &mut Self {
-        self.steps.push(system);
+    pub fn add_thread_local(&mut self, system: System) -> &mut Self {
+        debug_assert!(
+            !system.is_parallel(),
+            "Scheduler: Thread local system '{}' can not be a parallel system",
+            system.name
+        );
+
+        self.systems.push(system);
         self
     }
 
-    pub fn build(self) -> Scheduler {
-        Scheduler { steps: self.steps }
+    /// Build the scheduler. Systems are ordered by their constraints and, where no constraints
+    /// apply, by the order they were added in.
+    /// This will return an error if there are duplicate system names, constraints referring to
+    /// systems that are not in the scheduler, circular constraints or parallel systems that have
+    /// not declared any component access.
+    pub fn build(self) -> Result<Scheduler> {
+        let cnt = self.systems.len();
+
+        let mut indices = HashMap::new();
+        for (i, system) in self.systems.iter().enumerate() {
+            if indices.insert(system.name, i).is_some() {
+                return Err(formaterr!(
+                    ErrorKind::Ecs,
+                    "Scheduler: Duplicate system name '{}'",
+                    system.name
+                ));
+            }
+
+            if system.is_parallel() && system.reads.is_empty() && system.writes.is_empty() {
+                return Err(formaterr!(
+                    ErrorKind::Ecs,
+                    "Scheduler: Parallel system '{}' has not declared any component access",
+                    system.name
+                ));
+            }
+        }
+
+        // This holds the indices of the systems that must run before the system at each index
+        let mut dependencies = vec![Vec::new(); cnt];
+        for (i, system) in self.systems.iter().enumerate() {
+            for name in system.after.iter().chain(&system.before) {
+                if !indices.contains_key(name) {
+                    return Err(formaterr!(
+                        ErrorKind::Ecs,
+                        "Scheduler: System '{}' has an ordering constraint on unknown system '{}'",
+                        system.name,
+                        name
+                    ));
+                }
+            }
+
+            for name in &system.after {
+                dependencies[i].push(indices[name]);
+            }
+
+            for name in &system.before {
+                dependencies[indices[name]].push(i);
+            }
+        }
+
+        let mut order = Vec::with_capacity(cnt);
+        let mut is_ordered = vec![false; cnt];
+
+        while order.len() < cnt {
+            let next = (0..cnt)
+                .find(|&i| !is_ordered[i] && dependencies[i].iter().all(|&j| is_ordered[j]));
+
+            if let Some(i) = next {
+                is_ordered[i] = true;
+                order.push(i);
+            } else {
+                let names = (0..cnt)
+                    .filter(|&i| !is_ordered[i])
+                    .map(|i| self.systems[i].name)
+                    .collect::<Vec<_>>();
+
+                return Err(formaterr!(
+                    ErrorKind::Ecs,
+                    "Scheduler: Circular ordering constraints between systems '{}'",
+                    names.join("', '")
+                ));
+            }
+        }
+
+        let mut systems = self.systems.into_iter().map(Some).collect::<Vec<_>>();
+
+        let systems = order
+            .iter()
+            .map(|&i| systems[i].take().unwrap())
+            .collect::<Vec<_>>();
+
+        let dependencies = order
+            .iter()
+            .map(|&i| {
+                dependencies[i]
+                    .iter()
+                    .map(|&j| order.iter().position(|&k| k == j).unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut stages = Vec::new();
+        let mut current: Vec<usize> = Vec::new();
+
+        // A system that is ordered after a system in the current stage can not run at the same
+        // time as it, so it starts a new stage, even if their component access does not conflict
+        for (i, system) in systems.iter().enumerate() {
+            if current
+                .iter()
+                .any(|&j| systems[j].conflicts_with(system) || dependencies[i].contains(&j))
+            {
+                stages.push(Stage::Parallel(current));
+                current = Vec::new();
+            }
+
+            if system.is_parallel() {
+                current.push(i);
+            } else {
+                stages.push(Stage::Exclusive(i));
+            }
+        }
+
+        if !current.is_empty() {
+            stages.push(Stage::Parallel(current));
+        }
+
+        let timings = systems
+            .iter()
+            .map(|system| SystemTiming {
+                name: system.name,
+                duration: None,
+            })
+            .collect();
+
+        let worker_cnt = stages
+            .iter()
+            .map(|stage| match stage {
+                Stage::Parallel(indices) if indices.len() > 1 => indices.len(),
+                _ => 0,
+            })
+            .max()
+            .unwrap_or_default();
+
+        Ok(Scheduler {
+            systems,
+            stages,
+            timings,
+            workers: WorkerPool::new(worker_cnt),
+        })
     }
 }
 
-/// Placeholder until we implement threading
 pub struct Scheduler {
-    steps: Vec<SystemFn>,
+    systems: Vec<System>,
+    stages: Vec<Stage>,
+    timings: Vec<SystemTiming>,
+    workers: WorkerPool,
 }
 
 impl Scheduler {
@@ -54,8 +433,172 @@ impl Scheduler {
     }
 
     pub fn execute(&mut self, world: &mut World) {
-        for f in &mut self.steps {
-            f(world);
+        for timing in &mut self.timings {
+            timing.duration = None;
+        }
+
+        for stage in &self.stages {
+            match stage {
+                Stage::Exclusive(i) => {
+                    let system = &self.systems[*i];
+
+                    if system.should_run(world) {
+                        if let SystemKind::Exclusive(func) = system.kind {
+                            let start = date::now();
+                            func(world);
+                            self.timings[*i].duration = Some(date::now() - start);
+                        }
+                    }
+                }
+                Stage::Parallel(indices) => {
+                    let world: &World = world;
+
+                    let to_run = indices
+                        .iter()
+                        .filter_map(|&i| {
+                            let system = &self.systems[i];
+
+                            match system.kind {
+                                SystemKind::Parallel(func) if system.should_run(world) => {
+                                    Some((i, func))
+                                }
+                                _ => None,
+                            }
+                        })
+                        .collect::<Vec<_>>();
+
+                    for (i, duration) in self.workers.execute(world, &to_run) {
+                        self.timings[i].duration = Some(duration);
+                    }
+                }
+            }
         }
     }
+
+    /// Returns the timings from the last time the scheduler was executed, in execution order
+    pub fn timings(&self) -> &[SystemTiming] {
+        &self.timings
+    }
+}
+
+/// A parallel system to execute on a worker thread. The world is passed as a pointer, as the
+/// worker threads outlive the borrow of the world.
+#[cfg(not(target_family = "wasm"))]
+struct WorkerJob {
+    index: usize,
+    func: ParallelSystemFn,
+    world: *const World,
+}
+
+// The world is only accessed through a shared reference, and `WorkerPool::execute` does not
+// return until every job it has sent has been completed, so the pointer will always be valid.
+#[cfg(not(target_family = "wasm"))]
+unsafe impl Send for WorkerJob {}
+
+/// The threads that parallel systems are executed on. They are spawned when the scheduler is
+/// built and live for as long as it does, so that threads don't have to be spawned every time a
+/// parallel stage is executed.
+#[cfg(not(target_family = "wasm"))]
+struct WorkerPool {
+    jobs: Vec<mpsc::Sender<WorkerJob>>,
+    results: mpsc::Receiver<(usize, thread::Result<f64>)>,
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl WorkerPool {
+    fn new(worker_cnt: usize) -> Self {
+        let (result_sender, results) = mpsc::channel();
+
+        let jobs = (0..worker_cnt)
+            .map(|_| {
+                let (job_sender, job_receiver) = mpsc::channel::<WorkerJob>();
+                let result_sender = result_sender.clone();
+
+                thread::spawn(move || {
+                    for job in job_receiver {
+                        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                            let world = unsafe { &*job.world };
+
+                            let start = date::now();
+                            (job.func)(world);
+                            date::now() - start
+                        }));
+
+                        if result_sender.send((job.index, res)).is_err() {
+                            break;
+                        }
+                    }
+                });
+
+                job_sender
+            })
+            .collect();
+
+        WorkerPool { jobs, results }
+    }
+
+    /// Execute the specified parallel systems, each on its own worker, and return their
+    /// durations. A panic in any of the systems is resumed on the calling thread, once all of
+    /// them have completed.
+    fn execute(&self, world: &World, systems: &[(usize, ParallelSystemFn)]) -> Vec<(usize, f64)> {
+        if systems.len() < 2 {
+            return execute_sequential(world, systems);
+        }
+
+        debug_assert!(systems.len() <= self.jobs.len());
+
+        for (&(index, func), sender) in systems.iter().zip(&self.jobs) {
+            let job = WorkerJob {
+                index,
+                func,
+                world: world as *const World,
+            };
+
+            sender.send(job).unwrap();
+        }
+
+        let mut durations = Vec::with_capacity(systems.len());
+        let mut panic_payload = None;
+
+        for _ in systems {
+            let (i, res) = self.results.recv().unwrap();
+
+            match res {
+                Ok(duration) => durations.push((i, duration)),
+                Err(payload) => panic_payload = Some(payload),
+            }
+        }
+
+        if let Some(payload) = panic_payload {
+            panic::resume_unwind(payload);
+        }
+
+        durations
+    }
+}
+
+/// Threads are not available on wasm, so parallel systems are executed sequentially
+#[cfg(target_family = "wasm")]
+struct WorkerPool;
+
+#[cfg(target_family = "wasm")]
+impl WorkerPool {
+    fn new(_worker_cnt: usize) -> Self {
+        WorkerPool
+    }
+
+    fn execute(&self, world: &World, systems: &[(usize, ParallelSystemFn)]) -> Vec<(usize, f64)> {
+        execute_sequential(world, systems)
+    }
+}
+
+fn execute_sequential(world: &World, systems: &[(usize, ParallelSystemFn)]) -> Vec<(usize, f64)> {
+    systems
+        .iter()
+        .map(|&(i, func)| {
+            let start = date::now();
+            func(world);
+            (i, date::now() - start)
+        })
+        .collect()
 }
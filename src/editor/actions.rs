@@ -1,5 +1,6 @@
 use std::any::TypeId;
 use std::cmp::Ordering;
+use std::collections::HashMap;
 
 use macroquad::experimental::collections::storage;
 use macroquad::prelude::*;
@@ -7,7 +8,7 @@ use macroquad::prelude::*;
 use core::error::{Error, ErrorKind, Result};
 
 use crate::editor::gui::windows::Window;
-use crate::map::{MapBackgroundLayer, MapObject, MapObjectKind};
+use crate::map::{MapBackgroundLayer, MapObject, MapObjectKind, MapProperty};
 use crate::{
     map::{Map, MapLayer, MapLayerKind, MapTile, MapTileset},
     Resources,
@@ -104,6 +105,8 @@ pub enum EditorAction {
         id: String,
         kind: MapObjectKind,
         position: Vec2,
+        /// If this is `None`, the properties of the object will be left unchanged
+        properties: Option<HashMap<String, MapProperty>>,
     },
     CreateSpawnPoint(Vec2),
     DeleteSpawnPoint(usize),
@@ -929,6 +932,7 @@ pub struct UpdateObjectAction {
     id: String,
     kind: MapObjectKind,
     position: Vec2,
+    properties: Option<HashMap<String, MapProperty>>,
     object: Option<MapObject>,
 }
 
@@ -939,6 +943,7 @@ impl UpdateObjectAction {
         id: String,
         kind: MapObjectKind,
         position: Vec2,
+        properties: Option<HashMap<String, MapProperty>>,
     ) -> Self {
         UpdateObjectAction {
             layer_id,
@@ -946,6 +951,7 @@ impl UpdateObjectAction {
             id,
             kind,
             position,
+            properties,
             object: None,
         }
     }
@@ -960,6 +966,10 @@ impl UndoableAction for UpdateObjectAction {
                 object.id = self.id.clone();
                 object.kind = self.kind;
                 object.position = self.position;
+
+                if let Some(properties) = &self.properties {
+                    object.properties = properties.clone();
+                }
             } else {
                 return Err(Error::new_const(
                     ErrorKind::EditorAction,
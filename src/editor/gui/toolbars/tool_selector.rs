@@ -11,11 +11,7 @@ use super::{
 };
 
 use crate::editor::tools::EditorTool;
-use crate::{
-    editor::tools::{get_tool_instance_of_id, EditorToolParams},
-    gui::GuiResources,
-    Resources,
-};
+use crate::{editor::tools::EditorToolParams, gui::GuiResources, Resources};
 
 pub struct ToolSelectorElement {
     params: ToolbarElementParams,
@@ -60,7 +56,7 @@ impl ToolbarElement for ToolSelectorElement {
         &mut self,
         ui: &mut Ui,
         size: Vec2,
-        map: &Map,
+        _map: &Map,
         ctx: &EditorContext,
     ) -> Option<EditorAction> {
         let mut res = None;
@@ -78,12 +74,10 @@ impl ToolbarElement for ToolSelectorElement {
             .tools
             .iter()
             .filter_map(|id| {
-                let tool = get_tool_instance_of_id(id);
-                if tool.is_available(map, ctx) {
-                    return Some((Some(*id), tool.get_params().clone()));
-                }
-
-                None
+                ctx.available_tools
+                    .iter()
+                    .find(|(tool_id, _)| tool_id == id)
+                    .map(|(_, params)| (Some(*id), params.clone()))
             })
             .collect::<Vec<(Option<TypeId>, EditorToolParams)>>();
 
@@ -7,7 +7,7 @@ use macroquad::{
 use crate::editor::gui::combobox::ComboBoxVec;
 use crate::{
     editor::gui::{ComboBoxBuilder, ComboBoxValue},
-    map::{Map, MapObjectKind},
+    map::{Map, MapObjectKind, ENVIRONMENT_OBJECT_IDS},
     Resources,
 };
 
@@ -132,7 +132,7 @@ impl Window for CreateObjectWindow {
                 .keys()
                 .map(|k| k.as_str())
                 .collect::<Vec<&str>>(),
-            MapObjectKind::Environment => vec!["sproinger", "crab", "fish_school"],
+            MapObjectKind::Environment => ENVIRONMENT_OBJECT_IDS.to_vec(),
             MapObjectKind::Decoration => resources
                 .decoration
                 .keys()
@@ -5,7 +5,12 @@ use macroquad::{
 };
 
 use crate::editor::gui::combobox::ComboBoxVec;
-use crate::map::MapObject;
+use crate::gui::Checkbox;
+use crate::items::{
+    ItemDropBehavior, ITEM_DROP_BEHAVIOR_PROPERTY, ITEM_FACING_LEFT_PROPERTY,
+    ITEM_RESPAWN_DELAY_PROPERTY, ITEM_USES_PROPERTY, ITEM_VELOCITY_PROPERTY,
+};
+use crate::map::{get_number_property, MapObject, MapProperty, ENVIRONMENT_OBJECT_IDS};
 use crate::{
     editor::gui::{ComboBoxBuilder, ComboBoxValue},
     map::{Map, MapObjectKind},
@@ -19,13 +24,52 @@ pub struct ObjectPropertiesWindow {
     layer_id: String,
     index: usize,
     object: Option<MapObject>,
+    item_properties: Option<ItemPropertiesInput>,
+}
+
+/// The text input values of the item property overrides. An empty value means that the value in
+/// the item metadata will be used.
+struct ItemPropertiesInput {
+    uses: String,
+    respawn_delay: String,
+    velocity_x: String,
+    velocity_y: String,
+}
+
+impl ItemPropertiesInput {
+    fn from_object(object: &MapObject) -> Self {
+        let get_number_str = |key: &str| {
+            object
+                .properties
+                .get(key)
+                .and_then(get_number_property)
+                .map(|value| value.to_string())
+                .unwrap_or_default()
+        };
+
+        let (velocity_x, velocity_y) = object
+            .properties
+            .get(ITEM_VELOCITY_PROPERTY)
+            .and_then(|property| property.get_value::<Vec2>())
+            .map(|velocity| (velocity.x.to_string(), velocity.y.to_string()))
+            .unwrap_or_default();
+
+        ItemPropertiesInput {
+            uses: get_number_str(ITEM_USES_PROPERTY),
+            respawn_delay: get_number_str(ITEM_RESPAWN_DELAY_PROPERTY),
+            velocity_x,
+            velocity_y,
+        }
+    }
 }
 
 impl ObjectPropertiesWindow {
+    const DEFAULT_OPTION: &'static str = "default";
+
     pub fn new(layer_id: String, index: usize) -> Self {
         let params = WindowParams {
             title: Some("Object Properties".to_string()),
-            size: vec2(300.0, 300.0),
+            size: vec2(300.0, 450.0),
             ..Default::default()
         };
 
@@ -34,6 +78,143 @@ impl ObjectPropertiesWindow {
             layer_id,
             index,
             object: None,
+            item_properties: None,
+        }
+    }
+
+    fn draw_item_properties(&mut self, ui: &mut Ui, object: &mut MapObject) {
+        let id = hash!("update_object_window", "item_properties");
+
+        let input = self
+            .item_properties
+            .get_or_insert_with(|| ItemPropertiesInput::from_object(object));
+
+        let size = vec2(72.0, 28.0);
+
+        ui.separator();
+        ui.separator();
+        ui.separator();
+        ui.separator();
+
+        ui.label(None, "Overrides (empty for default)");
+
+        widgets::InputText::new(hash!(id, "uses_input"))
+            .size(size)
+            .ui(ui, &mut input.uses);
+
+        ui.same_line(0.0);
+
+        ui.label(None, "Uses");
+
+        widgets::InputText::new(hash!(id, "respawn_delay_input"))
+            .size(size)
+            .ui(ui, &mut input.respawn_delay);
+
+        ui.same_line(0.0);
+
+        ui.label(None, "Respawn delay");
+
+        widgets::InputText::new(hash!(id, "velocity_x_input"))
+            .size(size)
+            .ui(ui, &mut input.velocity_x);
+
+        ui.same_line(0.0);
+
+        ui.label(None, "x");
+
+        ui.same_line(0.0);
+
+        widgets::InputText::new(hash!(id, "velocity_y_input"))
+            .size(size)
+            .ui(ui, &mut input.velocity_y);
+
+        ui.same_line(0.0);
+
+        ui.label(None, "Velocity");
+
+        if let Ok(uses) = input.uses.trim().parse::<u32>() {
+            object
+                .properties
+                .insert(ITEM_USES_PROPERTY.to_string(), MapProperty::UInt(uses));
+        } else {
+            object.properties.remove(ITEM_USES_PROPERTY);
+        }
+
+        if let Ok(respawn_delay) = input.respawn_delay.trim().parse::<f32>() {
+            object.properties.insert(
+                ITEM_RESPAWN_DELAY_PROPERTY.to_string(),
+                MapProperty::Float(respawn_delay),
+            );
+        } else {
+            object.properties.remove(ITEM_RESPAWN_DELAY_PROPERTY);
+        }
+
+        if input.velocity_x.trim().is_empty() && input.velocity_y.trim().is_empty() {
+            object.properties.remove(ITEM_VELOCITY_PROPERTY);
+        } else {
+            let x = input.velocity_x.trim().parse::<f32>().unwrap_or_default();
+            let y = input.velocity_y.trim().parse::<f32>().unwrap_or_default();
+
+            object.properties.insert(
+                ITEM_VELOCITY_PROPERTY.to_string(),
+                MapProperty::Vec2(vec2(x, y)),
+            );
+        }
+
+        ui.separator();
+        ui.separator();
+        ui.separator();
+        ui.separator();
+
+        let mut drop_behavior_options = vec![Self::DEFAULT_OPTION];
+        drop_behavior_options.extend_from_slice(ItemDropBehavior::options());
+
+        let mut drop_behavior_value = {
+            let index = object
+                .properties
+                .get(ITEM_DROP_BEHAVIOR_PROPERTY)
+                .and_then(|property| property.get_value::<String>())
+                .and_then(|value| {
+                    drop_behavior_options
+                        .iter()
+                        .position(|option| *option == value.as_str())
+                })
+                .unwrap_or_default();
+
+            ComboBoxVec::new(index, &drop_behavior_options)
+        };
+
+        ComboBoxBuilder::new(hash!(id, "drop_behavior_input"))
+            .with_ratio(0.8)
+            .with_label("On drop")
+            .build(ui, &mut drop_behavior_value);
+
+        if drop_behavior_value.get_index() == 0 {
+            object.properties.remove(ITEM_DROP_BEHAVIOR_PROPERTY);
+        } else {
+            object.properties.insert(
+                ITEM_DROP_BEHAVIOR_PROPERTY.to_string(),
+                MapProperty::String(drop_behavior_value.get_value()),
+            );
+        }
+
+        let mut is_facing_left = object
+            .properties
+            .get(ITEM_FACING_LEFT_PROPERTY)
+            .and_then(|property| property.get_value::<bool>())
+            .copied()
+            .unwrap_or_default();
+
+        Checkbox::new(hash!(id, "facing_left_input"), None, "Face left")
+            .ui(ui, &mut is_facing_left);
+
+        if is_facing_left {
+            object.properties.insert(
+                ITEM_FACING_LEFT_PROPERTY.to_string(),
+                MapProperty::Bool(true),
+            );
+        } else {
+            object.properties.remove(ITEM_FACING_LEFT_PROPERTY);
         }
     }
 }
@@ -55,6 +236,7 @@ impl Window for ObjectPropertiesWindow {
                 id: object.id.clone(),
                 kind: object.kind,
                 position: object.position,
+                properties: Some(object.properties.clone()),
             });
 
             action = Some(batch);
@@ -146,7 +328,7 @@ impl Window for ObjectPropertiesWindow {
                 .keys()
                 .map(|k| k.as_str())
                 .collect::<Vec<&str>>(),
-            MapObjectKind::Environment => vec!["sproinger"],
+            MapObjectKind::Environment => ENVIRONMENT_OBJECT_IDS.to_vec(),
             MapObjectKind::Decoration => resources
                 .decoration
                 .keys()
@@ -171,6 +353,10 @@ impl Window for ObjectPropertiesWindow {
 
         object.id = item_id_value.get_value();
 
+        if object.kind == MapObjectKind::Item {
+            self.draw_item_properties(ui, &mut object);
+        }
+
         self.object = Some(object);
 
         None
@@ -103,6 +103,17 @@ impl Window for TilePropertiesWindow {
             } else if !is_jumpthrough && was_jumpthrough {
                 attributes.retain(|s| s != JUMPTHROUGH_ATTRIBUTE);
             }
+
+            let was_water = attributes.contains(&(Map::WATER_TILE_ATTRIBUTE.to_string()));
+            let mut is_water = was_water;
+
+            Checkbox::new(hash!(id, "water_input"), None, "Water").ui(ui, &mut is_water);
+
+            if is_water && !was_water {
+                attributes.push(Map::WATER_TILE_ATTRIBUTE.to_string());
+            } else if !is_water && was_water {
+                attributes.retain(|s| s != Map::WATER_TILE_ATTRIBUTE);
+            }
         }
 
         None
@@ -3,7 +3,7 @@ use std::path::Path;
 
 use crate::{
     exit_to_main_menu,
-    map::{CRAB_TEXTURE_ID, FISH_SCHOOL_ICON_TEXTURE_ID},
+    map::{CRAB_TEXTURE_ID, FISH_SCHOOL_ICON_TEXTURE_ID, ITEM_SPAWNER_ID},
     quit_to_desktop, Resources,
 };
 
@@ -36,8 +36,8 @@ mod history;
 mod tools;
 
 pub use tools::{
-    add_tool_instance, get_tool_instance, get_tool_instance_of_id, EraserTool, ObjectPlacementTool,
-    TilePlacementTool, DEFAULT_TOOL_ICON_TEXTURE_ID,
+    EditorTool, EditorToolParams, EditorTools, EraserTool, ObjectPlacementTool, TilePlacementTool,
+    DEFAULT_TOOL_ICON_TEXTURE_ID,
 };
 
 use history::EditorHistory;
@@ -75,6 +75,8 @@ use crate::resources::{
 #[derive(Debug, Clone)]
 pub struct EditorContext {
     pub selected_tool: Option<TypeId>,
+    /// The ids and params of the tools that are available in this context
+    pub available_tools: Vec<(TypeId, EditorToolParams)>,
     pub selected_layer: Option<String>,
     pub selected_tileset: Option<String>,
     pub selected_tile: Option<u32>,
@@ -90,6 +92,7 @@ impl Default for EditorContext {
     fn default() -> Self {
         EditorContext {
             selected_tool: None,
+            available_tools: Vec::new(),
             selected_layer: None,
             selected_tileset: None,
             selected_tile: None,
@@ -124,6 +127,7 @@ const SPAWN_POINT_COLLIDER_HEIGHT: f32 = 49.0;
 pub struct Editor {
     map_resource: MapResource,
 
+    tools: EditorTools,
     selected_tool: Option<TypeId>,
     selected_layer: Option<String>,
     selected_tileset: Option<String>,
@@ -182,10 +186,11 @@ impl Editor {
     const MESSAGE_TIMEOUT: f32 = 2.5;
 
     pub fn new(input_scheme: EditorInputScheme, map_resource: MapResource) -> Self {
-        add_tool_instance(TilePlacementTool::new());
-        add_tool_instance(ObjectPlacementTool::new());
-        add_tool_instance(SpawnPointPlacementTool::new());
-        add_tool_instance(EraserTool::new());
+        let tools = EditorTools::new()
+            .with_tool(TilePlacementTool::new())
+            .with_tool(ObjectPlacementTool::new())
+            .with_tool(SpawnPointPlacementTool::new())
+            .with_tool(EraserTool::new());
 
         let selected_tool = None;
 
@@ -231,6 +236,7 @@ impl Editor {
 
         Editor {
             map_resource,
+            tools,
             selected_tool,
             selected_layer,
             selected_tileset: None,
@@ -284,8 +290,9 @@ impl Editor {
     }
 
     fn get_context(&self) -> EditorContext {
-        EditorContext {
+        let mut ctx = EditorContext {
             selected_tool: self.selected_tool,
+            available_tools: Vec::new(),
             selected_layer: self.selected_layer.clone(),
             selected_tileset: self.selected_tileset.clone(),
             selected_tile: self.selected_tile,
@@ -295,7 +302,23 @@ impl Editor {
             is_user_map: self.map_resource.meta.is_user_map,
             is_tiled_map: self.map_resource.meta.is_tiled_map,
             should_snap_to_grid: self.should_snap_to_grid,
-        }
+        };
+
+        ctx.available_tools = self.tools.get_available(self.get_map(), &ctx);
+
+        ctx
+    }
+
+    /// Call `f` with the instance of the tool with the specified id, the map and the current
+    /// context
+    fn with_tool<F, R>(&mut self, id: &TypeId, f: F) -> R
+    where
+        F: FnOnce(&mut dyn EditorTool, &Map, &EditorContext) -> R,
+    {
+        let ctx = self.get_context();
+        let tool = self.tools.get_tool_of_id_mut(id);
+
+        f(tool, &self.map_resource.map, &ctx)
     }
 
     fn update_context(&mut self) {
@@ -335,7 +358,7 @@ impl Editor {
         }
 
         if let Some(tool_id) = &self.selected_tool {
-            let tool = get_tool_instance_of_id(tool_id);
+            let tool = self.tools.get_tool_of_id(tool_id);
             let ctx = self.get_context();
             if !tool.is_available(self.get_map(), &ctx) {
                 self.selected_tool = None;
@@ -530,8 +553,10 @@ impl Editor {
                 id,
                 kind,
                 position,
+                properties,
             } => {
-                let action = UpdateObjectAction::new(layer_id, index, id, kind, position);
+                let action =
+                    UpdateObjectAction::new(layer_id, index, id, kind, position, properties);
                 res = self
                     .history
                     .apply(Box::new(action), &mut self.map_resource.map);
@@ -783,14 +808,8 @@ impl Node for Editor {
             (is_over_gui, is_over_context_menu)
         };
 
-        if let Some(id) = &node.selected_tool {
-            let res = {
-                let tool = get_tool_instance_of_id(id);
-                let map = node.get_map();
-                let ctx = node.get_context();
-
-                tool.update(map, &ctx)
-            };
+        if let Some(id) = node.selected_tool {
+            let res = node.with_tool(&id, |tool, map, ctx| tool.update(map, ctx));
 
             if let Some(action) = res {
                 node.apply_action(action);
@@ -804,14 +823,18 @@ impl Node for Editor {
             }
 
             if !is_cursor_over_gui {
-                if let Some(id) = &node.selected_tool {
-                    let ctx = node.get_context();
-                    let tool = get_tool_instance_of_id(id);
-                    let params = tool.get_params();
-                    if !node.previous_input.action || params.is_continuous {
-                        if let Some(action) = tool.get_action(node.get_map(), &ctx) {
-                            node.apply_action(action);
+                if let Some(id) = node.selected_tool {
+                    let was_action = node.previous_input.action;
+                    let res = node.with_tool(&id, |tool, map, ctx| {
+                        if !was_action || tool.get_params().is_continuous {
+                            tool.get_action(map, ctx)
+                        } else {
+                            None
                         }
+                    });
+
+                    if let Some(action) = res {
+                        node.apply_action(action);
                     }
                 } else if node.previous_input.action {
                     if node.cursor_position == node.previous_cursor_position
@@ -1092,6 +1115,7 @@ impl Node for Editor {
                         index,
                         layer_id,
                         position,
+                        properties: None,
                     };
 
                     node.apply_action(action);
@@ -1490,6 +1514,9 @@ impl Node for Editor {
                                             },
                                         );
                                     }
+                                    ITEM_SPAWNER_ID => {
+                                        label = Some("ITEM SPAWNER".to_string());
+                                    }
                                     _ => {
                                         label = Some("INVALID OBJECT ID".to_string());
                                     }
@@ -1567,10 +1594,8 @@ impl Node for Editor {
 
         let mut res = None;
 
-        if let Some(tool_id) = &node.selected_tool {
-            let tool = get_tool_instance_of_id(tool_id);
-            let ctx = node.get_context();
-            res = tool.draw_cursor(node.get_map(), &ctx);
+        if let Some(tool_id) = node.selected_tool {
+            res = node.with_tool(&tool_id, |tool, map, ctx| tool.draw_cursor(map, ctx));
         }
 
         {
@@ -1630,6 +1655,7 @@ fn get_object_size(object: &MapObject) -> Vec2 {
                 let texture_res = resources.textures.get(FISH_SCHOOL_ICON_TEXTURE_ID).unwrap();
                 res = texture_res.meta.frame_size;
             }
+            ITEM_SPAWNER_ID => label = Some("ITEM SPAWNER".to_string()),
             _ => label = Some("INVALID OBJECT ID".to_string()),
         },
     }
@@ -10,44 +10,43 @@ use macroquad::prelude::*;
 
 use super::{EditorAction, EditorContext, Map};
 
-static mut TOOL_INSTANCES: Option<HashMap<TypeId, Box<dyn EditorTool>>> = None;
+/// This holds the tool instances of an editor
+#[derive(Default)]
+pub struct EditorTools {
+    instances: HashMap<TypeId, Box<dyn EditorTool>>,
+}
 
-unsafe fn get_tool_instance_directory() -> &'static mut HashMap<TypeId, Box<dyn EditorTool>> {
-    if TOOL_INSTANCES.is_none() {
-        TOOL_INSTANCES = Some(HashMap::new());
+impl EditorTools {
+    pub fn new() -> Self {
+        EditorTools {
+            instances: HashMap::new(),
+        }
     }
 
-    TOOL_INSTANCES.as_mut().unwrap()
-}
-
-pub fn add_tool_instance<T: EditorTool + 'static>(tool: T) -> TypeId {
-    let id = TypeId::of::<T>();
-    unsafe { get_tool_instance_directory() }.insert(id, Box::new(tool));
-    id
-}
+    #[must_use]
+    pub fn with_tool<T: EditorTool + 'static>(self, tool: T) -> Self {
+        let mut instances = self.instances;
+        instances.insert(TypeId::of::<T>(), Box::new(tool));
 
-pub fn get_tool_instance_of_id(id: &TypeId) -> &'static mut dyn EditorTool {
-    unsafe { get_tool_instance_directory() }
-        .get_mut(id)
-        .unwrap()
-        .as_mut()
-}
+        EditorTools { instances }
+    }
 
-// TODO: Cast to T
-pub fn get_tool_instance<T: EditorTool + 'static>() -> &'static mut dyn EditorTool {
-    let id = TypeId::of::<T>();
-    get_tool_instance_of_id(&id)
-}
+    pub fn get_tool_of_id(&self, id: &TypeId) -> &dyn EditorTool {
+        self.instances.get(id).unwrap().as_ref()
+    }
 
-#[allow(dead_code)]
-pub fn get_tool_params_of_id(id: &TypeId) -> &'static EditorToolParams {
-    get_tool_instance_of_id(id).get_params()
-}
+    pub fn get_tool_of_id_mut(&mut self, id: &TypeId) -> &mut dyn EditorTool {
+        self.instances.get_mut(id).unwrap().as_mut()
+    }
 
-#[allow(dead_code)]
-pub fn get_tool_params<T: EditorTool + 'static>() -> &'static EditorToolParams {
-    let id = TypeId::of::<T>();
-    get_tool_params_of_id(&id)
+    /// Returns the ids and params of the tools that are available in the specified context
+    pub fn get_available(&self, map: &Map, ctx: &EditorContext) -> Vec<(TypeId, EditorToolParams)> {
+        self.instances
+            .iter()
+            .filter(|(_, tool)| tool.is_available(map, ctx))
+            .map(|(id, tool)| (*id, tool.get_params().clone()))
+            .collect()
+    }
 }
 
 pub const DEFAULT_TOOL_ICON_TEXTURE_ID: &str = "default_tool_icon";
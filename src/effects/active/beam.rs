@@ -0,0 +1,163 @@
+use macroquad::experimental::collections::storage;
+use macroquad::prelude::*;
+
+use hecs::{Entity, World};
+use macroquad_platformer::Tile;
+
+use core::math::segment_rect_intersection;
+use core::Transform;
+
+use crate::ecs::get_resource;
+use crate::game::get_delta_time;
+use crate::player::{Player, PlayerState};
+use crate::{CollisionWorld, PhysicsBody, Resources};
+
+/// The distance between each point that is checked for solid tiles, when casting a beam
+const BEAM_RAYCAST_STEP: f32 = 2.0;
+
+/// This is the drawable part of a beam. The collision checks are done once, when the beam is
+/// spawned, so this is only used to draw the beam until it has faded out, at which point it is
+/// despawned by `update_beams`.
+pub struct Beam {
+    pub end: Vec2,
+    pub width: f32,
+    pub color: Color,
+    pub texture_id: Option<String>,
+    pub fade_duration: f32,
+    pub timer: f32,
+}
+
+/// Casts a beam from `origin`, in `direction`, until it hits a solid tile or reaches `range`.
+/// Players that are hit, other than `owner`, are returned in the order they were hit, along with
+/// the end point of the beam. If the beam hits more than `pierce` players it will stop at the
+/// last one of these.
+pub fn cast_beam(
+    world: &World,
+    owner: Entity,
+    origin: Vec2,
+    direction: Vec2,
+    range: f32,
+    width: f32,
+    pierce: u32,
+) -> (Vec2, Vec<Entity>) {
+    let direction = direction.normalize_or_zero();
+
+    let mut distance = 0.0;
+
+    {
+        let collision_world = get_resource::<CollisionWorld>(world);
+
+        while distance < range {
+            let next = (distance + BEAM_RAYCAST_STEP).min(range);
+            let point = origin + direction * next;
+
+            if collision_world.collide_solids(point, 1, 1) == Tile::Solid {
+                break;
+            }
+
+            distance = next;
+        }
+    }
+
+    let mut end = origin + direction * distance;
+
+    let mut hits = world
+        .query::<(&Player, &Transform, &PhysicsBody)>()
+        .iter()
+        .filter(|(e, (player, _, _))| *e != owner && player.state != PlayerState::Dead)
+        .filter_map(|(e, (_, transform, body))| {
+            let mut rect = body.as_rect(transform.position);
+            rect.x -= width / 2.0;
+            rect.y -= width / 2.0;
+            rect.w += width;
+            rect.h += width;
+
+            segment_rect_intersection(origin, end, &rect).map(|t| (e, t))
+        })
+        .collect::<Vec<_>>();
+
+    hits.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+
+    let max_hits = pierce as usize + 1;
+    if hits.len() >= max_hits {
+        hits.truncate(max_hits);
+
+        let (last, _) = hits[max_hits - 1];
+        let rect = {
+            let transform = world.get::<Transform>(last).unwrap();
+            let body = world.get::<PhysicsBody>(last).unwrap();
+            body.as_rect(transform.position)
+        };
+
+        let hit_distance = (rect.center() - origin).dot(direction).clamp(0.0, distance);
+        end = origin + direction * hit_distance;
+    }
+
+    (end, hits.into_iter().map(|(e, _)| e).collect())
+}
+
+pub fn spawn_beam(world: &mut World, origin: Vec2, beam: Beam) -> Entity {
+    world.spawn((Transform::from(origin), beam))
+}
+
+pub fn update_beams(world: &mut World) {
+    let mut to_remove = Vec::new();
+
+    let dt = get_delta_time(world);
+
+    for (e, beam) in world.query_mut::<&mut Beam>() {
+        beam.timer += dt;
+
+        if beam.timer >= beam.fade_duration {
+            to_remove.push(e);
+        }
+    }
+
+    for e in to_remove.drain(0..) {
+        world.despawn(e).unwrap();
+    }
+}
+
+pub fn draw_beams(world: &mut World) {
+    for (_, (transform, beam)) in world.query::<(&Transform, &Beam)>().iter() {
+        let start = transform.position;
+
+        let mut color = beam.color;
+        color.a *= 1.0 - beam.timer / beam.fade_duration;
+
+        if let Some(texture_id) = &beam.texture_id {
+            let resources = storage::get::<Resources>();
+            let texture_res = resources.textures.get(texture_id).unwrap();
+
+            let frame_size = texture_res.frame_size();
+
+            let length = start.distance(beam.end);
+            let direction = (beam.end - start).normalize_or_zero();
+            let rotation = direction.y.atan2(direction.x);
+
+            // The texture is repeated along the beam, with the last segment being cut short
+            let mut offset = 0.0;
+            while offset < length {
+                let segment_length = frame_size.x.min(length - offset);
+                let center = start + direction * (offset + segment_length / 2.0);
+
+                draw_texture_ex(
+                    texture_res.texture,
+                    center.x - segment_length / 2.0,
+                    center.y - beam.width / 2.0,
+                    color,
+                    DrawTextureParams {
+                        dest_size: Some(vec2(segment_length, beam.width)),
+                        source: Some(Rect::new(0.0, 0.0, segment_length, frame_size.y)),
+                        rotation,
+                        ..Default::default()
+                    },
+                );
+
+                offset += frame_size.x;
+            }
+        } else {
+            draw_line(start.x, start.y, beam.end.x, beam.end.y, beam.width, color);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use macroquad::prelude::*;
+
+use hecs::{Entity, World};
+
+use core::Transform;
+
+use crate::effects::active::instantiate_active_effect;
+use crate::game::get_delta_time;
+use crate::player::Player;
+use crate::ActiveEffectMetadata;
+
+/// An active effect that will be instantiated when its `delay` has passed
+pub struct DelayedEffect {
+    pub meta: ActiveEffectMetadata,
+    pub owner: Entity,
+    pub spawner: Entity,
+    /// The origin that the effect was scheduled with. This is used if the spawner no longer
+    /// exists, when the effect is instantiated.
+    pub origin: Vec2,
+    /// The origin of the effect, relative to the position of the spawner, so that delayed effects
+    /// follow the weapon or item that spawned them
+    pub offset: Option<Vec2>,
+    /// The direction that the owner was facing when the effect was scheduled, used to flip the
+    /// offset if the owner turns around before the effect is instantiated
+    pub is_facing_left: bool,
+    /// The id of the weapon or item that the effect was spawned by, if any
+    pub source_id: Option<String>,
+    pub timer: f32,
+}
+
+pub fn spawn_delayed_effect(
+    world: &mut World,
+    owner: Entity,
+    spawner: Entity,
+    origin: Vec2,
+    source_id: Option<String>,
+    meta: ActiveEffectMetadata,
+) -> Entity {
+    let offset = world
+        .get::<Transform>(spawner)
+        .map(|transform| origin - transform.position)
+        .ok();
+
+    let is_facing_left = world
+        .get::<Player>(owner)
+        .map(|player| player.is_facing_left)
+        .unwrap_or_default();
+
+    world.spawn((DelayedEffect {
+        meta,
+        owner,
+        spawner,
+        origin,
+        offset,
+        is_facing_left,
+        source_id,
+        timer: 0.0,
+    },))
+}
+
+pub fn update_delayed_effects(world: &mut World) {
+    let dt = get_delta_time(world);
+
+    let mut to_instantiate = Vec::new();
+
+    for (e, effect) in world.query_mut::<&mut DelayedEffect>() {
+        effect.timer += dt;
+
+        if effect.timer >= effect.meta.delay {
+            to_instantiate.push(e);
+        }
+    }
+
+    for e in to_instantiate {
+        let effect = world.remove_one::<DelayedEffect>(e).unwrap();
+        world.despawn(e).unwrap();
+
+        let is_facing_left = world
+            .get::<Player>(effect.owner)
+            .map(|player| player.is_facing_left)
+            .unwrap_or(effect.is_facing_left);
+
+        let spawner_position = world
+            .get::<Transform>(effect.spawner)
+            .map(|transform| transform.position)
+            .ok();
+
+        let (spawner, origin) = match (spawner_position, effect.offset) {
+            (Some(position), Some(mut offset)) => {
+                if is_facing_left != effect.is_facing_left {
+                    offset.x = -offset.x;
+                }
+
+                (effect.spawner, position + offset)
+            }
+            _ if world.contains(effect.spawner) => (effect.spawner, effect.origin),
+            // The spawner, like a triggered effect, might have been despawned, in which case
+            // the owner is used as spawner in stead
+            _ => (effect.owner, effect.origin),
+        };
+
+        let meta = ActiveEffectMetadata {
+            delay: 0.0,
+            ..effect.meta
+        };
+
+        let res =
+            instantiate_active_effect(world, effect.owner, spawner, origin, effect.source_id, meta);
+
+        if let Err(err) = res {
+            #[cfg(debug_assertions)]
+            println!("WARNING: {}", err);
+        }
+    }
+}
@@ -9,20 +9,30 @@ use serde::{Deserialize, Serialize};
 use core::math::{deg_to_rad, rotate_vector, IsZero};
 use core::Result;
 
+use crate::ecs::get_resource_mut;
 use crate::game::play_sound_effect;
-use crate::items::spawn_item;
-use crate::Resources;
+use crate::game::rng::Rng;
+use crate::items::{spawn_item, Weapon};
+use crate::{Item, Resources};
 use crate::{PassiveEffectInstance, PassiveEffectMetadata};
 
+pub mod beam;
+pub mod delayed;
 pub mod projectiles;
 pub mod triggered;
+pub mod zone;
 
 pub use triggered::{TriggeredEffectMetadata, TriggeredEffectTrigger};
 
-use crate::effects::active::projectiles::{spawn_projectile, ProjectileParams};
+use crate::effects::active::beam::{cast_beam, spawn_beam, Beam};
+use crate::effects::active::delayed::spawn_delayed_effect;
+use crate::effects::active::projectiles::{
+    spawn_projectile, Projectile, ProjectileBehavior, ProjectileHoming, ProjectileParams,
+};
 use crate::effects::active::triggered::{spawn_triggered_effect, TriggeredEffect};
+use crate::effects::active::zone::{spawn_zone, Zone, ZoneMetadata};
 use crate::particles::ParticleEmitterMetadata;
-use crate::player::{on_player_damage, Player};
+use crate::player::{apply_status_effect, on_player_damage, Player, StatusEffectMetadata};
 use crate::PhysicsBody;
 use core::Transform;
 pub use projectiles::ProjectileKind;
@@ -40,28 +50,59 @@ struct RectCollider {
     ttl_timer: f32,
 }
 
+/// Spawn an active effect. If the effect has a `delay`, it will be scheduled and instantiated by
+/// `update_delayed_effects`, once the delay has passed.
 pub fn spawn_active_effect(
     world: &mut World,
     owner: Entity,
     spawner: Entity,
     origin: Vec2,
     params: ActiveEffectMetadata,
+) -> Result<()> {
+    let source_id = get_source_id(world, spawner);
+
+    if params.delay > 0.0 {
+        spawn_delayed_effect(world, owner, spawner, origin, source_id, params);
+        Ok(())
+    } else {
+        instantiate_active_effect(world, owner, spawner, origin, source_id, params)
+    }
+}
+
+/// Instantiate an active effect immediately, ignoring its `delay`. The source id is passed
+/// separately, as the spawner of a delayed effect may no longer exist.
+pub fn instantiate_active_effect(
+    world: &mut World,
+    owner: Entity,
+    spawner: Entity,
+    origin: Vec2,
+    source_id: Option<String>,
+    params: ActiveEffectMetadata,
 ) -> Result<()> {
     let is_facing_left = {
         let player = world.get::<Player>(owner).unwrap();
         player.is_facing_left
     };
 
+    // If the effect is spawned by a weapon that can be aimed, its direction is rotated by the
+    // aim angle
+    let aim_rotation = world
+        .get::<Weapon>(spawner)
+        .map(|weapon| weapon.get_aim_rotation(is_facing_left))
+        .unwrap_or_default();
+
     if let Some(id) = &params.sound_effect_id {
         play_sound_effect(id, 1.0);
     }
 
     let mut damage = Vec::new();
+    let mut status_effects_to_apply = Vec::new();
 
     match *params.kind {
         ActiveEffectKind::CircleCollider {
             radius,
             passive_effects,
+            status_effects,
             is_lethal,
             is_explosion,
         } => {
@@ -91,6 +132,11 @@ pub fn spawn_active_effect(
                                 let effect_instance = PassiveEffectInstance::new(None, meta);
                                 player.passive_effects.push(effect_instance);
                             }
+
+                            let is_from_left = origin.x < transform.position.x;
+                            for meta in status_effects.iter().cloned() {
+                                status_effects_to_apply.push((e, is_from_left, meta));
+                            }
                         }
                     } else if is_explosion {
                         if let Ok(mut effect) = world.get_mut::<TriggeredEffect>(e) {
@@ -109,6 +155,7 @@ pub fn spawn_active_effect(
             height,
             is_lethal,
             passive_effects,
+            status_effects,
         } => {
             let mut rect = Rect::new(origin.x, origin.y, width, height);
             if is_facing_left {
@@ -141,12 +188,37 @@ pub fn spawn_active_effect(
                             let effect_instance = PassiveEffectInstance::new(None, meta);
                             player.passive_effects.push(effect_instance);
                         }
+
+                        for meta in status_effects.iter().cloned() {
+                            status_effects_to_apply.push((e, !is_facing_left, meta));
+                        }
                     }
                 }
             }
         }
-        ActiveEffectKind::TriggeredEffect { meta } => {
-            spawn_triggered_effect(world, owner, origin, is_facing_left, *meta)?;
+        ActiveEffectKind::TriggeredEffect { mut meta } => {
+            // The velocity is flipped when the effect is spawned, so it is rotated as if the
+            // owner is facing right
+            if aim_rotation != 0.0 {
+                let rotation = if is_facing_left {
+                    -aim_rotation
+                } else {
+                    aim_rotation
+                };
+
+                meta.velocity = rotate_vector(meta.velocity, rotation);
+            }
+
+            let entity = spawn_triggered_effect(world, owner, origin, is_facing_left, *meta)?;
+
+            let mut effect = world.get_mut::<TriggeredEffect>(entity)?;
+            effect.source_id = source_id.clone();
+        }
+        ActiveEffectKind::Zone { meta } => {
+            let entity = spawn_zone(world, owner, origin, is_facing_left, *meta);
+
+            let mut zone = world.get_mut::<Zone>(entity)?;
+            zone.source_id = source_id.clone();
         }
         ActiveEffectKind::Projectile {
             kind,
@@ -155,7 +227,13 @@ pub fn spawn_active_effect(
             spread,
             is_lethal,
             passive_effects,
+            status_effects,
             particles,
+            gravity,
+            bounces,
+            restitution,
+            homing,
+            pierce,
         } => {
             let mut velocity = Vec2::ZERO;
             if is_facing_left {
@@ -164,9 +242,11 @@ pub fn spawn_active_effect(
                 velocity.x = speed
             }
 
+            velocity = rotate_vector(velocity, aim_rotation);
+
             if spread != 0.0 {
                 let rad = deg_to_rad(spread);
-                let spread = rand::gen_range(-rad, rad);
+                let spread = get_resource_mut::<Rng>(world).gen_range(-rad, rad);
 
                 velocity = rotate_vector(velocity, spread);
             }
@@ -181,10 +261,94 @@ pub fn spawn_active_effect(
                 ProjectileParams {
                     is_lethal,
                     passive_effects,
+                    status_effects,
                     particle_effects: particles,
+                    source_id: source_id.clone(),
+                    behavior: ProjectileBehavior {
+                        gravity,
+                        bounces,
+                        restitution,
+                        homing,
+                        pierce,
+                    },
+                },
+            );
+        }
+        ActiveEffectKind::Beam {
+            range,
+            width,
+            pierce,
+            is_lethal,
+            passive_effects,
+            status_effects,
+            color,
+            texture_id,
+            fade_duration,
+        } => {
+            let mut direction = if is_facing_left {
+                vec2(-1.0, 0.0)
+            } else {
+                vec2(1.0, 0.0)
+            };
+
+            direction = rotate_vector(direction, aim_rotation);
+
+            let (end, hits) = cast_beam(world, owner, origin, direction, range, width, pierce);
+
+            for e in hits {
+                if is_lethal {
+                    damage.push((owner, e));
+                }
+
+                let mut player = world.get_mut::<Player>(e).unwrap();
+                for meta in passive_effects.clone().into_iter() {
+                    let effect_instance = PassiveEffectInstance::new(None, meta);
+                    player.passive_effects.push(effect_instance);
+                }
+
+                for meta in status_effects.iter().cloned() {
+                    status_effects_to_apply.push((e, !is_facing_left, meta));
+                }
+            }
+
+            spawn_beam(
+                world,
+                origin,
+                Beam {
+                    end,
+                    width,
+                    color: color.unwrap_or(color::WHITE),
+                    texture_id,
+                    fade_duration,
+                    timer: 0.0,
                 },
             );
         }
+        ActiveEffectKind::Sequence { effects } => {
+            for meta in effects {
+                if meta.delay > 0.0 {
+                    spawn_delayed_effect(world, owner, spawner, origin, source_id.clone(), meta);
+                } else {
+                    instantiate_active_effect(
+                        world,
+                        owner,
+                        spawner,
+                        origin,
+                        source_id.clone(),
+                        meta,
+                    )?;
+                }
+            }
+        }
+        ActiveEffectKind::Detonate => {
+            for (_, effect) in world.query_mut::<&mut TriggeredEffect>() {
+                if effect.owner == owner && effect.source_id == source_id {
+                    effect.is_triggered = true;
+                    effect.triggered_by = Some(owner);
+                    effect.should_override_delay = true;
+                }
+            }
+        }
         ActiveEffectKind::SpawnItem {
             item,
             offset,
@@ -215,13 +379,40 @@ pub fn spawn_active_effect(
         }
     }
 
+    for (player_entity, is_from_left, meta) in status_effects_to_apply {
+        apply_status_effect(world, player_entity, is_from_left, meta);
+    }
+
     for (damage_from_entity, damage_to_entity) in damage.drain(0..) {
-        on_player_damage(world, damage_from_entity, damage_to_entity);
+        on_player_damage(
+            world,
+            damage_from_entity,
+            damage_to_entity,
+            source_id.clone(),
+        );
     }
 
     Ok(())
 }
 
+/// Returns the id of the weapon or item that an entity originates from, if any. Triggered effects,
+/// projectiles and zones hold the id of the weapon or item that spawned them.
+pub fn get_source_id(world: &World, entity: Entity) -> Option<String> {
+    if let Ok(weapon) = world.get::<Weapon>(entity) {
+        Some(weapon.id.clone())
+    } else if let Ok(item) = world.get::<Item>(entity) {
+        Some(item.id.clone())
+    } else if let Ok(effect) = world.get::<TriggeredEffect>(entity) {
+        effect.source_id.clone()
+    } else if let Ok(projectile) = world.get::<Projectile>(entity) {
+        projectile.source_id.clone()
+    } else if let Ok(zone) = world.get::<Zone>(entity) {
+        zone.source_id.clone()
+    } else {
+        None
+    }
+}
+
 /// This holds all the common parameters, available to all implementations, as well as specialized
 /// parameters, in the `ActiveEffectKind`.
 // NOTE: We would prefer to `serde(deny_unknown_fields)` here, but we are blocked by this issue:
@@ -241,12 +432,22 @@ pub struct ActiveEffectMetadata {
     pub sound_effect_id: Option<String>,
     /// The delay between instantiation of the effect is requested and the actual instantiation.
     /// This will delay the entire effect, including sound effects, particle effects and anything
-    /// drawn as a result of the effect, so in most cases it is probably better to use a
-    /// `TriggeredEffect`, with a `timed_trigger` value, in stead of using this.
-    #[serde(default)]
+    /// drawn as a result of the effect. The effect will follow the weapon or item that spawned it,
+    /// while it is delayed, so this can be used for things like burst fire.
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
     pub delay: f32,
 }
 
+impl ActiveEffectMetadata {
+    /// Returns a copy of the effect, with its speed and size multiplied by the given factors.
+    /// This is used to scale the effects of charge weapons.
+    pub fn scaled(&self, speed_factor: f32, size_factor: f32) -> Self {
+        let mut res = self.clone();
+        res.kind.scale(speed_factor, size_factor);
+        res
+    }
+}
+
 /// This should hold implementations of the commonly used weapon effects, that see usage spanning
 /// many different weapon implementations.
 ///
@@ -262,6 +463,9 @@ pub enum ActiveEffectKind {
         /// This contains any passive effects that will be spawned on collision
         #[serde(default, skip_serializing_if = "Vec::is_empty")]
         passive_effects: Vec<PassiveEffectMetadata>,
+        /// This contains any status effects that will be applied on collision
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        status_effects: Vec<StatusEffectMetadata>,
         /// If `true` the effect will do damage to any player it hits
         #[serde(
             default = "core::json::default_true",
@@ -284,12 +488,21 @@ pub enum ActiveEffectKind {
         /// This contains any passive effects that will be spawned on collision
         #[serde(default, skip_serializing_if = "Vec::is_empty")]
         passive_effects: Vec<PassiveEffectMetadata>,
+        /// This contains any status effects that will be applied on collision
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        status_effects: Vec<StatusEffectMetadata>,
     },
     /// Spawn a trigger that will set of another effect if its trigger conditions are met.
     TriggeredEffect {
         #[serde(flatten)]
         meta: Box<TriggeredEffectMetadata>,
     },
+    /// Spawn an area that stays in the world for a while, affecting any players inside it, at a
+    /// set interval.
+    Zone {
+        #[serde(flatten)]
+        meta: Box<ZoneMetadata>,
+    },
     /// Spawn a projectile.
     /// This would typically be used for things like a gun.
     Projectile {
@@ -308,10 +521,71 @@ pub enum ActiveEffectKind {
         /// This contains any passive effects that will be spawned on collision
         #[serde(default, skip_serializing_if = "Vec::is_empty")]
         passive_effects: Vec<PassiveEffectMetadata>,
+        /// This contains any status effects that will be applied on collision
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        status_effects: Vec<StatusEffectMetadata>,
         /// Particle effects that will be attached to the projectile
         #[serde(default, skip_serializing_if = "Vec::is_empty")]
         particles: Vec<ParticleEmitterMetadata>,
+        /// This is added to the vertical velocity of the projectile every step, making it arc
+        #[serde(default, skip_serializing_if = "f32::is_zero")]
+        gravity: f32,
+        /// The number of times the projectile will bounce off solid tiles, before it is removed
+        #[serde(default, skip_serializing_if = "u32::is_zero")]
+        bounces: u32,
+        /// The velocity of the projectile is multiplied by this, every time it bounces
+        #[serde(default = "default_restitution")]
+        restitution: f32,
+        /// If this is set, the projectile will turn towards the nearest enemy player
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        homing: Option<ProjectileHoming>,
+        /// The number of players the projectile can pass through, before it is removed
+        #[serde(default, skip_serializing_if = "u32::is_zero")]
+        pierce: u32,
     },
+    /// Cast a beam in the direction the owner is facing, that hits players instantly.
+    /// The beam will stop at solid tiles and at the first player it hits, unless `pierce` is set.
+    Beam {
+        range: f32,
+        width: f32,
+        /// The number of players the beam can pass through, before it stops
+        #[serde(default, skip_serializing_if = "u32::is_zero")]
+        pierce: u32,
+        /// If `true` the effect will do damage to any player it hits
+        #[serde(
+            default = "core::json::default_true",
+            skip_serializing_if = "core::json::is_true"
+        )]
+        is_lethal: bool,
+        /// This contains any passive effects that will be spawned on collision
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        passive_effects: Vec<PassiveEffectMetadata>,
+        /// This contains any status effects that will be applied on collision
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        status_effects: Vec<StatusEffectMetadata>,
+        /// The color of the beam. If a texture is specified, this will be used to tint it.
+        #[serde(
+            default,
+            with = "core::json::color_opt",
+            skip_serializing_if = "Option::is_none"
+        )]
+        color: Option<Color>,
+        /// If this is specified, the texture will be repeated along the beam, in stead of drawing
+        /// a line
+        #[serde(default, rename = "texture", skip_serializing_if = "Option::is_none")]
+        texture_id: Option<String>,
+        /// The time it takes for the beam to fade out, after it has been cast
+        #[serde(default = "default_beam_fade_duration")]
+        fade_duration: f32,
+    },
+    /// Spawn a list of effects. The `delay` of each effect is counted from when the sequence is
+    /// instantiated, so this can be used to chain effects, like a muzzle flash followed by a burst
+    /// of projectiles.
+    Sequence { effects: Vec<ActiveEffectMetadata> },
+    /// Trigger all the triggered effects that the owner has deployed with the same weapon or item,
+    /// ignoring their trigger conditions and delays. This can be used for things like airburst
+    /// grenades or remote detonators.
+    Detonate,
     SpawnItem {
         item: String,
         #[serde(default, with = "core::json::vec2_def")]
@@ -321,6 +595,63 @@ pub enum ActiveEffectKind {
     },
 }
 
+impl ActiveEffectKind {
+    /// Multiplies the speed and size of the effect by the given factors. Sprite projectiles are
+    /// only resized if they have a `size` specified.
+    pub fn scale(&mut self, speed_factor: f32, size_factor: f32) {
+        match self {
+            ActiveEffectKind::CircleCollider { radius, .. } => {
+                *radius *= size_factor;
+            }
+            ActiveEffectKind::RectCollider { width, height, .. } => {
+                *width *= size_factor;
+                *height *= size_factor;
+            }
+            ActiveEffectKind::TriggeredEffect { meta } => {
+                meta.velocity *= speed_factor;
+            }
+            ActiveEffectKind::Projectile { kind, speed, .. } => {
+                *speed *= speed_factor;
+
+                match kind {
+                    ProjectileKind::Circle { radius, .. } => {
+                        *radius *= size_factor;
+                    }
+                    ProjectileKind::Rect { width, height, .. } => {
+                        *width *= size_factor;
+                        *height *= size_factor;
+                    }
+                    ProjectileKind::Sprite { params, .. } => {
+                        if let Some(size) = &mut params.size {
+                            *size *= size_factor;
+                        }
+                    }
+                }
+            }
+            ActiveEffectKind::Beam { width, .. } => {
+                *width *= size_factor;
+            }
+            ActiveEffectKind::Zone { meta } => {
+                meta.size *= size_factor;
+            }
+            ActiveEffectKind::Sequence { effects } => {
+                for effect in effects {
+                    effect.kind.scale(speed_factor, size_factor);
+                }
+            }
+            ActiveEffectKind::Detonate | ActiveEffectKind::SpawnItem { .. } => {}
+        }
+    }
+}
+
+fn default_restitution() -> f32 {
+    1.0
+}
+
+fn default_beam_fade_duration() -> f32 {
+    0.25
+}
+
 pub fn debug_draw_active_effects(world: &mut World) {
     let mut to_remove = Vec::new();
 
@@ -359,6 +690,11 @@ pub fn debug_draw_active_effects(world: &mut World) {
         }
     }
 
+    for (_, (transform, zone)) in world.query_mut::<(&Transform, &Zone)>() {
+        let rect = zone.get_rect(transform.position);
+        draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, color::PURPLE);
+    }
+
     for (_, (transform, body, effect)) in
         world.query_mut::<(&Transform, &PhysicsBody, &TriggeredEffect)>()
     {
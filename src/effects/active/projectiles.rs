@@ -7,16 +7,26 @@ use macroquad_platformer::Tile;
 
 use serde::{Deserialize, Serialize};
 
+use core::math::{deg_to_rad, rotate_vector, sweep_rect, sweep_rect_grid, IsZero, SweepHit};
+
+use crate::ecs::get_resource;
+use crate::effects::active::get_source_id;
 use crate::effects::active::triggered::TriggeredEffect;
 use crate::effects::TriggeredEffectTrigger;
 use crate::particles::{ParticleEmitter, ParticleEmitterMetadata};
-use crate::player::{on_player_damage, Player, PlayerState};
-use crate::{CollisionWorld, PhysicsBody, Resources, RigidBody, RigidBodyParams, SpriteMetadata};
-use crate::{Drawable, PassiveEffectInstance, PassiveEffectMetadata, SpriteParams};
+use crate::player::{
+    apply_status_effect, on_player_damage, Player, PlayerState, StatusEffectMetadata,
+};
+use crate::{CollisionWorld, Map, PhysicsBody, Resources, RigidBody, RigidBodyParams};
+use crate::{Drawable, PassiveEffectInstance, PassiveEffectMetadata, SpriteMetadata, SpriteParams};
 use core::Transform;
 
 const PROJECTILE_DRAW_ORDER: u32 = 1;
 
+/// The distance a projectile is moved away from a tile that it bounces off, so that it is not
+/// touching the tile on the next step
+const BOUNCE_SEPARATION: f32 = 0.01;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -42,6 +52,54 @@ pub enum ProjectileKind {
     },
 }
 
+/// Optional behaviors that change how a projectile moves and what happens when it hits something.
+/// All values are per fixed update step, like the velocity of the projectile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectileBehavior {
+    /// This is added to the vertical velocity of the projectile every step, making it arc
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub gravity: f32,
+    /// The number of times the projectile will bounce off solid tiles, before it is removed
+    #[serde(default, skip_serializing_if = "u32::is_zero")]
+    pub bounces: u32,
+    /// The velocity of the projectile is multiplied by this, every time it bounces
+    #[serde(default = "default_restitution")]
+    pub restitution: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub homing: Option<ProjectileHoming>,
+    /// The number of players the projectile can pass through, before it is removed
+    #[serde(default, skip_serializing_if = "u32::is_zero")]
+    pub pierce: u32,
+}
+
+impl Default for ProjectileBehavior {
+    fn default() -> Self {
+        ProjectileBehavior {
+            gravity: 0.0,
+            bounces: 0,
+            restitution: default_restitution(),
+            homing: None,
+            pierce: 0,
+        }
+    }
+}
+
+fn default_restitution() -> f32 {
+    1.0
+}
+
+/// This will make the projectile turn towards the nearest enemy player
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectileHoming {
+    /// The maximum number of degrees the projectile can turn, every step
+    pub turn_rate: f32,
+    /// If this is set, only players within this distance will be targeted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<f32>,
+}
+
 pub struct Projectile {
     pub kind: ProjectileKind,
     pub owner: Entity,
@@ -49,6 +107,17 @@ pub struct Projectile {
     pub range: f32,
     pub is_lethal: bool,
     pub passive_effects: Vec<PassiveEffectMetadata>,
+    pub status_effects: Vec<StatusEffectMetadata>,
+    /// The particle effects that were attached to the projectile when it was spawned
+    pub particle_effects: Vec<ParticleEmitterMetadata>,
+    /// The id of the weapon or item that the projectile was fired from, if any
+    pub source_id: Option<String>,
+    pub behavior: ProjectileBehavior,
+    pub distance_traveled: f32,
+    pub bounce_cnt: u32,
+    /// The players that have been hit by the projectile, so that piercing projectiles only hit
+    /// each player once
+    pub hit_players: Vec<Entity>,
 }
 
 impl Projectile {
@@ -67,6 +136,13 @@ impl Projectile {
             range,
             is_lethal,
             passive_effects: passive_effects.to_vec(),
+            status_effects: Vec::new(),
+            particle_effects: Vec::new(),
+            source_id: None,
+            behavior: ProjectileBehavior::default(),
+            distance_traveled: 0.0,
+            bounce_cnt: 0,
+            hit_players: Vec::new(),
         }
     }
 }
@@ -75,7 +151,11 @@ impl Projectile {
 pub struct ProjectileParams {
     pub is_lethal: bool,
     pub passive_effects: Vec<PassiveEffectMetadata>,
+    pub status_effects: Vec<StatusEffectMetadata>,
     pub particle_effects: Vec<ParticleEmitterMetadata>,
+    /// The id of the weapon or item that the projectile is fired from, if any
+    pub source_id: Option<String>,
+    pub behavior: ProjectileBehavior,
 }
 
 impl Default for ProjectileParams {
@@ -83,7 +163,10 @@ impl Default for ProjectileParams {
         ProjectileParams {
             is_lethal: true,
             passive_effects: Vec::new(),
+            status_effects: Vec::new(),
             particle_effects: Vec::new(),
+            source_id: None,
+            behavior: ProjectileBehavior::default(),
         }
     }
 }
@@ -101,14 +184,20 @@ pub fn spawn_projectile(
     world
         .insert_one(
             entity,
-            Projectile::new(
-                owner,
-                kind.clone(),
-                origin,
-                range,
-                params.is_lethal,
-                &params.passive_effects,
-            ),
+            Projectile {
+                status_effects: params.status_effects.clone(),
+                particle_effects: params.particle_effects.clone(),
+                source_id: params.source_id.clone(),
+                behavior: params.behavior.clone(),
+                ..Projectile::new(
+                    owner,
+                    kind.clone(),
+                    origin,
+                    range,
+                    params.is_lethal,
+                    &params.passive_effects,
+                )
+            },
         )
         .unwrap();
 
@@ -141,19 +230,7 @@ pub fn spawn_projectile(
             let is_flipped_x = velocity.x < 0.0;
 
             if can_rotate {
-                let mut direction = Vec2::ZERO;
-
-                if is_flipped_x {
-                    direction.x = 1.0;
-                } else {
-                    direction.x = -1.0;
-                }
-
-                transform.rotation = (velocity.y - direction.y).atan2(velocity.x - direction.x);
-
-                if is_flipped_x {
-                    transform.rotation += PI;
-                }
+                transform.rotation = get_projectile_rotation(velocity);
             }
 
             world
@@ -198,8 +275,63 @@ pub fn spawn_projectile(
     entity
 }
 
+/// Returns the rotation of a sprite projectile, with the given velocity
+fn get_projectile_rotation(velocity: Vec2) -> f32 {
+    let is_flipped_x = velocity.x < 0.0;
+
+    let mut direction = Vec2::ZERO;
+
+    if is_flipped_x {
+        direction.x = 1.0;
+    } else {
+        direction.x = -1.0;
+    }
+
+    let mut rotation = (velocity.y - direction.y).atan2(velocity.x - direction.x);
+
+    if is_flipped_x {
+        rotation += PI;
+    }
+
+    rotation
+}
+
+/// Moves the projectile to where it hit a solid tile and reflects its velocity off the tile
+fn bounce_projectile(
+    transform: &mut Transform,
+    body: &mut RigidBody,
+    hit: SweepHit,
+    restitution: f32,
+) {
+    transform.position -= body.velocity * (1.0 - hit.time);
+
+    if hit.normal == Vec2::ZERO {
+        // The projectile was already inside the tile, so we just send it back the way it came
+        body.velocity = -body.velocity;
+    } else {
+        if hit.normal.x != 0.0 {
+            body.velocity.x = -body.velocity.x;
+        }
+
+        if hit.normal.y != 0.0 {
+            body.velocity.y = -body.velocity.y;
+        }
+
+        transform.position += hit.normal * BOUNCE_SEPARATION;
+    }
+
+    body.velocity *= restitution;
+}
+
+/// Rotates `velocity` towards `direction`, by no more than `max_rad`
+fn turn_towards(velocity: Vec2, direction: Vec2, max_rad: f32) -> Vec2 {
+    let cross = velocity.x * direction.y - velocity.y * direction.x;
+    let angle = cross.atan2(velocity.dot(direction));
+
+    rotate_vector(velocity, angle.clamp(-max_rad, max_rad))
+}
+
 enum ProjectileCollision {
-    Player(Entity),
     Trigger(Entity),
     Map,
 }
@@ -211,79 +343,174 @@ pub fn fixed_update_projectiles(world: &mut World) {
         .map(|(e, (transform, body))| (e, body.as_rect(transform.position)))
         .collect::<Vec<_>>();
 
-    let collision_world = storage::get::<CollisionWorld>();
+    let players = world
+        .query::<(&Player, &Transform, &PhysicsBody)>()
+        .iter()
+        .filter(|(_, (player, _, _))| player.state != PlayerState::Dead)
+        .map(|(e, (_, transform, body))| (e, body.as_rect(transform.position).center()))
+        .collect::<Vec<_>>();
+
+    let map = get_resource::<Map>(world);
+    let collision_world = get_resource::<CollisionWorld>(world);
+
+    // The collision world does not account for the world offset of the map, so the grid starts at
+    // the origin
+    let is_tile_solid = |x: u32, y: u32| {
+        if x >= map.grid_size.x || y >= map.grid_size.y {
+            return false;
+        }
+
+        let position = (vec2(x as f32, y as f32) + 0.5) * map.tile_size;
+        collision_world.collide_solids(position, 1, 1) == Tile::Solid
+    };
 
     let mut events = Vec::new();
+    let mut damage = Vec::new();
+    let mut status_effects_to_apply = Vec::new();
 
     'projectiles: for (e, (projectile, transform, body)) in world
-        .query::<(&Projectile, &Transform, &RigidBody)>()
+        .query::<(&mut Projectile, &mut Transform, &mut RigidBody)>()
         .iter()
     {
-        if projectile.origin.distance(transform.position) >= projectile.range {
+        projectile.distance_traveled += body.velocity.length();
+        if projectile.distance_traveled >= projectile.range {
             events.push((projectile.owner, e, None));
             continue 'projectiles;
         }
 
-        let size = body.size.as_i32();
-        let map_collision = collision_world.collide_solids(transform.position, size.x, size.y);
-        if map_collision == Tile::Solid {
-            let res = (projectile.owner, e, Some(ProjectileCollision::Map));
-            events.push(res);
-            continue 'projectiles;
-        }
-
-        let rect = body.as_rect(transform.position);
-        for (other, other_rect) in &bodies {
-            if rect.overlaps(other_rect) {
-                if let Ok(mut player) = world.get_mut::<Player>(*other) {
-                    if player.state != PlayerState::Dead {
-                        for meta in projectile.passive_effects.clone().into_iter() {
-                            let effect_instance = PassiveEffectInstance::new(None, meta);
+        // The projectile has already been moved by its velocity, this step, so we check for
+        // collisions along the way from its previous position, so that fast projectiles don't
+        // pass through thin walls or players
+        let delta = body.velocity;
+        let rect = body.as_rect(transform.position - delta);
+
+        let tile_hit = sweep_rect_grid(&rect, delta, map.tile_size, is_tile_solid);
+
+        // The projectile is spawned inside or next to its owner, so the owner is ignored until the
+        // projectile has bounced and might be coming back at them
+        let mut hits = bodies
+            .iter()
+            .filter(|(other, _)| *other != projectile.owner || projectile.bounce_cnt > 0)
+            .filter_map(|(other, other_rect)| {
+                sweep_rect(&rect, delta, other_rect).map(|hit| (*other, hit.time))
+            })
+            .filter(|(_, time)| tile_hit.map(|hit| *time < hit.time).unwrap_or(true))
+            .collect::<Vec<_>>();
+
+        hits.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+
+        for (other, _) in hits {
+            if let Ok(mut player) = world.get_mut::<Player>(other) {
+                if player.state != PlayerState::Dead && !projectile.hit_players.contains(&other) {
+                    for meta in projectile.passive_effects.clone().into_iter() {
+                        let effect_instance = PassiveEffectInstance::new(None, meta);
+
+                        player.passive_effects.push(effect_instance);
+                    }
 
-                            player.passive_effects.push(effect_instance);
-                        }
+                    let is_from_left = body.velocity.x > 0.0;
+                    for meta in projectile.status_effects.iter().cloned() {
+                        status_effects_to_apply.push((other, is_from_left, meta));
+                    }
 
-                        if projectile.is_lethal {
-                            let res = (
-                                projectile.owner,
-                                e,
-                                Some(ProjectileCollision::Player(*other)),
-                            );
+                    if projectile.is_lethal {
+                        damage.push((projectile.owner, e, other));
+                    }
 
-                            events.push(res);
-                        }
+                    projectile.hit_players.push(other);
 
+                    if projectile.hit_players.len() as u32 > projectile.behavior.pierce {
+                        events.push((projectile.owner, e, None));
                         continue 'projectiles;
                     }
-                } else if let Ok(effect) = world.get::<TriggeredEffect>(*other) {
-                    if effect.trigger.contains(&TriggeredEffectTrigger::Projectile) {
-                        let res = (
-                            projectile.owner,
-                            e,
-                            Some(ProjectileCollision::Trigger(*other)),
-                        );
-                        events.push(res);
-                    }
+                }
+            } else if let Ok(effect) = world.get::<TriggeredEffect>(other) {
+                if effect.trigger.contains(&TriggeredEffectTrigger::Projectile) {
+                    let res = (
+                        projectile.owner,
+                        e,
+                        Some(ProjectileCollision::Trigger(other)),
+                    );
+                    events.push(res);
                 }
             }
         }
-    }
 
-    for (damage_from_entity, projectile_entity, collision) in events {
-        if let Some(collision_kind) = collision {
-            match collision_kind {
-                ProjectileCollision::Player(damage_to_entity) => {
-                    on_player_damage(world, damage_from_entity, damage_to_entity);
+        if let Some(hit) = tile_hit {
+            if projectile.bounce_cnt < projectile.behavior.bounces {
+                projectile.bounce_cnt += 1;
+
+                let restitution = projectile.behavior.restitution;
+                bounce_projectile(transform, body, hit, restitution);
+            } else {
+                let res = (projectile.owner, e, Some(ProjectileCollision::Map));
+                events.push(res);
+                continue 'projectiles;
+            }
+        }
+
+        let previous_velocity = body.velocity;
+
+        body.velocity.y += projectile.behavior.gravity;
+
+        if let Some(homing) = &projectile.behavior.homing {
+            let target = players
+                .iter()
+                .filter(|(player, position)| {
+                    *player != projectile.owner
+                        && !projectile.hit_players.contains(player)
+                        && homing
+                            .range
+                            .map(|range| transform.position.distance(*position) <= range)
+                            .unwrap_or(true)
+                })
+                .map(|(_, position)| *position)
+                .min_by(|a, b| {
+                    let a = transform.position.distance(*a);
+                    let b = transform.position.distance(*b);
+                    a.partial_cmp(&b).unwrap()
+                });
+
+            if let Some(target) = target {
+                let max_rad = deg_to_rad(homing.turn_rate);
+                body.velocity = turn_towards(body.velocity, target - transform.position, max_rad);
+            }
+        }
+
+        if body.velocity != previous_velocity {
+            if let ProjectileKind::Sprite { can_rotate, .. } = projectile.kind {
+                if can_rotate {
+                    transform.rotation = get_projectile_rotation(body.velocity);
                 }
-                ProjectileCollision::Trigger(trigger_entity) => {
-                    let mut effect = world.get_mut::<TriggeredEffect>(trigger_entity).unwrap();
-                    if !effect.should_override_delay {
-                        effect.is_triggered = true;
-                        effect.should_override_delay = true;
-                        effect.triggered_by = Some(damage_from_entity);
+
+                if let Ok(mut drawable) = world.get_mut::<Drawable>(e) {
+                    if let Some(sprite) = drawable.get_sprite_mut() {
+                        sprite.is_flipped_x = body.velocity.x < 0.0;
                     }
                 }
-                _ => {}
+            }
+        }
+    }
+
+    drop(collision_world);
+    drop(map);
+
+    for (player_entity, is_from_left, meta) in status_effects_to_apply {
+        apply_status_effect(world, player_entity, is_from_left, meta);
+    }
+
+    for (damage_from_entity, projectile_entity, damage_to_entity) in damage {
+        let source_id = get_source_id(world, projectile_entity);
+        on_player_damage(world, damage_from_entity, damage_to_entity, source_id);
+    }
+
+    for (damage_from_entity, projectile_entity, collision) in events {
+        if let Some(ProjectileCollision::Trigger(trigger_entity)) = collision {
+            let mut effect = world.get_mut::<TriggeredEffect>(trigger_entity).unwrap();
+            if !effect.should_override_delay {
+                effect.is_triggered = true;
+                effect.should_override_delay = true;
+                effect.triggered_by = Some(damage_from_entity);
             }
         }
 
@@ -1,6 +1,10 @@
+use std::collections::HashMap;
+
 use macroquad::experimental::collections::storage;
 use macroquad::prelude::*;
 
+use macroquad_platformer::Actor;
+
 use hecs::{Entity, World};
 
 use serde::{Deserialize, Serialize};
@@ -8,13 +12,18 @@ use serde::{Deserialize, Serialize};
 use core::math::{deg_to_rad, rotate_vector};
 use core::{Result, Transform};
 
+use crate::ecs::{get_resource, get_resource_mut, try_get_resource_mut};
 use crate::effects::active::spawn_active_effect;
+use crate::game::events::{publish_game_event, GameEvent};
+use crate::game::get_delta_time;
+use crate::game::rng::Rng;
 use crate::items::spawn_item;
 use crate::particles::{ParticleEmitter, ParticleEmitterMetadata};
+use crate::physics::step_physics_body;
 use crate::player::{Player, PlayerController, PlayerInventory, PlayerState};
 use crate::{physics, Resources};
 use crate::{ActiveEffectMetadata, AnimatedSpriteMetadata, CollisionWorld, PhysicsBody};
-use crate::{Drawable, DrawableKind, PhysicsBodyParams};
+use crate::{Drawable, DrawableKind, Map, PhysicsBodyParams};
 
 const TRIGGERED_EFFECT_DRAW_ORDER: u32 = 5;
 
@@ -36,6 +45,9 @@ pub enum TriggeredEffectTrigger {
 }
 
 pub struct TriggeredEffect {
+    /// The metadata that the effect was spawned from, kept so that it can be respawned when
+    /// restoring a saved match
+    pub meta: TriggeredEffectMetadata,
     pub owner: Entity,
     pub trigger: Vec<TriggeredEffectTrigger>,
     pub effects: Vec<ActiveEffectMetadata>,
@@ -52,6 +64,8 @@ pub struct TriggeredEffect {
     pub is_triggered: bool,
     /// This holds a handle to the player that triggered the effect, if applicable.
     pub triggered_by: Option<Entity>,
+    /// The id of the weapon or item that the effect was spawned by, if any
+    pub source_id: Option<String>,
     pub grab_options: Option<TriggeredEffectGrabOptions>,
     pub kick_delay_timer: f32,
     pub activation_timer: f32,
@@ -62,6 +76,7 @@ pub struct TriggeredEffect {
 impl TriggeredEffect {
     pub fn new(owner: Entity, meta: TriggeredEffectMetadata) -> Self {
         TriggeredEffect {
+            meta: meta.clone(),
             owner,
             trigger: meta.trigger,
             effects: meta.effects,
@@ -75,6 +90,7 @@ impl TriggeredEffect {
             should_collide_with_platforms: meta.should_collide_with_platforms,
             is_triggered: false,
             triggered_by: None,
+            source_id: None,
             grab_options: meta.grab_options,
             kick_delay_timer: 0.0,
             activation_timer: 0.0,
@@ -98,7 +114,7 @@ pub fn spawn_triggered_effect(
 
     if meta.spread != 0.0 {
         let rad = deg_to_rad(meta.spread);
-        let spread = rand::gen_range(-rad, rad);
+        let spread = get_resource_mut::<Rng>(world).gen_range(-rad, rad);
 
         velocity = rotate_vector(velocity, spread);
     }
@@ -106,7 +122,7 @@ pub fn spawn_triggered_effect(
     let offset = -meta.size / 2.0;
 
     let actor = {
-        let mut collision_world = storage::get_mut::<CollisionWorld>();
+        let mut collision_world = get_resource_mut::<CollisionWorld>(world);
         collision_world.add_actor(origin, meta.size.x as i32, meta.size.y as i32)
     };
 
@@ -163,11 +179,99 @@ pub fn spawn_triggered_effect(
     Ok(entity)
 }
 
+/// The time that passes between each step, when simulating the trajectory of a triggered effect.
+/// This should match the rate of fixed updates.
+const TRAJECTORY_STEP_TIME: f32 = 1.0 / 60.0;
+
+/// This holds the actors used to simulate the trajectories of triggered effects, one for each
+/// collider size, as actors can not be removed from the collision world once they are added.
+#[derive(Default)]
+pub struct TrajectoryActors(HashMap<(i32, i32), Actor>);
+
+/// Simulates the trajectory of a triggered effect deployed at `origin` with `velocity`, using the
+/// same physics as `fixed_update_physics_bodies`, and returns its position after each fixed update.
+/// The simulation ends after `max_steps`, or when the effect would be triggered by the ground or
+/// by its timed trigger, whichever comes first.
+/// The velocity should already be flipped and rotated, as spread is not applied.
+pub fn simulate_triggered_effect_trajectory(
+    world: &World,
+    origin: Vec2,
+    velocity: Vec2,
+    meta: &TriggeredEffectMetadata,
+    max_steps: u32,
+) -> Vec<Vec2> {
+    let mut res = Vec::new();
+
+    let mut actors = match try_get_resource_mut::<TrajectoryActors>(world) {
+        Some(actors) => actors,
+        None => return res,
+    };
+
+    let mut collision_world = get_resource_mut::<CollisionWorld>(world);
+    let map = get_resource::<Map>(world);
+
+    let size = (meta.size.x as i32, meta.size.y as i32);
+    let actor = *actors
+        .0
+        .entry(size)
+        .or_insert_with(|| collision_world.add_actor(origin, size.0, size.1));
+
+    let mut body = PhysicsBody::new(
+        actor,
+        velocity,
+        PhysicsBodyParams {
+            offset: -meta.size / 2.0,
+            size: meta.size,
+            can_rotate: meta.can_rotate,
+            gravity: meta.gravity,
+            angular_velocity: meta.angular_velocity,
+            bouncyness: meta.bouncyness,
+            ..Default::default()
+        },
+    );
+
+    let can_be_triggered_by_ground = meta.trigger.contains(&TriggeredEffectTrigger::Ground);
+
+    let mut transform = Transform::from(origin);
+    let mut time = 0.0;
+
+    for _ in 0..max_steps {
+        step_physics_body(
+            &mut collision_world,
+            &map,
+            &mut body,
+            &mut transform,
+            TRAJECTORY_STEP_TIME,
+        );
+        res.push(transform.position);
+
+        if !meta.should_collide_with_platforms {
+            collision_world.descent(actor);
+        }
+
+        time += TRAJECTORY_STEP_TIME;
+
+        let is_triggered_by_ground =
+            can_be_triggered_by_ground && body.is_on_ground && time >= meta.activation_delay;
+
+        let is_timed_out = [meta.timed_trigger, meta.expire_time]
+            .iter()
+            .flatten()
+            .any(|lifetime| time >= *lifetime);
+
+        if is_triggered_by_ground || is_timed_out {
+            break;
+        }
+    }
+
+    res
+}
+
 const KICK_FORCE: f32 = 15.0;
 const KICK_DELAY: f32 = 0.22;
 
 pub fn fixed_update_triggered_effects(world: &mut World) {
-    let dt = get_frame_time();
+    let dt = get_delta_time(world);
 
     let mut to_trigger = Vec::new();
 
@@ -188,7 +292,7 @@ pub fn fixed_update_triggered_effects(world: &mut World) {
         .iter()
     {
         if !effect.should_collide_with_platforms {
-            let mut collision_world = storage::get_mut::<CollisionWorld>();
+            let mut collision_world = get_resource_mut::<CollisionWorld>(world);
             collision_world.descent(body.actor);
         }
 
@@ -285,7 +389,16 @@ pub fn fixed_update_triggered_effects(world: &mut World) {
         }
     }
 
-    for (e, _, owner, origin, effects) in to_trigger.drain(0..) {
+    for (e, triggered_by, owner, origin, effects) in to_trigger.drain(0..) {
+        publish_game_event(
+            world,
+            GameEvent::EffectTriggered {
+                effect: e,
+                owner,
+                triggered_by,
+            },
+        );
+
         for params in effects {
             if let Err(err) = spawn_active_effect(world, owner, e, origin, params) {
                 #[cfg(debug_assertions)]
@@ -0,0 +1,197 @@
+use macroquad::prelude::*;
+
+use hecs::{Entity, World};
+
+use serde::{Deserialize, Serialize};
+
+use core::Transform;
+
+use crate::game::get_delta_time;
+use crate::particles::{ParticleEmitter, ParticleEmitterMetadata};
+use crate::player::{
+    apply_status_effect, on_player_damage, Player, PlayerState, StatusEffectMetadata,
+};
+use crate::{PassiveEffectInstance, PassiveEffectMetadata, PhysicsBody};
+
+/// An area that stays in the world for a while, affecting any players inside it, like a fire
+/// patch or a poison cloud.
+pub struct Zone {
+    /// The metadata that the zone was spawned from, kept so that it can be respawned when
+    /// restoring a saved match
+    pub meta: ZoneMetadata,
+    pub owner: Entity,
+    /// The id of the weapon or item that the zone was spawned by, if any
+    pub source_id: Option<String>,
+    pub timer: f32,
+    pub tick_timer: f32,
+}
+
+impl Zone {
+    pub fn new(owner: Entity, meta: ZoneMetadata) -> Self {
+        Zone {
+            // The first tick happens as soon as the zone is spawned
+            tick_timer: meta.tick_interval,
+            meta,
+            owner,
+            source_id: None,
+            timer: 0.0,
+        }
+    }
+
+    pub fn get_rect(&self, position: Vec2) -> Rect {
+        let size = self.meta.size;
+        Rect::new(
+            position.x - size.x / 2.0,
+            position.y - size.y / 2.0,
+            size.x,
+            size.y,
+        )
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ZoneMetadata {
+    /// The size of the zone, which is centered on the origin of the effect
+    #[serde(with = "core::json::vec2_def")]
+    pub size: Vec2,
+    /// This will be added to the origin of the effect. It is flipped horizontally if the owner is
+    /// facing left.
+    #[serde(default, with = "core::json::vec2_def")]
+    pub offset: Vec2,
+    /// The time, in seconds, before the zone is removed
+    pub duration: f32,
+    /// The time, in seconds, between each time the zone affects the players inside it
+    #[serde(default = "default_tick_interval")]
+    pub tick_interval: f32,
+    /// If `true` the zone will do damage to any player inside it
+    #[serde(
+        default = "core::json::default_true",
+        skip_serializing_if = "core::json::is_true"
+    )]
+    pub is_lethal: bool,
+    /// If `true` the zone will also affect the player that spawned it
+    #[serde(default, skip_serializing_if = "core::json::is_false")]
+    pub can_hit_owner: bool,
+    /// This contains any passive effects that will be added to players inside the zone. If a
+    /// player already has an effect with the same name, its duration is restarted in stead.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub passive_effects: Vec<PassiveEffectMetadata>,
+    /// This contains any status effects that will be applied to players inside the zone
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub status_effects: Vec<StatusEffectMetadata>,
+    /// Particle effects that will be attached to the zone
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub particles: Vec<ParticleEmitterMetadata>,
+}
+
+fn default_tick_interval() -> f32 {
+    0.5
+}
+
+pub fn spawn_zone(
+    world: &mut World,
+    owner: Entity,
+    origin: Vec2,
+    is_facing_left: bool,
+    meta: ZoneMetadata,
+) -> Entity {
+    let mut offset = meta.offset;
+    if is_facing_left {
+        offset.x = -offset.x;
+    }
+
+    let mut particle_emitters = Vec::new();
+    for params in meta.particles.clone() {
+        let mut emitter = ParticleEmitter::from(params);
+        emitter.is_active = true;
+
+        particle_emitters.push(emitter);
+    }
+
+    let entity = world.spawn((Zone::new(owner, meta), Transform::from(origin + offset)));
+
+    if !particle_emitters.is_empty() {
+        world.insert_one(entity, particle_emitters).unwrap();
+    }
+
+    entity
+}
+
+pub fn update_zones(world: &mut World) {
+    let dt = get_delta_time(world);
+
+    let players = world
+        .query::<(&Player, &Transform, &PhysicsBody)>()
+        .iter()
+        .filter(|(_, (player, _, _))| player.state != PlayerState::Dead)
+        .map(|(e, (_, transform, body))| (e, body.as_rect(transform.position)))
+        .collect::<Vec<_>>();
+
+    let mut to_remove = Vec::new();
+    let mut hits = Vec::new();
+
+    for (e, (zone, transform)) in world.query_mut::<(&mut Zone, &Transform)>() {
+        zone.timer += dt;
+        zone.tick_timer += dt;
+
+        if zone.timer >= zone.meta.duration {
+            to_remove.push(e);
+            continue;
+        }
+
+        if zone.tick_timer < zone.meta.tick_interval {
+            continue;
+        }
+
+        zone.tick_timer = 0.0;
+
+        let rect = zone.get_rect(transform.position);
+
+        for (player_entity, player_rect) in &players {
+            if (zone.meta.can_hit_owner || *player_entity != zone.owner)
+                && rect.overlaps(player_rect)
+            {
+                let is_from_left = transform.position.x < player_rect.center().x;
+                hits.push((e, *player_entity, is_from_left));
+            }
+        }
+    }
+
+    for (zone_entity, player_entity, is_from_left) in hits {
+        let (owner, meta, source_id) = {
+            let zone = world.get::<Zone>(zone_entity).unwrap();
+            (zone.owner, zone.meta.clone(), zone.source_id.clone())
+        };
+
+        {
+            let mut player = world.get_mut::<Player>(player_entity).unwrap();
+
+            for meta in meta.passive_effects {
+                let existing = player
+                    .passive_effects
+                    .iter_mut()
+                    .find(|effect| effect.name == meta.name);
+
+                if let Some(effect) = existing {
+                    effect.duration_timer = 0.0;
+                } else {
+                    let effect_instance = PassiveEffectInstance::new(None, meta);
+                    player.passive_effects.push(effect_instance);
+                }
+            }
+        }
+
+        for meta in meta.status_effects {
+            apply_status_effect(world, player_entity, is_from_left, meta);
+        }
+
+        if meta.is_lethal {
+            on_player_damage(world, owner, player_entity, source_id);
+        }
+    }
+
+    for e in to_remove.drain(0..) {
+        let _ = world.despawn(e);
+    }
+}
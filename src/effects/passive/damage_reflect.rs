@@ -0,0 +1,55 @@
+use hecs::{Entity, World};
+
+use crate::ecs::get_resource_mut;
+use crate::effects::passive::{get_number_param, PassiveEffectParams};
+use crate::game::rng::Rng;
+use crate::player::{on_player_damage, Player, PlayerEventQueue};
+use crate::PlayerEvent;
+
+/// Blocks damage and reflects it back to the attacking player, with a probability given by the
+/// `chance` param. Reflected damage will not be reflected again.
+pub const EFFECT_FUNCTION_ID: &str = "damage_reflect";
+
+const CHANCE_PARAM: &str = "chance";
+const DEFAULT_CHANCE: f32 = 1.0;
+
+pub fn effect_function(
+    world: &mut World,
+    player_entity: Entity,
+    _item_entity: Option<Entity>,
+    params: &PassiveEffectParams,
+    event: PlayerEvent,
+) {
+    if let PlayerEvent::ReceiveDamage {
+        is_from_left,
+        damage_from: Some(damage_from),
+        source_id,
+    } = event
+    {
+        if damage_from == player_entity
+            || source_id.as_deref() == Some(EFFECT_FUNCTION_ID)
+            || world.get::<Player>(damage_from).is_err()
+        {
+            return;
+        }
+
+        let chance = get_number_param(params, CHANCE_PARAM, DEFAULT_CHANCE);
+
+        if get_resource_mut::<Rng>(world).gen_bool(chance) {
+            {
+                let mut events = world.get_mut::<PlayerEventQueue>(player_entity).unwrap();
+
+                events
+                    .queue
+                    .push(PlayerEvent::DamageBlocked { is_from_left });
+            }
+
+            on_player_damage(
+                world,
+                player_entity,
+                damage_from,
+                Some(EFFECT_FUNCTION_ID.to_string()),
+            );
+        }
+    }
+}
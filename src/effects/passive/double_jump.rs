@@ -0,0 +1,63 @@
+use hecs::{Entity, World};
+
+use crate::effects::passive::{get_number_param, PassiveEffectParams};
+use crate::game::play_sound_effect;
+use crate::player::{Player, PlayerAttributes, PlayerController, PlayerState, JUMP_SOUND_ID};
+use crate::{PhysicsBody, PlayerEvent};
+
+/// Allows the player to jump `jump_cnt` more times while in the air. The force of these jumps is
+/// the jump force of the player, multiplied by `jump_force_factor`.
+pub const EFFECT_FUNCTION_ID: &str = "double_jump";
+
+const JUMP_CNT_PARAM: &str = "jump_cnt";
+const JUMP_FORCE_FACTOR_PARAM: &str = "jump_force_factor";
+const DEFAULT_JUMP_CNT: f32 = 1.0;
+const DEFAULT_JUMP_FORCE_FACTOR: f32 = 1.0;
+
+pub fn effect_function(
+    world: &mut World,
+    player_entity: Entity,
+    _item_entity: Option<Entity>,
+    params: &PassiveEffectParams,
+    event: PlayerEvent,
+) {
+    if let PlayerEvent::Update { .. } = event {
+        let mut player = world.get_mut::<Player>(player_entity).unwrap();
+        let mut body = world.get_mut::<PhysicsBody>(player_entity).unwrap();
+
+        if body.is_on_ground {
+            player.air_jump_cnt = 0;
+            return;
+        }
+
+        let controller = world.get::<PlayerController>(player_entity).unwrap();
+
+        let jump_cnt = get_number_param(params, JUMP_CNT_PARAM, DEFAULT_JUMP_CNT) as u32;
+
+        if controller.should_jump
+            && player.air_jump_cnt < jump_cnt
+            && !player.is_attacking
+            && !matches!(
+                player.state,
+                PlayerState::Dead
+                    | PlayerState::Incapacitated
+                    | PlayerState::Sliding
+                    | PlayerState::Swimming
+            )
+        {
+            let attributes = world.get::<PlayerAttributes>(player_entity).unwrap();
+
+            let jump_force_factor =
+                get_number_param(params, JUMP_FORCE_FACTOR_PARAM, DEFAULT_JUMP_FORCE_FACTOR);
+
+            body.velocity.y = -attributes.stats.jump_force * jump_force_factor;
+            body.has_mass = true;
+
+            player.state = PlayerState::Jumping;
+            player.jump_frame_counter = 0;
+            player.air_jump_cnt += 1;
+
+            play_sound_effect(JUMP_SOUND_ID, 0.4);
+        }
+    }
+}
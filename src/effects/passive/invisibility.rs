@@ -0,0 +1,42 @@
+use hecs::{Entity, World};
+
+use crate::effects::passive::{get_number_param, PassiveEffectParams};
+use crate::{Drawable, PlayerEvent};
+
+/// Sets the opacity of the player's sprites to the `opacity` param, relative to their original
+/// opacity, which is restored by `restore_opacity` once the effect is removed. Equipped items will
+/// still be visible.
+pub const EFFECT_FUNCTION_ID: &str = "invisibility";
+
+const OPACITY_PARAM: &str = "opacity";
+const DEFAULT_OPACITY: f32 = 0.1;
+
+pub fn effect_function(
+    world: &mut World,
+    player_entity: Entity,
+    _item_entity: Option<Entity>,
+    params: &PassiveEffectParams,
+    event: PlayerEvent,
+) {
+    if let PlayerEvent::Update { .. } = event {
+        let opacity = get_number_param(params, OPACITY_PARAM, DEFAULT_OPACITY).clamp(0.0, 1.0);
+
+        let mut drawable = world.get_mut::<Drawable>(player_entity).unwrap();
+
+        if let Some(sprite_set) = drawable.get_animated_sprite_set_mut() {
+            for sprite in sprite_set.map.values_mut() {
+                sprite.tint.a = sprite.base_tint.a * opacity;
+            }
+        }
+    }
+}
+
+/// Restores the original opacity of the player's sprites, after the last invisibility effect has
+/// been removed
+pub fn restore_opacity(drawable: &mut Drawable) {
+    if let Some(sprite_set) = drawable.get_animated_sprite_set_mut() {
+        for sprite in sprite_set.map.values_mut() {
+            sprite.tint.a = sprite.base_tint.a;
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use hecs::{Entity, World};
+
+use crate::effects::passive::{get_number_param, shield, PassiveEffectParams};
+use crate::map::get_number_property;
+use crate::player::{Player, PlayerEventKind};
+use crate::{PassiveEffectInstance, PassiveEffectMetadata, PlayerEvent};
+
+/// Gives the player a shield that blocks the next hit, each time it damages another player. The
+/// number of stacked shields is limited by `max_shields` and each shield will last for `duration`
+/// seconds, if specified.
+pub const EFFECT_FUNCTION_ID: &str = "lifesteal";
+
+const MAX_SHIELDS_PARAM: &str = "max_shields";
+const DURATION_PARAM: &str = "duration";
+const DEFAULT_MAX_SHIELDS: f32 = 1.0;
+
+const STOLEN_LIFE_EFFECT_NAME: &str = "Stolen Life";
+
+pub fn effect_function(
+    world: &mut World,
+    player_entity: Entity,
+    _item_entity: Option<Entity>,
+    params: &PassiveEffectParams,
+    event: PlayerEvent,
+) {
+    if let PlayerEvent::GiveDamage {
+        damage_to: Some(damage_to),
+    } = event
+    {
+        if damage_to == player_entity {
+            return;
+        }
+
+        let mut player = world.get_mut::<Player>(player_entity).unwrap();
+
+        let max_shields = get_number_param(params, MAX_SHIELDS_PARAM, DEFAULT_MAX_SHIELDS) as usize;
+
+        let shield_cnt = player
+            .passive_effects
+            .iter()
+            .filter(|effect| effect.name == STOLEN_LIFE_EFFECT_NAME)
+            .count();
+
+        if shield_cnt < max_shields {
+            let meta = PassiveEffectMetadata {
+                name: STOLEN_LIFE_EFFECT_NAME.to_string(),
+                function_id: Some(shield::EFFECT_FUNCTION_ID.to_string()),
+                function_params: Default::default(),
+                stat_modifiers: Vec::new(),
+                activated_on: vec![PlayerEventKind::ReceiveDamage],
+                particle_effect_id: None,
+                event_particle_effect_id: None,
+                blocks_damage: true,
+                uses: Some(1),
+                duration: params.get(DURATION_PARAM).and_then(get_number_property),
+                sprite: None,
+            };
+
+            player
+                .passive_effects
+                .push(PassiveEffectInstance::new(None, meta));
+        }
+    }
+}
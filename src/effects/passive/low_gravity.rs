@@ -0,0 +1,20 @@
+use crate::effects::passive::{get_number_param, PassiveEffectParams};
+use crate::player::{PlayerStat, StatModifierMetadata, StatModifierOperation};
+
+/// Multiplies the gravity of the player by the `gravity_factor` param
+pub const EFFECT_FUNCTION_ID: &str = "low_gravity";
+
+const GRAVITY_FACTOR_PARAM: &str = "gravity_factor";
+const DEFAULT_GRAVITY_FACTOR: f32 = 0.5;
+
+pub fn stat_modifiers(params: &PassiveEffectParams) -> Vec<StatModifierMetadata> {
+    let gravity_factor = get_number_param(params, GRAVITY_FACTOR_PARAM, DEFAULT_GRAVITY_FACTOR);
+
+    vec![StatModifierMetadata {
+        stat: PlayerStat::Gravity,
+        operation: StatModifierOperation::Multiply,
+        value: gravity_factor,
+        priority: 0,
+        duration: None,
+    }]
+}
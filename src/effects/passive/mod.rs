@@ -2,49 +2,128 @@ use std::collections::HashMap;
 
 use macroquad::prelude::*;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 use hecs::{Entity, World};
 
-mod turtle_shell;
+use core::json::GenericParam;
 
-use crate::player::PlayerEventKind;
-use crate::{AnimatedSprite, AnimatedSpriteMetadata, PlayerEvent};
+mod damage_reflect;
+mod double_jump;
+mod invisibility;
+mod lifesteal;
+mod low_gravity;
+mod shield;
+mod speed_boost;
+mod turtle_shell;
 
-static mut PASSIVE_EFFECT_FUNCS: Option<HashMap<String, PassiveEffectFn>> = None;
+pub use invisibility::{restore_opacity, EFFECT_FUNCTION_ID as INVISIBILITY_EFFECT_FUNCTION_ID};
 
-unsafe fn get_passive_effects_map() -> &'static mut HashMap<String, PassiveEffectFn> {
-    PASSIVE_EFFECT_FUNCS.get_or_insert(HashMap::new())
-}
+use crate::map::get_number_property;
+use crate::player::{PlayerEventKind, StatModifierMetadata};
+use crate::{AnimatedSprite, AnimatedSpriteMetadata, PlayerEvent};
 
-#[allow(dead_code)]
-pub fn add_passive_effect(id: &str, f: PassiveEffectFn) {
-    unsafe { get_passive_effects_map() }.insert(id.to_string(), f);
+/// The parameters passed to a passive effect function, from the `function_params` of the effect
+/// metadata. Which parameters are used depends on the function.
+pub type PassiveEffectParams = HashMap<String, GenericParam>;
+
+pub type PassiveEffectFn = fn(
+    world: &mut World,
+    player_entity: Entity,
+    item_entity: Option<Entity>,
+    params: &PassiveEffectParams,
+    event: PlayerEvent,
+);
+
+/// The passive effect functions that can be referenced by their ids from passive effect metadata.
+/// This is immutable, so that it can be shared by all the games running in a process.
+const PASSIVE_EFFECT_FUNCS: &[(&str, PassiveEffectFn)] = &[
+    (
+        turtle_shell::EFFECT_FUNCTION_ID,
+        turtle_shell::effect_function,
+    ),
+    (shield::EFFECT_FUNCTION_ID, shield::effect_function),
+    (
+        double_jump::EFFECT_FUNCTION_ID,
+        double_jump::effect_function,
+    ),
+    (
+        damage_reflect::EFFECT_FUNCTION_ID,
+        damage_reflect::effect_function,
+    ),
+    (
+        invisibility::EFFECT_FUNCTION_ID,
+        invisibility::effect_function,
+    ),
+    (lifesteal::EFFECT_FUNCTION_ID, lifesteal::effect_function),
+];
+
+pub type StatModifierFn = fn(params: &PassiveEffectParams) -> Vec<StatModifierMetadata>;
+
+/// The built-in effects that are expressed as stat modifiers, rather than functions that are
+/// called on player events. They are referenced by `function_id` like the passive effect
+/// functions, and the modifiers they return are applied for as long as the effect is active.
+const STAT_MODIFIER_FUNCS: &[(&str, StatModifierFn)] = &[
+    (speed_boost::EFFECT_FUNCTION_ID, speed_boost::stat_modifiers),
+    (low_gravity::EFFECT_FUNCTION_ID, low_gravity::stat_modifiers),
+];
+
+pub fn try_get_stat_modifier_function(id: &str) -> Option<&'static StatModifierFn> {
+    STAT_MODIFIER_FUNCS
+        .iter()
+        .find(|(function_id, _)| *function_id == id)
+        .map(|(_, f)| f)
 }
 
-pub fn try_get_passive_effect(id: &str) -> Option<&PassiveEffectFn> {
-    unsafe { get_passive_effects_map() }.get(id)
+pub fn try_get_passive_effect(id: &str) -> Option<&'static PassiveEffectFn> {
+    PASSIVE_EFFECT_FUNCS
+        .iter()
+        .find(|(function_id, _)| *function_id == id)
+        .map(|(_, f)| f)
 }
 
-pub fn get_passive_effect(id: &str) -> &PassiveEffectFn {
+/// This will panic if there is no function with the specified id. Function ids are validated when
+/// passive effect metadata is deserialized, so this should only happen with metadata that was
+/// constructed in code.
+pub fn get_passive_effect(id: &str) -> &'static PassiveEffectFn {
     try_get_passive_effect(id).unwrap()
 }
 
-pub type PassiveEffectFn =
-    fn(world: &mut World, player_entity: Entity, item_entity: Option<Entity>, event: PlayerEvent);
+/// Get a number parameter, or `default` if it is not specified or is not a number
+pub fn get_number_param(params: &PassiveEffectParams, key: &str, default: f32) -> f32 {
+    params
+        .get(key)
+        .and_then(get_number_property)
+        .unwrap_or(default)
+}
 
-pub fn init_passive_effects() {
-    let effects = unsafe { get_passive_effects_map() };
+fn deserialize_function_id<'de, D>(deserializer: D) -> std::result::Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let id: Option<String> = Option::deserialize(deserializer)?;
+
+    if let Some(id) = &id {
+        if try_get_passive_effect(id).is_none() && try_get_stat_modifier_function(id).is_none() {
+            return Err(serde::de::Error::custom(format!(
+                "Invalid passive effect function id '{}'",
+                id
+            )));
+        }
+    }
 
-    effects.insert(
-        turtle_shell::EFFECT_FUNCTION_ID.to_string(),
-        turtle_shell::effect_function,
-    );
+    Ok(id)
 }
 
 pub struct PassiveEffectInstance {
+    /// The metadata that the instance was created from, kept so that it can be recreated when
+    /// restoring a saved match
+    pub meta: PassiveEffectMetadata,
     pub name: String,
     pub function: Option<PassiveEffectFn>,
+    /// The stat modifiers of the effect, including the ones returned by its stat modifier
+    /// function, if it references one
+    pub stat_modifiers: Vec<StatModifierMetadata>,
     pub activated_on: Vec<PlayerEventKind>,
     pub sprite: Option<AnimatedSprite>,
     pub sprite_entity: Option<Entity>,
@@ -60,11 +139,25 @@ pub struct PassiveEffectInstance {
 
 impl PassiveEffectInstance {
     pub fn new(item: Option<Entity>, meta: PassiveEffectMetadata) -> Self {
-        let function = meta.function_id.map(|id| *get_passive_effect(&id));
+        let mut stat_modifiers = meta.stat_modifiers.clone();
+
+        let stat_modifier_function = meta
+            .function_id
+            .as_deref()
+            .and_then(try_get_stat_modifier_function);
+
+        let function = if let Some(f) = stat_modifier_function {
+            stat_modifiers.extend(f(&meta.function_params));
+            None
+        } else {
+            meta.function_id.as_ref().map(|id| *get_passive_effect(id))
+        };
 
         PassiveEffectInstance {
+            meta: meta.clone(),
             name: meta.name,
             function,
+            stat_modifiers,
             activated_on: meta.activated_on,
             sprite: meta.sprite.map(Into::into),
             sprite_entity: None,
@@ -104,8 +197,22 @@ impl PassiveEffectInstance {
 #[serde(deny_unknown_fields)]
 pub struct PassiveEffectMetadata {
     pub name: String,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// The id of the function that will be called when the effect is activated, or of the stat
+    /// modifier function that adds to the effect's stat modifiers. This will cause an error, when
+    /// the metadata is loaded, if there is no function with the specified id.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_function_id",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub function_id: Option<String>,
+    /// The parameters passed to the function, like `{ "multiplier": 1.5 }` for `speed_boost`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub function_params: PassiveEffectParams,
+    /// Stat modifiers that are applied to the player while the effect is active. The duration of
+    /// a modifier is counted from when the effect was added.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stat_modifiers: Vec<StatModifierMetadata>,
     /// This specifies the player events that will trigger an activation of the event
     pub activated_on: Vec<PlayerEventKind>,
     /// This is the particle effect that will be spawned when the effect become active.
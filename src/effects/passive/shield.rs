@@ -0,0 +1,24 @@
+use hecs::{Entity, World};
+
+use crate::effects::passive::PassiveEffectParams;
+use crate::player::PlayerEventQueue;
+use crate::PlayerEvent;
+
+/// Blocks damage from any direction
+pub const EFFECT_FUNCTION_ID: &str = "shield";
+
+pub fn effect_function(
+    world: &mut World,
+    player_entity: Entity,
+    _item_entity: Option<Entity>,
+    _params: &PassiveEffectParams,
+    event: PlayerEvent,
+) {
+    if let PlayerEvent::ReceiveDamage { is_from_left, .. } = event {
+        let mut events = world.get_mut::<PlayerEventQueue>(player_entity).unwrap();
+
+        events
+            .queue
+            .push(PlayerEvent::DamageBlocked { is_from_left });
+    }
+}
@@ -0,0 +1,20 @@
+use crate::effects::passive::{get_number_param, PassiveEffectParams};
+use crate::player::{PlayerStat, StatModifierMetadata, StatModifierOperation};
+
+/// Multiplies the move speed of the player by the `multiplier` param
+pub const EFFECT_FUNCTION_ID: &str = "speed_boost";
+
+const MULTIPLIER_PARAM: &str = "multiplier";
+const DEFAULT_MULTIPLIER: f32 = 1.5;
+
+pub fn stat_modifiers(params: &PassiveEffectParams) -> Vec<StatModifierMetadata> {
+    let multiplier = get_number_param(params, MULTIPLIER_PARAM, DEFAULT_MULTIPLIER);
+
+    vec![StatModifierMetadata {
+        stat: PlayerStat::MoveSpeed,
+        operation: StatModifierOperation::Multiply,
+        value: multiplier,
+        priority: 0,
+        duration: None,
+    }]
+}
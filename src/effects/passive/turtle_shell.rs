@@ -1,5 +1,6 @@
 use hecs::{Entity, World};
 
+use crate::effects::passive::PassiveEffectParams;
 use crate::player::{Player, PlayerEventQueue};
 use crate::PlayerEvent;
 
@@ -9,6 +10,7 @@ pub fn effect_function(
     world: &mut World,
     player_entity: Entity,
     _item_entity: Option<Entity>,
+    _params: &PassiveEffectParams,
     event: PlayerEvent,
 ) {
     if let PlayerEvent::ReceiveDamage { is_from_left, .. } = event {
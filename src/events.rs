@@ -3,14 +3,14 @@
 //! jumping between game modes, for example, like starting a test game with a map we are editing
 //! in the editor, without having to exit to main menu, select game mode, select map, etc.
 
-static mut APPLICATION_EVENTS: Option<Vec<ApplicationEvent>> = None;
+use std::sync::Mutex;
 
-unsafe fn get_event_queue() -> &'static mut Vec<ApplicationEvent> {
-    APPLICATION_EVENTS.get_or_insert(Vec::new())
-}
+/// The main loop is shared by everything running in the process, so the queue is behind a mutex,
+/// allowing events to be dispatched from any game, on any thread.
+static APPLICATION_EVENTS: Mutex<Vec<ApplicationEvent>> = Mutex::new(Vec::new());
 
 pub fn dispatch_application_event(event: ApplicationEvent) {
-    unsafe { get_event_queue() }.push(event);
+    APPLICATION_EVENTS.lock().unwrap().push(event);
 }
 
 pub fn iter_events() -> ApplicationEventIterator {
@@ -48,6 +48,6 @@ impl Iterator for ApplicationEventIterator {
     type Item = ApplicationEvent;
 
     fn next(&mut self) -> Option<Self::Item> {
-        unsafe { get_event_queue() }.pop()
+        APPLICATION_EVENTS.lock().unwrap().pop()
     }
 }
@@ -1,11 +1,8 @@
-use macroquad::prelude::collections::storage;
 use macroquad::prelude::*;
 use macroquad::rand::gen_range;
 
 use core::noise::NoiseGenerator;
 
-use crate::map::Map;
-
 struct Shake {
     direction: (f32, f32),
     kind: ShakeType,
@@ -25,6 +22,7 @@ enum ShakeType {
 
 pub struct GameCamera {
     bounds: Rect,
+    playable_area: Rect,
     follow_buffer: Vec<(Vec2, f32)>,
     shake: Vec<Shake>,
     noisegen: NoiseGenerator,
@@ -37,11 +35,12 @@ pub struct GameCamera {
 impl GameCamera {
     const BUFFER_CAPACITY: usize = 20;
 
-    pub fn new(map_size: Vec2) -> GameCamera {
+    pub fn new(map_size: Vec2, playable_area: Rect) -> GameCamera {
         let bounds = Rect::new(0.0, 0.0, map_size.x, map_size.y);
 
         GameCamera {
             bounds,
+            playable_area,
             follow_buffer: vec![],
             shake: vec![],
             manual: None,
@@ -52,8 +51,7 @@ impl GameCamera {
     }
 
     pub fn add_player_rect(&mut self, rect: Rect) {
-        let map = storage::get::<Map>();
-        let playable = map.get_playable_area();
+        let playable = self.playable_area;
         if playable.overlaps(&rect) {
             self.player_rects.push(rect);
 
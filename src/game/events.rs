@@ -0,0 +1,84 @@
+//! The game event bus lets systems publish gameplay events, like a player being killed or a
+//! weapon being fired, so that other systems can react to them without being coupled to the
+//! systems that publish them.
+//!
+//! Events published during a frame, including those published by fixed updates, are made
+//! available when `update_game_events` runs at the start of the next update. Subscribers are
+//! just systems that call `get_game_events` once every update, as the events are replaced the
+//! next time `update_game_events` runs.
+//!
+//! Entities referenced by an event might have been despawned by the time the event is read, so
+//! subscribers should not expect them to still be in the world.
+
+use std::mem;
+
+use hecs::{Entity, World};
+
+use crate::ecs::{insert_resource, try_get_resource, try_get_resource_mut};
+
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    /// A player was killed. `killed_by` will be `None` if the player was not killed by another
+    /// player, like when leaving the playable area of the map. `source_id` holds the id of the
+    /// weapon or item that caused the kill, if any.
+    PlayerKilled {
+        player: Entity,
+        killed_by: Option<Entity>,
+        source_id: Option<String>,
+    },
+    /// A player was hit by something that does damage, whether it was blocked or not. `hit_by`
+    /// is the player that the damage originates from.
+    PlayerHit {
+        player: Entity,
+        hit_by: Entity,
+        source_id: Option<String>,
+    },
+    /// A dead player respawned
+    PlayerRespawned { player: Entity },
+    /// A player picked up an item or a weapon
+    ItemPickedUp { player: Entity, item: Entity },
+    /// A player fired a weapon
+    WeaponFired { player: Entity, weapon: Entity },
+    /// A triggered effect was triggered, either by a player or on its own
+    EffectTriggered {
+        effect: Entity,
+        owner: Entity,
+        triggered_by: Option<Entity>,
+    },
+    /// The round ended. `winner` is the player with the most kills, or `None` if the lead is
+    /// shared.
+    RoundEnded { winner: Option<Entity> },
+}
+
+/// This holds the game events and is added to the world as a resource
+#[derive(Debug, Default)]
+pub struct GameEventBus {
+    published: Vec<GameEvent>,
+    current: Vec<GameEvent>,
+}
+
+pub fn spawn_game_event_bus(world: &mut World) -> Entity {
+    insert_resource(world, GameEventBus::default())
+}
+
+/// Publish an event. It will be available to subscribers from the start of the next update.
+pub fn publish_game_event(world: &mut World, event: GameEvent) {
+    if let Some(mut bus) = try_get_resource_mut::<GameEventBus>(world) {
+        bus.published.push(event);
+    }
+}
+
+/// Returns the events that were published during the previous frame
+pub fn get_game_events(world: &World) -> Vec<GameEvent> {
+    try_get_resource::<GameEventBus>(world)
+        .map(|bus| bus.current.clone())
+        .unwrap_or_default()
+}
+
+/// This makes the events published since the last time it ran available to subscribers and
+/// should be the first system to run in every update.
+pub fn update_game_events(world: &mut World) {
+    if let Some(mut bus) = try_get_resource_mut::<GameEventBus>(world) {
+        bus.current = mem::take(&mut bus.published);
+    }
+}
@@ -0,0 +1,157 @@
+//! The kill feed shows the most recent kills in the upper right corner of the screen. Entries are
+//! built from `GameEvent::PlayerKilled` events and fade out after a while.
+
+use macroquad::experimental::collections::storage;
+use macroquad::prelude::*;
+
+use hecs::{Entity, World};
+
+use crate::ecs::{get_resource, get_resource_mut};
+use crate::game::events::{get_game_events, GameEvent};
+use crate::game::get_delta_time;
+use crate::player::Player;
+use crate::Resources;
+
+/// The time an entry is shown, including the time it takes to fade out
+const KILL_FEED_ENTRY_DURATION: f32 = 5.0;
+const KILL_FEED_FADE_DURATION: f32 = 1.0;
+
+const KILL_FEED_MAX_ENTRIES: usize = 5;
+
+const KILL_FEED_MARGIN: f32 = 16.0;
+const KILL_FEED_FONT_SIZE: u16 = 24;
+const KILL_FEED_LINE_HEIGHT: f32 = 28.0;
+
+const KILL_FEED_TEXT_COLOR: Color = WHITE;
+
+#[derive(Debug, Clone)]
+pub struct KillFeedEntry {
+    pub text: String,
+    pub timer: f32,
+}
+
+impl KillFeedEntry {
+    pub fn new(text: String) -> Self {
+        KillFeedEntry { text, timer: 0.0 }
+    }
+
+    fn get_alpha(&self) -> f32 {
+        let remaining = KILL_FEED_ENTRY_DURATION - self.timer;
+        (remaining / KILL_FEED_FADE_DURATION).clamp(0.0, 1.0)
+    }
+}
+
+/// This holds the entries of the kill feed, newest last, and is added to the world as a resource
+#[derive(Debug, Default)]
+pub struct KillFeed {
+    entries: Vec<KillFeedEntry>,
+}
+
+impl KillFeed {
+    pub fn push(&mut self, entry: KillFeedEntry) {
+        self.entries.push(entry);
+
+        if self.entries.len() > KILL_FEED_MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+}
+
+fn get_player_name(world: &World, entity: Entity) -> String {
+    world
+        .get::<Player>(entity)
+        .map(|player| format!("Player {}", player.index + 1))
+        .unwrap_or_else(|_| "Unknown".to_string())
+}
+
+/// Returns the name of the weapon or item with the specified id, or the id itself, if it is not
+/// found in the item resources
+fn get_source_name(id: &str) -> String {
+    storage::get::<Resources>()
+        .items
+        .get(id)
+        .map(|meta| meta.name.clone())
+        .unwrap_or_else(|| id.to_string())
+}
+
+fn get_kill_feed_text(
+    world: &World,
+    player: Entity,
+    killed_by: Option<Entity>,
+    source_id: Option<&str>,
+) -> String {
+    let victim = get_player_name(world, player);
+
+    let mut text = match killed_by {
+        Some(killer) if killer == player => format!("{} killed themselves", victim),
+        Some(killer) => format!("{} killed {}", get_player_name(world, killer), victim),
+        None => format!("{} fell out of the map", victim),
+    };
+
+    if let Some(id) = source_id {
+        text = format!("{} with {}", text, get_source_name(id));
+    }
+
+    text
+}
+
+pub fn update_kill_feed(world: &mut World) {
+    let dt = get_delta_time(world);
+
+    let mut new_entries = Vec::new();
+
+    for event in get_game_events(world) {
+        if let GameEvent::PlayerKilled {
+            player,
+            killed_by,
+            source_id,
+        } = event
+        {
+            let text = get_kill_feed_text(world, player, killed_by, source_id.as_deref());
+            new_entries.push(KillFeedEntry::new(text));
+        }
+    }
+
+    let mut kill_feed = get_resource_mut::<KillFeed>(world);
+
+    for entry in &mut kill_feed.entries {
+        entry.timer += dt;
+    }
+
+    kill_feed
+        .entries
+        .retain(|entry| entry.timer < KILL_FEED_ENTRY_DURATION);
+
+    for entry in new_entries {
+        kill_feed.push(entry);
+    }
+}
+
+pub fn draw_kill_feed(world: &mut World) {
+    let kill_feed = get_resource::<KillFeed>(world);
+
+    if kill_feed.entries.is_empty() {
+        return;
+    }
+
+    push_camera_state();
+    set_default_camera();
+
+    let mut y = KILL_FEED_MARGIN + KILL_FEED_LINE_HEIGHT;
+
+    for entry in kill_feed.entries.iter().rev() {
+        let size = measure_text(&entry.text, None, KILL_FEED_FONT_SIZE, 1.0);
+        let x = screen_width() - KILL_FEED_MARGIN - size.width;
+
+        let color = Color {
+            a: entry.get_alpha(),
+            ..KILL_FEED_TEXT_COLOR
+        };
+
+        draw_text(&entry.text, x, y, KILL_FEED_FONT_SIZE as f32, color);
+
+        y += KILL_FEED_LINE_HEIGHT;
+    }
+
+    pop_camera_state();
+}
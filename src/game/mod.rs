@@ -1,6 +1,13 @@
 mod camera;
+pub mod events;
+mod kill_feed;
 mod music;
+pub mod replay;
+pub mod rng;
+pub mod save;
+pub mod simulation;
 pub mod sound;
+pub mod stats;
 
 pub use camera::GameCamera;
 
@@ -14,47 +21,85 @@ use macroquad::ui::root_ui;
 use hecs::{Entity, World};
 
 use core::input::is_gamepad_btn_pressed;
-use core::Result;
+use core::{Result, Transform};
 
 use crate::debug;
-use crate::ecs::Scheduler;
+use crate::ecs::{get_resource, get_resource_mut, insert_resource, Scheduler};
 use crate::gui::{self, GAME_MENU_RESULT_MAIN_MENU, GAME_MENU_RESULT_QUIT};
 use crate::physics::{debug_draw_physics_bodies, fixed_update_physics_bodies};
 use crate::player::{
-    draw_weapons_hud, spawn_player, update_player_animations, update_player_camera_box,
-    update_player_controllers, update_player_events, update_player_inventory,
-    update_player_passive_effects, update_player_states, PlayerParams,
+    draw_trajectory_previews, draw_weapons_hud, spawn_player, update_bots,
+    update_player_animations, update_player_camera_box, update_player_controllers,
+    update_player_events, update_player_inventory, update_player_passive_effects,
+    update_player_states, update_player_stats, update_player_status_effects,
+    update_trajectory_previews, Player, PlayerController, PlayerControllerKind, PlayerInventory,
+    PlayerParams, TrajectoryPreviews,
 };
 use crate::{
     create_collision_world, debug_draw_drawables, debug_draw_rigid_bodies, draw_drawables,
-    exit_to_main_menu, fixed_update_rigid_bodies, quit_to_desktop, update_animated_sprites, Map,
-    MapLayerKind, MapObjectKind, Resources,
+    exit_to_main_menu, fixed_update_rigid_bodies, quit_to_desktop, update_animated_sprites,
+    Drawable, Map, MapLayerKind, MapObjectKind, PhysicsBody, Resources, RigidBody,
 };
+use crate::{parallel_system, system};
 
+use crate::effects::active::beam::{draw_beams, update_beams};
 use crate::effects::active::debug_draw_active_effects;
+use crate::effects::active::delayed::update_delayed_effects;
 use crate::effects::active::projectiles::fixed_update_projectiles;
-use crate::effects::active::triggered::{fixed_update_triggered_effects, update_triggered_effects};
-use crate::items::{spawn_item, update_respawning_items};
+use crate::effects::active::triggered::{
+    fixed_update_triggered_effects, update_triggered_effects, TrajectoryActors,
+};
+use crate::effects::active::zone::update_zones;
+use crate::items::{spawn_map_item, update_respawning_items};
 use crate::map::{
     debug_draw_fish_schools, fixed_update_sproingers, spawn_crab, spawn_decoration,
-    spawn_fish_school, spawn_sproinger, update_crabs, update_fish_schools, update_map_kill_zone,
+    spawn_fish_school, spawn_item_spawner, spawn_sproinger, update_crabs, update_fish_schools,
+    update_item_spawners, update_map_kill_zone, MapObject, ITEM_SPAWNER_ID,
 };
 use crate::network::{
     fixed_update_network_client, fixed_update_network_host, update_network_client,
     update_network_host,
 };
-use crate::particles::{draw_particles, update_particle_emitters};
+use crate::particles::{draw_particles, update_particle_emitters, Particles};
 pub use music::{start_music, stop_music};
 pub use sound::play_sound_effect;
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+use events::{publish_game_event, spawn_game_event_bus, update_game_events, GameEvent};
+use kill_feed::{draw_kill_feed, update_kill_feed, KillFeed};
+use sound::update_game_event_sounds;
+use stats::{update_match_stats, MatchStats};
+
+use rng::Rng;
+
+use save::{get_quick_save_path, SavedMatch, QUICK_LOAD_KEY, QUICK_SAVE_KEY};
+
+use replay::{
+    collect_player_inputs, draw_replay_hud, Replay, ReplayPlayback, ReplayPlaybackStep,
+    ReplayRecorder,
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum GameMode {
     Local,
     NetworkHost,
     NetworkClient,
+    /// A local match that is simulated at a fixed time step, without rendering or sound, like
+    /// when running balance simulations
+    Simulation,
+}
+
+/// The time step of the current update. This is kept in the world as a resource, rather than
+/// read from macroquad by the systems, so that matches can be simulated at a fixed time step.
+#[derive(Debug, Copy, Clone)]
+pub struct DeltaTime(pub f32);
+
+/// Returns the time step of the current update
+pub fn get_delta_time(world: &World) -> f32 {
+    get_resource::<DeltaTime>(world).0
 }
 
 pub struct Game {
+    mode: GameMode,
     world: World,
     #[allow(dead_code)]
     players: Vec<Entity>,
@@ -63,19 +108,26 @@ pub struct Game {
     draws: Scheduler,
     #[cfg(debug_assertions)]
     debug_draws: Scheduler,
+    #[cfg(debug_assertions)]
+    is_debug_draw_enabled: bool,
+    #[cfg(debug_assertions)]
+    is_system_timings_enabled: bool,
+    replay_recorder: Option<ReplayRecorder>,
+    replay_playback: Option<ReplayPlayback>,
+    /// This will hold the stats of the match, once it has ended, while showing the results
+    match_results: Option<MatchStats>,
 }
 
 impl Game {
-    pub fn new(mode: GameMode, map: Map, player_params: &[PlayerParams]) -> Result<Game> {
-        let mut world = World::default();
-
-        {
-            let camera = GameCamera::new(map.get_size());
-            storage::store(camera);
-
-            let collision_world = create_collision_world(&map);
-            storage::store(collision_world);
-        }
+    /// Create a new game on the specified map. The random number generator of the game is
+    /// seeded with `seed`.
+    pub fn new(
+        mode: GameMode,
+        map: Map,
+        player_params: &[PlayerParams],
+        seed: u64,
+    ) -> Result<Game> {
+        let mut world = Game::create_world(&map, Rng::new(seed));
 
         spawn_map_objects(&mut world, &map).unwrap();
 
@@ -83,7 +135,7 @@ impl Game {
             .iter()
             .cloned()
             .map(|params| {
-                let position = map.get_random_spawn_point();
+                let position = map.get_random_spawn_point(&mut get_resource_mut::<Rng>(&world));
                 spawn_player(
                     &mut world,
                     params.index,
@@ -94,74 +146,179 @@ impl Game {
             })
             .collect();
 
-        storage::store(map);
+        insert_resource(&mut world, map);
 
-        let mut updates_builder = Scheduler::builder();
+        Game::from_world(mode, world, players)
+    }
 
-        let mut fixed_updates_builder = Scheduler::builder();
+    /// Create a world holding the resources needed to run a match on the specified map. The map
+    /// itself is not added, so that it can be inserted as a resource once the map objects have
+    /// been spawned.
+    pub fn create_world(map: &Map, rng: Rng) -> World {
+        let mut world = World::default();
 
-        match mode {
-            GameMode::NetworkClient => {
-                updates_builder.add_system(update_network_client);
+        let camera = GameCamera::new(map.get_size(), map.get_playable_area());
+        insert_resource(&mut world, camera);
 
-                fixed_updates_builder.add_system(fixed_update_network_client);
-            }
-            GameMode::NetworkHost => {
-                updates_builder.add_system(update_network_host);
+        let collision_world = create_collision_world(map);
+        insert_resource(&mut world, collision_world);
 
-                fixed_updates_builder.add_system(fixed_update_network_host);
-            }
-            _ => {}
-        }
+        insert_resource(&mut world, TrajectoryActors::default());
+        insert_resource(&mut world, TrajectoryPreviews::default());
 
-        updates_builder
-            .add_system(update_player_controllers)
-            .add_system(update_player_camera_box);
-
-        if matches!(mode, GameMode::Local | GameMode::NetworkHost) {
-            updates_builder
-                .add_system(update_respawning_items)
-                .add_system(update_map_kill_zone)
-                .add_system(update_player_states)
-                .add_system(update_player_inventory)
-                .add_system(update_player_passive_effects)
-                .add_system(update_player_events)
-                .add_system(update_triggered_effects)
-                .add_system(update_fish_schools)
-                .add_system(update_crabs);
-
-            fixed_updates_builder
-                .add_system(fixed_update_physics_bodies)
-                .add_system(fixed_update_rigid_bodies)
-                .add_system(fixed_update_projectiles)
-                .add_system(fixed_update_triggered_effects)
-                .add_system(fixed_update_sproingers);
-        }
+        insert_resource(&mut world, Particles::new());
+
+        spawn_game_event_bus(&mut world);
+
+        insert_resource(&mut world, KillFeed::default());
+
+        insert_resource(&mut world, MatchStats::default());
+
+        insert_resource(&mut world, DeltaTime(0.0));
 
-        let updates = updates_builder
-            .with_system(update_player_animations)
-            .with_system(update_animated_sprites)
-            .with_system(update_particle_emitters)
-            .build();
+        insert_resource(&mut world, rng);
 
-        let fixed_updates = fixed_updates_builder.build();
+        world
+    }
+
+    fn from_world(mode: GameMode, world: World, players: Vec<Entity>) -> Result<Game> {
+        // Network clients only run the systems that are not authoritative, like animation, and
+        // rely on the host for the state of the game
+        let is_network_client = move |_: &World| mode == GameMode::NetworkClient;
+        let is_network_host = move |_: &World| mode == GameMode::NetworkHost;
+        let is_authoritative = move |_: &World| {
+            matches!(
+                mode,
+                GameMode::Local | GameMode::NetworkHost | GameMode::Simulation
+            )
+        };
+        let is_not_simulation = move |_: &World| mode != GameMode::Simulation;
+
+        let updates = Scheduler::builder()
+            .with_system(system!(update_game_events))
+            .with_system(system!(update_network_client).with_run_criteria(is_network_client))
+            .with_system(system!(update_network_host).with_run_criteria(is_network_host))
+            .with_system(
+                system!(update_player_controllers)
+                    .after("update_network_client")
+                    .after("update_network_host")
+                    .after("update_bots"),
+            )
+            .with_system(system!(update_bots).with_run_criteria(is_authoritative))
+            .with_system(system!(update_respawning_items).with_run_criteria(is_authoritative))
+            .with_system(system!(update_item_spawners).with_run_criteria(is_authoritative))
+            .with_system(system!(update_map_kill_zone).with_run_criteria(is_authoritative))
+            .with_system(
+                system!(update_player_status_effects)
+                    .after("update_player_controllers")
+                    .with_run_criteria(is_authoritative),
+            )
+            .with_system(
+                system!(update_player_stats)
+                    .after("update_player_status_effects")
+                    .with_run_criteria(is_authoritative),
+            )
+            .with_system(
+                system!(update_player_states)
+                    .after("update_player_controllers")
+                    .after("update_player_stats")
+                    .with_run_criteria(is_authoritative),
+            )
+            .with_system(system!(update_player_inventory).with_run_criteria(is_authoritative))
+            .with_system(
+                system!(update_player_passive_effects)
+                    .after("update_player_states")
+                    .with_run_criteria(is_authoritative),
+            )
+            .with_system(
+                system!(update_player_events)
+                    .after("update_player_passive_effects")
+                    .with_run_criteria(is_authoritative),
+            )
+            .with_system(
+                system!(update_trajectory_previews)
+                    .after("update_player_inventory")
+                    .with_run_criteria(is_not_simulation),
+            )
+            .with_system(system!(update_triggered_effects).with_run_criteria(is_authoritative))
+            .with_system(system!(update_delayed_effects).with_run_criteria(is_authoritative))
+            .with_system(system!(update_zones).with_run_criteria(is_authoritative))
+            .with_system(system!(update_beams))
+            .with_system(system!(update_fish_schools).with_run_criteria(is_authoritative))
+            .with_system(system!(update_crabs).with_run_criteria(is_authoritative))
+            .with_system(
+                parallel_system!(update_player_animations)
+                    .reads::<Player>()
+                    .reads::<PhysicsBody>()
+                    .writes::<PlayerInventory>()
+                    .writes::<Drawable>()
+                    .after("update_player_states"),
+            )
+            .with_system(
+                parallel_system!(update_player_camera_box)
+                    .reads::<Transform>()
+                    .writes::<Player>()
+                    .writes::<GameCamera>(),
+            )
+            .with_system(
+                parallel_system!(update_animated_sprites)
+                    .reads::<DeltaTime>()
+                    .writes::<Drawable>(),
+            )
+            .with_system(system!(update_particle_emitters))
+            .with_system(
+                system!(update_game_event_sounds)
+                    .after("update_game_events")
+                    .with_run_criteria(is_not_simulation),
+            )
+            .with_system(system!(update_kill_feed).after("update_game_events"))
+            .with_system(system!(update_match_stats).after("update_game_events"))
+            .build()?;
+
+        let fixed_updates = Scheduler::builder()
+            .with_system(system!(fixed_update_network_client).with_run_criteria(is_network_client))
+            .with_system(system!(fixed_update_network_host).with_run_criteria(is_network_host))
+            .with_system(system!(fixed_update_physics_bodies).with_run_criteria(is_authoritative))
+            .with_system(
+                parallel_system!(fixed_update_rigid_bodies)
+                    .reads::<DeltaTime>()
+                    .writes::<Transform>()
+                    .writes::<RigidBody>()
+                    .with_run_criteria(is_authoritative),
+            )
+            .with_system(
+                system!(fixed_update_projectiles)
+                    .after("fixed_update_rigid_bodies")
+                    .with_run_criteria(is_authoritative),
+            )
+            .with_system(
+                system!(fixed_update_triggered_effects)
+                    .after("fixed_update_physics_bodies")
+                    .with_run_criteria(is_authoritative),
+            )
+            .with_system(system!(fixed_update_sproingers).with_run_criteria(is_authoritative))
+            .build()?;
 
         let draws = Scheduler::builder()
-            .with_thread_local(draw_drawables)
-            .with_thread_local(draw_weapons_hud)
-            .with_thread_local(draw_particles)
-            .build();
+            .with_thread_local(system!(draw_drawables))
+            .with_thread_local(system!(draw_beams).after("draw_drawables"))
+            .with_thread_local(system!(draw_trajectory_previews).after("draw_drawables"))
+            .with_thread_local(system!(draw_weapons_hud).after("draw_drawables"))
+            .with_thread_local(system!(draw_particles).after("draw_drawables"))
+            .with_thread_local(system!(draw_kill_feed).after("draw_particles"))
+            .build()?;
 
         #[cfg(debug_assertions)]
         let debug_draws = Scheduler::builder()
-            .with_thread_local(debug_draw_drawables)
-            .with_thread_local(debug_draw_physics_bodies)
-            .with_thread_local(debug_draw_rigid_bodies)
-            .with_thread_local(debug_draw_active_effects)
-            .with_thread_local(debug_draw_fish_schools)
-            .build();
+            .with_thread_local(system!(debug_draw_drawables))
+            .with_thread_local(system!(debug_draw_physics_bodies))
+            .with_thread_local(system!(debug_draw_rigid_bodies))
+            .with_thread_local(system!(debug_draw_active_effects))
+            .with_thread_local(system!(debug_draw_fish_schools))
+            .build()?;
 
         let res = Game {
+            mode,
             world,
             players,
             updates,
@@ -169,17 +326,285 @@ impl Game {
             draws,
             #[cfg(debug_assertions)]
             debug_draws,
+            #[cfg(debug_assertions)]
+            is_debug_draw_enabled: true,
+            #[cfg(debug_assertions)]
+            is_system_timings_enabled: false,
+            replay_recorder: None,
+            replay_playback: None,
+            match_results: None,
         };
 
         Ok(res)
     }
 
-    fn on_update(&mut self) {
+    /// Create a game that plays back the specified replay
+    pub fn from_replay(replay: Replay) -> Result<Game> {
+        let (map, players) = replay.get_game_params()?;
+
+        let mut game = Game::new(GameMode::Local, map, &players, replay.seed)?;
+        game.replay_playback = Some(ReplayPlayback::new(replay));
+
+        Ok(game)
+    }
+
+    /// Create a game that continues from a saved match. The players will be given the
+    /// controllers found at their indices in `controllers`.
+    pub fn from_saved_match(
+        saved: SavedMatch,
+        controllers: &[PlayerControllerKind],
+    ) -> Result<Game> {
+        let (world, players) = saved.restore(controllers)?;
+
+        Game::from_world(GameMode::Local, world, players)
+    }
+
+    /// Record the match to a replay. The game should have been created with the seed passed to
+    /// the recorder.
+    pub fn with_replay_recorder(self, recorder: ReplayRecorder) -> Self {
+        Game {
+            replay_recorder: Some(recorder),
+            ..self
+        }
+    }
+
+    /// Give the game its own connection to the API, which will be available to the network
+    /// systems as a resource
+    #[cfg(feature = "ultimate")]
+    pub fn with_api(mut self, api: core::network::Api) -> Self {
+        insert_resource(&mut self.world, api);
+        self
+    }
+
+    /// Take the game's API connection, if it has one, so that it can be closed before the game
+    /// is torn down
+    #[cfg(feature = "ultimate")]
+    pub fn take_api(&mut self) -> Option<core::network::Api> {
+        let entity = self
+            .world
+            .query::<&core::network::Api>()
+            .iter()
+            .next()
+            .map(|(entity, _)| entity)?;
+
+        self.world.remove_one::<core::network::Api>(entity).ok()
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Advance the match by a single update and a single fixed update, using `dt` as the time
+    /// step. This does not read any local input and nothing is drawn, so it is used to simulate
+    /// matches as fast as possible.
+    pub fn step_simulation(&mut self, dt: f32) {
+        get_resource_mut::<DeltaTime>(&self.world).0 = dt;
+
         self.updates.execute(&mut self.world);
+        self.fixed_updates.execute(&mut self.world);
+    }
+
+    /// Stop recording and save the replay, if the match is being recorded
+    fn save_replay(&mut self) {
+        if let Some(recorder) = self.replay_recorder.take() {
+            let replay = recorder.finish();
+            if let Err(err) = replay.save_to_replays_dir() {
+                #[cfg(debug_assertions)]
+                println!("WARNING: Unable to save replay: {}", err);
+            }
+        }
+    }
+
+    /// End the match, saving the replay and the match stats. This returns the stats, so that
+    /// they can be shown on the results screen, unless a replay is being played back.
+    fn end_match(&mut self) -> Option<MatchStats> {
+        self.end_round();
+
+        self.save_replay();
+
+        if self.replay_playback.is_some() {
+            return None;
+        }
+
+        let stats = (*get_resource::<MatchStats>(&self.world)).clone();
+        if let Err(err) = stats.save_to_stats_dir() {
+            #[cfg(debug_assertions)]
+            println!("WARNING: Unable to save match stats: {}", err);
+        }
+
+        Some(stats)
+    }
+
+    /// Publish a `RoundEnded` event, with the player that has the most kills as the winner.
+    /// No more updates will run once the round has ended, so the event is delivered and the
+    /// match stats updated right away, without advancing the match time.
+    pub fn end_round(&mut self) {
+        let leader = get_resource::<MatchStats>(&self.world).get_leader();
+
+        let winner = leader.and_then(|index| {
+            self.world
+                .query::<&Player>()
+                .iter()
+                .find(|(_, player)| player.index == index)
+                .map(|(entity, _)| entity)
+        });
+
+        publish_game_event(&mut self.world, GameEvent::RoundEnded { winner });
+
+        get_resource_mut::<DeltaTime>(&self.world).0 = 0.0;
+
+        update_game_events(&mut self.world);
+        update_match_stats(&mut self.world);
+    }
+
+    /// Save the match to the quick save file
+    fn quick_save(&mut self) {
+        let saved = SavedMatch::snapshot(&self.world);
+        if let Err(err) = saved.save(get_quick_save_path()) {
+            #[cfg(debug_assertions)]
+            println!("WARNING: Unable to save match: {}", err);
+        }
+    }
+
+    /// Replace the match with the one in the quick save file, keeping the current controllers
+    fn quick_load(&mut self) {
+        let mut controllers = self
+            .world
+            .query::<(&Player, &PlayerController)>()
+            .iter()
+            .map(|(_, (player, controller))| (player.index, controller.kind.clone()))
+            .collect::<Vec<_>>();
+
+        controllers.sort_by_key(|(index, _)| *index);
+
+        let controllers = controllers
+            .into_iter()
+            .map(|(_, kind)| kind)
+            .collect::<Vec<_>>();
+
+        let res = SavedMatch::load(get_quick_save_path())
+            .and_then(|saved| Game::from_saved_match(saved, &controllers));
+
+        match res {
+            Ok(game) => {
+                self.save_replay();
+                *self = game;
+            }
+            Err(err) => {
+                #[cfg(debug_assertions)]
+                println!("WARNING: Unable to load match: {}", err);
+            }
+        }
+    }
+
+    /// Simulate a single update, and the fixed updates following it, from the replay that is
+    /// being played back. Returns `false` if the end of the replay has been reached.
+    fn step_replay_playback(&mut self) -> bool {
+        let fixed_update_cnt = match self.replay_playback.as_mut() {
+            Some(playback) => playback.next_update(&mut self.world),
+            None => None,
+        };
+
+        if let Some(fixed_update_cnt) = fixed_update_cnt {
+            self.updates.execute(&mut self.world);
+
+            for _ in 0..fixed_update_cnt {
+                self.fixed_updates.execute(&mut self.world);
+            }
+
+            true
+        } else {
+            false
+        }
+    }
+
+    fn update_replay_playback(&mut self) {
+        let step = match self.replay_playback.as_mut() {
+            Some(playback) => playback.update(),
+            None => return,
+        };
+
+        match step {
+            ReplayPlaybackStep::Advance(update_cnt) => {
+                for _ in 0..update_cnt {
+                    if !self.step_replay_playback() {
+                        break;
+                    }
+                }
+            }
+            ReplayPlaybackStep::Seek(target) => {
+                let playback = self.replay_playback.take().unwrap();
+
+                match Game::from_replay(playback.replay().clone()) {
+                    Ok(mut game) => {
+                        if let Some(new_playback) = game.replay_playback.as_mut() {
+                            new_playback.restore_controls(&playback);
+                        }
+
+                        while game
+                            .replay_playback
+                            .as_ref()
+                            .map(|playback| playback.current() < target)
+                            .unwrap_or_default()
+                        {
+                            if !game.step_replay_playback() {
+                                break;
+                            }
+                        }
+
+                        *self = game;
+                    }
+                    Err(err) => {
+                        #[cfg(debug_assertions)]
+                        println!("WARNING: Unable to seek in replay: {}", err);
+
+                        self.replay_playback = Some(playback);
+                    }
+                }
+            }
+        }
+    }
+
+    fn on_update(&mut self) {
+        // The match is over while the results are shown
+        if self.match_results.is_some() {
+            return;
+        }
+
+        get_resource_mut::<DeltaTime>(&self.world).0 = get_frame_time();
+
+        if self.replay_playback.is_some() {
+            self.update_replay_playback();
+        } else {
+            self.updates.execute(&mut self.world);
+
+            if let Some(recorder) = self.replay_recorder.as_mut() {
+                let inputs = collect_player_inputs(&self.world);
+                recorder.record_update(&inputs, get_delta_time(&self.world));
+            }
+        }
+
+        // Saving is limited to local matches, as network clients do not hold the full state
+        if self.mode == GameMode::Local && self.replay_playback.is_none() {
+            if is_key_pressed(QUICK_SAVE_KEY) {
+                self.quick_save();
+            } else if is_key_pressed(QUICK_LOAD_KEY) {
+                self.quick_load();
+            }
+        }
 
         #[cfg(debug_assertions)]
         if is_key_pressed(macroquad::prelude::KeyCode::U) {
-            crate::debug::toggle_debug_draw();
+            self.is_debug_draw_enabled = !self.is_debug_draw_enabled;
+        }
+
+        #[cfg(debug_assertions)]
+        if is_key_pressed(macroquad::prelude::KeyCode::T) {
+            self.is_system_timings_enabled = !self.is_system_timings_enabled;
         }
 
         {
@@ -193,30 +618,61 @@ impl Game {
     }
 
     fn on_fixed_update(&mut self) {
+        // Fixed updates are driven by the playback, when playing back a replay
+        if self.replay_playback.is_some() || self.match_results.is_some() {
+            return;
+        }
+
         self.fixed_updates.execute(&mut self.world);
+
+        if let Some(recorder) = self.replay_recorder.as_mut() {
+            recorder.record_fixed_update();
+        }
     }
 
     fn on_draw(&mut self) {
-        let mut camera = storage::get_mut::<GameCamera>();
-        camera.update();
-
-        {
-            let map = storage::get::<Map>();
-            map.draw(None, true);
-        }
+        get_resource_mut::<GameCamera>(&self.world).update();
+        get_resource::<Map>(&self.world).draw(None, true);
 
         self.draws.execute(&mut self.world);
 
         #[cfg(debug_assertions)]
-        if debug::is_debug_draw_enabled() {
+        if self.is_debug_draw_enabled {
             self.debug_draws.execute(&mut self.world);
         }
 
-        if gui::is_game_menu_open() {
+        #[cfg(debug_assertions)]
+        if self.is_system_timings_enabled {
+            debug::draw_system_timings(
+                &mut *root_ui(),
+                &[
+                    ("update", &self.updates),
+                    ("fixed update", &self.fixed_updates),
+                    ("draw", &self.draws),
+                    ("debug draw", &self.debug_draws),
+                ],
+            );
+        }
+
+        if let Some(playback) = &self.replay_playback {
+            draw_replay_hud(&mut *root_ui(), playback);
+        }
+
+        if let Some(stats) = &self.match_results {
+            if gui::draw_match_results(&mut *root_ui(), stats) {
+                exit_to_main_menu();
+            }
+        } else if gui::is_game_menu_open() {
             if let Some(res) = gui::draw_game_menu(&mut *root_ui()) {
                 match res.into_usize() {
-                    GAME_MENU_RESULT_MAIN_MENU => exit_to_main_menu(),
-                    GAME_MENU_RESULT_QUIT => quit_to_desktop(),
+                    GAME_MENU_RESULT_MAIN_MENU => match self.end_match() {
+                        Some(stats) => self.match_results = Some(stats),
+                        None => exit_to_main_menu(),
+                    },
+                    GAME_MENU_RESULT_QUIT => {
+                        self.end_match();
+                        quit_to_desktop();
+                    }
                     _ => {}
                 }
             }
@@ -244,49 +700,8 @@ pub fn spawn_map_objects(world: &mut World, map: &Map) -> Result<Vec<Entity>> {
     for layer in map.layers.values() {
         if layer.is_visible && layer.kind == MapLayerKind::ObjectLayer {
             for map_object in &layer.objects {
-                match map_object.kind {
-                    MapObjectKind::Decoration => {
-                        let resources = storage::get::<Resources>();
-                        let res = resources.decoration.get(&map_object.id).cloned();
-
-                        if let Some(params) = res {
-                            let decoration = spawn_decoration(world, map_object.position, params);
-                            objects.push(decoration);
-                        } else {
-                            #[cfg(debug_assertions)]
-                            println!("WARNING: Invalid decoration id '{}'", &map_object.id)
-                        }
-                    }
-                    MapObjectKind::Item => {
-                        let resources = storage::get::<Resources>();
-                        let res = resources.items.get(&map_object.id).cloned();
-
-                        if let Some(params) = res {
-                            let item = spawn_item(world, map_object.position, params)?;
-                            objects.push(item);
-                        } else {
-                            #[cfg(debug_assertions)]
-                            println!("WARNING: Invalid item id '{}'", &map_object.id)
-                        }
-                    }
-                    MapObjectKind::Environment => match map_object.id.as_str() {
-                        "sproinger" => {
-                            let sproinger = spawn_sproinger(world, map_object.position)?;
-                            objects.push(sproinger);
-                        }
-                        "crab" => {
-                            let crab = spawn_crab(world, map_object.position)?;
-                            objects.push(crab);
-                        }
-                        "fish_school" => {
-                            let fish_school = spawn_fish_school(world, map_object.position)?;
-                            objects.push(fish_school);
-                        }
-                        _ => {
-                            #[cfg(debug_assertions)]
-                            println!("WARNING: Invalid environment item id '{}'", &map_object.id)
-                        }
-                    },
+                if let Some(entity) = spawn_map_object(world, map_object)? {
+                    objects.push(entity);
                 }
             }
         }
@@ -294,3 +709,58 @@ pub fn spawn_map_objects(world: &mut World, map: &Map) -> Result<Vec<Entity>> {
 
     Ok(objects)
 }
+
+/// Spawn a single map object. This will return `None` if the id of the object is invalid.
+pub fn spawn_map_object(world: &mut World, map_object: &MapObject) -> Result<Option<Entity>> {
+    let res = match map_object.kind {
+        MapObjectKind::Decoration => {
+            let resources = storage::get::<Resources>();
+            let res = resources.decoration.get(&map_object.id).cloned();
+
+            if let Some(params) = res {
+                Some(spawn_decoration(world, map_object.position, params))
+            } else {
+                #[cfg(debug_assertions)]
+                println!("WARNING: Invalid decoration id '{}'", &map_object.id);
+
+                None
+            }
+        }
+        MapObjectKind::Item => {
+            let resources = storage::get::<Resources>();
+            let res = resources.items.get(&map_object.id).cloned();
+
+            if let Some(params) = res {
+                Some(spawn_map_item(
+                    world,
+                    map_object.position,
+                    params,
+                    &map_object.properties,
+                )?)
+            } else {
+                #[cfg(debug_assertions)]
+                println!("WARNING: Invalid item id '{}'", &map_object.id);
+
+                None
+            }
+        }
+        MapObjectKind::Environment => match map_object.id.as_str() {
+            "sproinger" => Some(spawn_sproinger(world, map_object.position)?),
+            "crab" => Some(spawn_crab(world, map_object.position)?),
+            "fish_school" => Some(spawn_fish_school(world, map_object.position)?),
+            ITEM_SPAWNER_ID => Some(spawn_item_spawner(
+                world,
+                map_object.position,
+                &map_object.properties,
+            )?),
+            _ => {
+                #[cfg(debug_assertions)]
+                println!("WARNING: Invalid environment item id '{}'", &map_object.id);
+
+                None
+            }
+        },
+    };
+
+    Ok(res)
+}
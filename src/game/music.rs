@@ -1,3 +1,5 @@
+use std::sync::Mutex;
+
 use macroquad::{
     audio::{play_sound, stop_sound, PlaySoundParams, Sound},
     experimental::collections::storage,
@@ -5,7 +7,8 @@ use macroquad::{
 
 use crate::Resources;
 
-static mut CURRENTLY_PLAYING: Option<Sound> = None;
+/// Music is played for the whole process, not for a single game, so this is shared
+static CURRENTLY_PLAYING: Mutex<Option<Sound>> = Mutex::new(None);
 
 pub fn start_music(id: &str) {
     stop_music();
@@ -21,11 +24,11 @@ pub fn start_music(id: &str) {
         },
     );
 
-    unsafe { CURRENTLY_PLAYING = Some(sound) };
+    *CURRENTLY_PLAYING.lock().unwrap() = Some(sound);
 }
 
 pub fn stop_music() {
-    if let Some(sound) = unsafe { CURRENTLY_PLAYING } {
+    if let Some(sound) = CURRENTLY_PLAYING.lock().unwrap().take() {
         stop_sound(sound);
     }
 }
@@ -0,0 +1,432 @@
+//! Replays are recorded as the seed, map and player characters that a match was started with,
+//! followed by the input of every player and the time step, for every update. Playback starts a
+//! new match from the same parameters and feeds the recorded input and time steps back into the
+//! world, so seeking is done by re-simulating the match from the beginning.
+
+use std::path::{Path, PathBuf};
+
+use macroquad::experimental::collections::storage;
+use macroquad::prelude::*;
+use macroquad::ui::{widgets, Ui};
+
+use hecs::World;
+
+use serde::{Deserialize, Serialize};
+
+use core::data::deserialize_json_file;
+use core::error::ErrorKind;
+use core::input::PlayerInput;
+use core::{formaterr, Result};
+
+use crate::ecs::get_resource_mut;
+use crate::player::{Player, PlayerController, PlayerControllerKind, PlayerParams};
+use crate::Resources;
+
+use super::DeltaTime;
+
+const REPLAYS_DIR_ENV_VAR: &str = "FISHFIGHT_REPLAYS";
+const REPLAYS_DEFAULT_DIR: &str = "./replays";
+
+pub const REPLAY_FILE_EXTENSION: &str = "json";
+
+/// The number of updates skipped, forwards or backwards, when seeking
+const REPLAY_SEEK_STEP: usize = 300;
+/// The available playback speeds, cycled through with the fast-forward key
+const REPLAY_SPEEDS: &[usize] = &[1, 2, 4, 8];
+
+const REPLAY_PAUSE_KEY: KeyCode = KeyCode::Space;
+const REPLAY_FAST_FORWARD_KEY: KeyCode = KeyCode::F;
+const REPLAY_SEEK_BACKWARD_KEY: KeyCode = KeyCode::Left;
+const REPLAY_SEEK_FORWARD_KEY: KeyCode = KeyCode::Right;
+
+const REPLAY_HUD_MARGIN: f32 = 16.0;
+
+/// Returns the directory that replays are saved to and loaded from. This can be overridden
+/// with the `FISHFIGHT_REPLAYS` environment variable.
+pub fn get_replays_dir() -> PathBuf {
+    std::env::var(REPLAYS_DIR_ENV_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(REPLAYS_DEFAULT_DIR))
+}
+
+/// Returns the paths of all the replays in the replays directory, newest first
+#[cfg(any(target_family = "unix", target_family = "windows"))]
+pub fn list_replays() -> Vec<PathBuf> {
+    let mut res = Vec::new();
+
+    if let Ok(entries) = std::fs::read_dir(get_replays_dir()) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some(REPLAY_FILE_EXTENSION) {
+                res.push(path);
+            }
+        }
+    }
+
+    res.sort();
+    res.reverse();
+
+    res
+}
+
+#[cfg(target_family = "wasm")]
+pub fn list_replays() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// This holds the input of all players for one or more consecutive updates, where neither the
+/// input nor the time step changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplayFrame {
+    /// The input of each player, ordered by player index, packed with `PlayerInput::to_bits`
+    pub inputs: Vec<u32>,
+    /// The time step of each of the updates in this frame
+    pub dt: f32,
+    /// The number of fixed updates that followed each of the updates in this frame
+    #[serde(default, rename = "fixed", skip_serializing_if = "is_zero")]
+    pub fixed_updates: u32,
+    /// The number of consecutive updates this frame covers
+    #[serde(
+        default = "ReplayFrame::default_repeat",
+        skip_serializing_if = "is_one"
+    )]
+    pub repeat: u32,
+}
+
+impl ReplayFrame {
+    fn default_repeat() -> u32 {
+        1
+    }
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+fn is_one(value: &u32) -> bool {
+    *value == 1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Replay {
+    /// The seed that the random number generator was initialized with when the match started
+    pub seed: u64,
+    /// The path of the map, as it is found in the map resources
+    pub map_id: String,
+    /// The ids of the player characters, ordered by player index
+    pub characters: Vec<String>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub async fn load<P: AsRef<Path>>(path: P) -> Result<Replay> {
+        deserialize_json_file(path).await
+    }
+
+    #[cfg(any(target_family = "unix", target_family = "windows"))]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    #[cfg(target_family = "wasm")]
+    pub fn save<P: AsRef<Path>>(&self, _: P) -> Result<()> {
+        Ok(())
+    }
+
+    /// Save the replay to the replays directory, with a file name based on its seed
+    pub fn save_to_replays_dir(&self) -> Result<PathBuf> {
+        let dir = get_replays_dir();
+
+        #[cfg(any(target_family = "unix", target_family = "windows"))]
+        std::fs::create_dir_all(&dir)?;
+
+        let path = dir
+            .join(format!("replay_{}", self.seed))
+            .with_extension(REPLAY_FILE_EXTENSION);
+
+        self.save(&path)?;
+
+        Ok(path)
+    }
+
+    /// Returns the map and the player parameters needed to start playback of the replay
+    pub fn get_game_params(&self) -> Result<(crate::Map, Vec<PlayerParams>)> {
+        let resources = storage::get::<Resources>();
+
+        let map = resources
+            .maps
+            .iter()
+            .find(|res| res.meta.path == self.map_id)
+            .map(|res| res.map.clone())
+            .ok_or_else(|| {
+                formaterr!(
+                    ErrorKind::General,
+                    "Replay: Unable to find map '{}'",
+                    &self.map_id
+                )
+            })?;
+
+        let mut players = Vec::new();
+
+        for (i, character_id) in self.characters.iter().enumerate() {
+            let character = resources
+                .player_characters
+                .get(character_id)
+                .cloned()
+                .ok_or_else(|| {
+                    formaterr!(
+                        ErrorKind::General,
+                        "Replay: Unable to find player character '{}'",
+                        character_id
+                    )
+                })?;
+
+            players.push(PlayerParams {
+                index: i as u8,
+                controller: PlayerControllerKind::Replay(PlayerInput::default()),
+                character,
+            });
+        }
+
+        Ok((map, players))
+    }
+}
+
+/// Returns a seed for a new match, based on the current time
+pub fn generate_seed() -> u64 {
+    (macroquad::miniquad::date::now() * 1000.0) as u64
+}
+
+/// Returns the input of all players in the world, ordered by player index
+pub fn collect_player_inputs(world: &World) -> Vec<PlayerInput> {
+    let mut inputs = world
+        .query::<(&Player, &PlayerController)>()
+        .iter()
+        .map(|(_, (player, controller))| (player.index, controller.input))
+        .collect::<Vec<_>>();
+
+    inputs.sort_by_key(|(index, _)| *index);
+
+    inputs.into_iter().map(|(_, input)| input).collect()
+}
+
+/// Set the input of all players with a replay controller. The input should be ordered by
+/// player index.
+pub fn apply_player_inputs(world: &mut World, inputs: &[PlayerInput]) {
+    for (_, (player, controller)) in world.query_mut::<(&Player, &mut PlayerController)>() {
+        if let PlayerControllerKind::Replay(input) = &mut controller.kind {
+            *input = inputs
+                .get(player.index as usize)
+                .copied()
+                .unwrap_or_default();
+        }
+    }
+}
+
+pub struct ReplayRecorder {
+    replay: Replay,
+    current: Option<ReplayFrame>,
+}
+
+impl ReplayRecorder {
+    pub fn new(seed: u64, map_id: &str, players: &[PlayerParams]) -> Self {
+        let mut players = players.to_vec();
+        players.sort_by_key(|params| params.index);
+
+        let characters = players
+            .into_iter()
+            .map(|params| params.character.id)
+            .collect();
+
+        ReplayRecorder {
+            replay: Replay {
+                seed,
+                map_id: map_id.to_string(),
+                characters,
+                frames: Vec::new(),
+            },
+            current: None,
+        }
+    }
+
+    /// This should be called once for every update, with the input and time step that was applied
+    pub fn record_update(&mut self, inputs: &[PlayerInput], dt: f32) {
+        self.end_frame();
+
+        self.current = Some(ReplayFrame {
+            inputs: inputs.iter().map(|input| input.to_bits()).collect(),
+            dt,
+            fixed_updates: 0,
+            repeat: 1,
+        });
+    }
+
+    /// This should be called once for every fixed update
+    pub fn record_fixed_update(&mut self) {
+        if let Some(frame) = &mut self.current {
+            frame.fixed_updates += 1;
+        }
+    }
+
+    fn end_frame(&mut self) {
+        if let Some(frame) = self.current.take() {
+            if let Some(last) = self.replay.frames.last_mut() {
+                if last.inputs == frame.inputs
+                    && last.dt == frame.dt
+                    && last.fixed_updates == frame.fixed_updates
+                {
+                    last.repeat += 1;
+                    return;
+                }
+            }
+
+            self.replay.frames.push(frame);
+        }
+    }
+
+    /// Stop recording and return the replay
+    pub fn finish(mut self) -> Replay {
+        self.end_frame();
+        self.replay
+    }
+}
+
+struct PlaybackFrame {
+    inputs: Vec<PlayerInput>,
+    dt: f32,
+    fixed_updates: u32,
+}
+
+/// The result of a playback update, telling the game what to simulate next
+pub enum ReplayPlaybackStep {
+    /// Simulate the given number of updates
+    Advance(usize),
+    /// Restart the match and simulate until the given update is reached
+    Seek(usize),
+}
+
+pub struct ReplayPlayback {
+    replay: Replay,
+    frames: Vec<PlaybackFrame>,
+    current: usize,
+    speed_index: usize,
+    pub is_paused: bool,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        let mut frames = Vec::new();
+
+        for frame in &replay.frames {
+            for _ in 0..frame.repeat {
+                frames.push(PlaybackFrame {
+                    inputs: frame
+                        .inputs
+                        .iter()
+                        .map(|&bits| PlayerInput::from_bits(bits))
+                        .collect(),
+                    dt: frame.dt,
+                    fixed_updates: frame.fixed_updates,
+                });
+            }
+        }
+
+        ReplayPlayback {
+            replay,
+            frames,
+            current: 0,
+            speed_index: 0,
+            is_paused: false,
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Returns the total number of updates in the replay
+    pub fn update_cnt(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn has_ended(&self) -> bool {
+        self.current >= self.frames.len()
+    }
+
+    pub fn speed(&self) -> usize {
+        REPLAY_SPEEDS[self.speed_index]
+    }
+
+    /// Handle playback controls and return what should be simulated this update
+    pub fn update(&mut self) -> ReplayPlaybackStep {
+        if is_key_pressed(REPLAY_PAUSE_KEY) {
+            self.is_paused = !self.is_paused;
+        }
+
+        if is_key_pressed(REPLAY_FAST_FORWARD_KEY) {
+            self.speed_index = (self.speed_index + 1) % REPLAY_SPEEDS.len();
+        }
+
+        if is_key_pressed(REPLAY_SEEK_BACKWARD_KEY) {
+            return ReplayPlaybackStep::Seek(self.current.saturating_sub(REPLAY_SEEK_STEP));
+        }
+
+        if is_key_pressed(REPLAY_SEEK_FORWARD_KEY) {
+            let target = (self.current + REPLAY_SEEK_STEP).min(self.frames.len());
+            return ReplayPlaybackStep::Advance(target - self.current);
+        }
+
+        if self.is_paused {
+            ReplayPlaybackStep::Advance(0)
+        } else {
+            ReplayPlaybackStep::Advance(self.speed())
+        }
+    }
+
+    /// Apply the input and time step of the next update to the world and return the number of
+    /// fixed updates that should follow it, or `None` if the end of the replay has been reached.
+    pub fn next_update(&mut self, world: &mut World) -> Option<u32> {
+        let frame = self.frames.get(self.current)?;
+
+        apply_player_inputs(world, &frame.inputs);
+
+        get_resource_mut::<DeltaTime>(world).0 = frame.dt;
+
+        self.current += 1;
+
+        Some(frame.fixed_updates)
+    }
+
+    /// Restore the playback state of a previous instance, after the match has been restarted
+    pub fn restore_controls(&mut self, other: &ReplayPlayback) {
+        self.speed_index = other.speed_index;
+        self.is_paused = other.is_paused;
+    }
+}
+
+pub fn draw_replay_hud(ui: &mut Ui, playback: &ReplayPlayback) {
+    let mut label = format!(
+        "REPLAY {}/{} x{}",
+        playback.current(),
+        playback.update_cnt(),
+        playback.speed()
+    );
+
+    if playback.is_paused {
+        label.push_str(" (paused)");
+    } else if playback.has_ended() {
+        label.push_str(" (ended)");
+    }
+
+    widgets::Label::new(label)
+        .position(vec2(REPLAY_HUD_MARGIN, REPLAY_HUD_MARGIN))
+        .ui(ui);
+}
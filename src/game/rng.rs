@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+
+const PCG_MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+const PCG_INCREMENT: u64 = 1_442_695_040_888_963_407;
+
+/// The random number generator of a match. This is kept as a resource in the world, so that every
+/// game has its own generator, and so that its state can be saved along with the match. Anything
+/// that affects the outcome of a match should use this, rather than the global generator, so that
+/// a match played back from the same seed and inputs plays out the same way.
+///
+/// This is a PCG-XSH-RR generator, with 64 bits of state and 32 bits of output.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Rng { state: 0 };
+
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+
+        self.state = state
+            .wrapping_mul(PCG_MULTIPLIER)
+            .wrapping_add(PCG_INCREMENT);
+
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        let rotation = (state >> 59) as u32;
+
+        xorshifted.rotate_right(rotation)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+
+    /// Returns a random value in the range from `low`, inclusive, to `high`, exclusive.
+    /// This will return `low` if the range is empty.
+    pub fn gen_range<T: RngRange>(&mut self, low: T, high: T) -> T {
+        T::gen_range(self, low, high)
+    }
+
+    /// Returns `true` with the specified probability, from `0.0` to `1.0`
+    pub fn gen_bool(&mut self, probability: f32) -> bool {
+        self.gen_range(0.0, 1.0) < probability
+    }
+}
+
+/// Implemented for the types that `Rng::gen_range` can return
+pub trait RngRange: Sized {
+    fn gen_range(rng: &mut Rng, low: Self, high: Self) -> Self;
+}
+
+impl RngRange for f32 {
+    fn gen_range(rng: &mut Rng, low: f32, high: f32) -> f32 {
+        // Only the 24 most significant bits are used, as that is the precision of an `f32`
+        let value = (rng.next_u32() >> 8) as f32 / (1u32 << 24) as f32;
+
+        low + value * (high - low)
+    }
+}
+
+macro_rules! impl_int_rng_range {
+    ($($ty:ty),*) => {
+        $(
+            impl RngRange for $ty {
+                fn gen_range(rng: &mut Rng, low: $ty, high: $ty) -> $ty {
+                    if high <= low {
+                        return low;
+                    }
+
+                    let range = (high as i64 - low as i64) as u64;
+                    let value = rng.next_u32() as u64 % range;
+
+                    (low as i64 + value as i64) as $ty
+                }
+            }
+        )*
+    };
+}
+
+impl_int_rng_range!(u8, i32, u32, usize);
@@ -0,0 +1,1073 @@
+//! Saved matches hold the state of a running match, so that it can be restored later, like when
+//! quick-saving during local play, or when recovering from a crash.
+//!
+//! Entities are not serialized component by component, as many components hold things like
+//! textures, sounds and function pointers that can not be serialized. Instead, each entity is
+//! saved as the parameters it was spawned from, along with the state that changes during the
+//! match. When restoring, the entities are spawned using the same functions as in a new match,
+//! which will rebuild everything that is derived from resource ids, and then the saved state is
+//! applied on top.
+//!
+//! Transient state, like animations, particles and the camera, is not saved. Decorations,
+//! sproingers and fish schools are spawned from the saved map, as their state is only cosmetic.
+//!
+//! Entities reference each other by the id they had when the match was saved, which is mapped to
+//! the respawned entities when restoring.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use macroquad::experimental::collections::storage;
+use macroquad::prelude::*;
+
+use hecs::{Entity, World};
+
+use serde::{Deserialize, Serialize};
+
+use core::error::ErrorKind;
+use core::math::IsZero;
+use core::{formaterr, Result, Transform};
+
+use crate::ecs::{get_resource, get_resource_mut, insert_resource};
+use crate::effects::active::delayed::{spawn_delayed_effect, DelayedEffect};
+use crate::effects::active::projectiles::{
+    spawn_projectile, Projectile, ProjectileBehavior, ProjectileKind, ProjectileParams,
+};
+use crate::effects::active::triggered::{
+    spawn_triggered_effect, TriggeredEffect, TriggeredEffectMetadata,
+};
+use crate::effects::active::zone::{spawn_zone, Zone, ZoneMetadata};
+use crate::items::{
+    spawn_item, ItemDropBehavior, RespawnInfo, RespawningItem, RespawningItemKind, Weapon,
+};
+use crate::map::{spawn_crab, Crab, CrabState, ItemSpawner, LootTableEntry};
+use crate::particles::ParticleEmitterMetadata;
+use crate::player::{
+    spawn_player, Player, PlayerAttributes, PlayerControllerKind, PlayerInventory,
+    PlayerStatusEffects, StatModifier, StatusEffectMetadata,
+};
+use crate::utils::timer::Timer;
+use crate::{
+    ActiveEffectMetadata, CollisionWorld, Item, Map, MapLayerKind, MapObjectKind, Owner,
+    PassiveEffectInstance, PassiveEffectMetadata, PhysicsBody, Resources, RigidBody,
+};
+
+use super::rng::Rng;
+use super::stats::MatchStats;
+use super::{spawn_map_object, Game};
+
+const SAVES_DIR_ENV_VAR: &str = "FISHFIGHT_SAVES";
+const SAVES_DEFAULT_DIR: &str = "./saves";
+
+pub const SAVE_FILE_EXTENSION: &str = "json";
+
+const QUICK_SAVE_FILE_NAME: &str = "quick_save";
+
+pub const QUICK_SAVE_KEY: KeyCode = KeyCode::F5;
+pub const QUICK_LOAD_KEY: KeyCode = KeyCode::F9;
+
+/// Returns the directory that saved matches are saved to and loaded from. This can be overridden
+/// with the `FISHFIGHT_SAVES` environment variable.
+pub fn get_saves_dir() -> PathBuf {
+    std::env::var(SAVES_DIR_ENV_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(SAVES_DEFAULT_DIR))
+}
+
+/// Returns the path of the quick save, in the saves directory
+pub fn get_quick_save_path() -> PathBuf {
+    get_saves_dir()
+        .join(QUICK_SAVE_FILE_NAME)
+        .with_extension(SAVE_FILE_EXTENSION)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedTransform {
+    #[serde(with = "core::json::vec2_def")]
+    pub position: Vec2,
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+impl From<&Transform> for SavedTransform {
+    fn from(transform: &Transform) -> Self {
+        SavedTransform {
+            position: transform.position,
+            rotation: transform.rotation,
+        }
+    }
+}
+
+impl From<SavedTransform> for Transform {
+    fn from(saved: SavedTransform) -> Self {
+        Transform::new(saved.position, saved.rotation)
+    }
+}
+
+/// The state of a `PhysicsBody` that changes during a match. The rest is set when the entity is
+/// spawned.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedPhysicsBody {
+    #[serde(with = "core::json::vec2_def")]
+    pub velocity: Vec2,
+    pub angular_velocity: f32,
+    pub is_on_ground: bool,
+    pub was_on_ground: bool,
+    pub is_on_platform: bool,
+    pub has_mass: bool,
+    pub has_friction: bool,
+    pub is_deactivated: bool,
+    #[serde(default)]
+    pub is_in_water: bool,
+}
+
+impl From<&PhysicsBody> for SavedPhysicsBody {
+    fn from(body: &PhysicsBody) -> Self {
+        SavedPhysicsBody {
+            velocity: body.velocity,
+            angular_velocity: body.angular_velocity,
+            is_on_ground: body.is_on_ground,
+            was_on_ground: body.was_on_ground,
+            is_on_platform: body.is_on_platform,
+            has_mass: body.has_mass,
+            has_friction: body.has_friction,
+            is_deactivated: body.is_deactivated,
+            is_in_water: body.is_in_water,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedPassiveEffect {
+    pub meta: PassiveEffectMetadata,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<u32>,
+    pub use_cnt: u32,
+    pub duration_timer: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedPlayer {
+    pub character_id: String,
+    pub transform: SavedTransform,
+    pub body: SavedPhysicsBody,
+    pub player: Player,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub passive_effects: Vec<SavedPassiveEffect>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stat_modifiers: Vec<StatModifier>,
+    #[serde(default)]
+    pub status_effects: PlayerStatusEffects,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weapon: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hat: Option<u32>,
+}
+
+/// This is used for both items and weapons, including those that are waiting to respawn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedItem {
+    pub item_id: String,
+    pub transform: SavedTransform,
+    pub body: SavedPhysicsBody,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<u32>,
+    pub use_cnt: u32,
+    /// The duration timer of an item, or the cooldown timer of a weapon
+    pub timer: f32,
+    /// The uses and drop behavior are saved, as they can be overridden for each placement in a map
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uses: Option<u32>,
+    #[serde(default)]
+    pub drop_behavior: ItemDropBehavior,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub respawn_info: Option<RespawnInfo>,
+    /// This will be set if the item is waiting to respawn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub respawn_timer: Option<Timer>,
+    /// The ammo state of a weapon with a magazine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ammo: Option<SavedAmmo>,
+    /// The state of the alternate attack of a weapon
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt_fire: Option<SavedAltFire>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedAltFire {
+    pub use_cnt: u32,
+    pub cooldown_timer: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedAmmo {
+    pub magazine: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reserve: Option<u32>,
+    /// This will be set if the weapon is reloading
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reload_timer: Option<f32>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedTriggeredEffect {
+    pub meta: TriggeredEffectMetadata,
+    pub owner: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub triggered_by: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_id: Option<String>,
+    pub transform: SavedTransform,
+    pub body: SavedPhysicsBody,
+    pub should_override_delay: bool,
+    pub is_triggered: bool,
+    pub kick_delay_timer: f32,
+    pub activation_timer: f32,
+    pub trigger_delay_timer: f32,
+    pub timed_trigger_timer: f32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedProjectile {
+    pub owner: u32,
+    pub kind: ProjectileKind,
+    #[serde(with = "core::json::vec2_def")]
+    pub origin: Vec2,
+    pub range: f32,
+    pub is_lethal: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub passive_effects: Vec<PassiveEffectMetadata>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub status_effects: Vec<StatusEffectMetadata>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub particle_effects: Vec<ParticleEmitterMetadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_id: Option<String>,
+    #[serde(default)]
+    pub behavior: ProjectileBehavior,
+    #[serde(default)]
+    pub distance_traveled: f32,
+    #[serde(default, skip_serializing_if = "u32::is_zero")]
+    pub bounce_cnt: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hit_players: Vec<u32>,
+    pub transform: SavedTransform,
+    #[serde(with = "core::json::vec2_def")]
+    pub velocity: Vec2,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedDelayedEffect {
+    pub meta: ActiveEffectMetadata,
+    pub owner: u32,
+    pub spawner: u32,
+    #[serde(with = "core::json::vec2_def")]
+    pub origin: Vec2,
+    #[serde(
+        default,
+        with = "core::json::vec2_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub offset: Option<Vec2>,
+    pub is_facing_left: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_id: Option<String>,
+    pub timer: f32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedZone {
+    pub meta: ZoneMetadata,
+    pub owner: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_id: Option<String>,
+    #[serde(with = "core::json::vec2_def")]
+    pub position: Vec2,
+    pub timer: f32,
+    pub tick_timer: f32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SavedCrabState {
+    Paused,
+    Walking { left: bool },
+    Running { scared_of: u32 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedCrab {
+    pub transform: SavedTransform,
+    pub body: SavedPhysicsBody,
+    #[serde(with = "core::json::vec2_def")]
+    pub spawn_position: Vec2,
+    pub state: SavedCrabState,
+    pub state_timer: Timer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedItemSpawner {
+    #[serde(with = "core::json::vec2_def")]
+    pub position: Vec2,
+    pub loot_table: Vec<LootTableEntry>,
+    pub spawn_interval: f32,
+    pub max_count: u32,
+    pub spawn_timer: f32,
+    /// The items spawned by the spawner that are still waiting to be picked up
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spawned: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SavedEntityKind {
+    Player(SavedPlayer),
+    Item(SavedItem),
+    TriggeredEffect(SavedTriggeredEffect),
+    Projectile(SavedProjectile),
+    Zone(SavedZone),
+    DelayedEffect(SavedDelayedEffect),
+    Crab(SavedCrab),
+    ItemSpawner(SavedItemSpawner),
+}
+
+// NOTE: We would prefer to `serde(deny_unknown_fields)` here, but we are blocked by this issue:
+// https://github.com/serde-rs/serde/issues/1358
+#[derive(Serialize, Deserialize)]
+pub struct SavedEntity {
+    /// The id of the entity, when the match was saved
+    pub id: u32,
+    #[serde(flatten)]
+    pub kind: SavedEntityKind,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedMatch {
+    /// The state of the random number generator when the match was saved
+    pub rng: Rng,
+    pub map: Map,
+    pub entities: Vec<SavedEntity>,
+    /// The stats of the match, up until it was saved
+    #[serde(default)]
+    pub stats: MatchStats,
+}
+
+impl SavedMatch {
+    /// Save the state of the match held by `world`
+    pub fn snapshot(world: &World) -> SavedMatch {
+        let rng = get_resource::<Rng>(world).clone();
+        let map = (*get_resource::<Map>(world)).clone();
+        let stats = (*get_resource::<MatchStats>(world)).clone();
+
+        let mut entities = Vec::new();
+
+        for (entity, (player, attributes, status_effects, transform, body, inventory)) in world
+            .query::<(
+                &Player,
+                &PlayerAttributes,
+                &PlayerStatusEffects,
+                &Transform,
+                &PhysicsBody,
+                &PlayerInventory,
+            )>()
+            .iter()
+        {
+            let passive_effects = player
+                .passive_effects
+                .iter()
+                .map(|effect| SavedPassiveEffect {
+                    meta: effect.meta.clone(),
+                    item: effect.item.map(|e| e.id()),
+                    use_cnt: effect.use_cnt,
+                    duration_timer: effect.duration_timer,
+                })
+                .collect();
+
+            let saved = SavedPlayer {
+                character_id: attributes.character_id.clone(),
+                transform: transform.into(),
+                body: body.into(),
+                player: Player {
+                    passive_effects: Vec::new(),
+                    ..*player
+                },
+                passive_effects,
+                stat_modifiers: attributes.stat_modifiers.clone(),
+                status_effects: status_effects.clone(),
+                weapon: inventory.weapon.map(|e| e.id()),
+                items: inventory.items.iter().map(|e| e.id()).collect(),
+                hat: inventory.hat.map(|e| e.id()),
+            };
+
+            entities.push(SavedEntity {
+                id: entity.id(),
+                kind: SavedEntityKind::Player(saved),
+            });
+        }
+
+        let owner_of = |entity: Entity| world.get::<Owner>(entity).ok().map(|owner| owner.0.id());
+
+        for (entity, (item, transform, body)) in
+            world.query::<(&Item, &Transform, &PhysicsBody)>().iter()
+        {
+            let saved = SavedItem {
+                item_id: item.id.clone(),
+                transform: transform.into(),
+                body: body.into(),
+                owner: owner_of(entity),
+                use_cnt: item.use_cnt,
+                timer: item.duration_timer,
+                uses: item.uses,
+                drop_behavior: item.drop_behavior.clone(),
+                respawn_info: item.respawn_info,
+                respawn_timer: None,
+                ammo: None,
+                alt_fire: None,
+            };
+
+            entities.push(SavedEntity {
+                id: entity.id(),
+                kind: SavedEntityKind::Item(saved),
+            });
+        }
+
+        for (entity, (weapon, transform, body)) in
+            world.query::<(&Weapon, &Transform, &PhysicsBody)>().iter()
+        {
+            let saved = SavedItem {
+                item_id: weapon.id.clone(),
+                transform: transform.into(),
+                body: body.into(),
+                owner: owner_of(entity),
+                use_cnt: weapon.use_cnt,
+                timer: weapon.cooldown_timer,
+                uses: weapon.uses,
+                drop_behavior: weapon.drop_behavior.clone(),
+                respawn_info: weapon.respawn_info,
+                respawn_timer: None,
+                ammo: weapon.magazine.as_ref().map(|_| SavedAmmo {
+                    magazine: weapon.magazine_ammo,
+                    reserve: weapon.reserve_ammo,
+                    reload_timer: if weapon.is_reloading {
+                        Some(weapon.reload_timer)
+                    } else {
+                        None
+                    },
+                }),
+                alt_fire: weapon.alt_fire.as_ref().map(|_| SavedAltFire {
+                    use_cnt: weapon.alt_use_cnt,
+                    cooldown_timer: weapon.alt_cooldown_timer,
+                }),
+            };
+
+            entities.push(SavedEntity {
+                id: entity.id(),
+                kind: SavedEntityKind::Item(saved),
+            });
+        }
+
+        for (entity, (respawning_item, transform, body)) in world
+            .query::<(&RespawningItem, &Transform, &PhysicsBody)>()
+            .iter()
+        {
+            let (item_id, use_cnt, timer, uses, drop_behavior) = match &respawning_item.kind {
+                RespawningItemKind::Weapon(weapon) => (
+                    weapon.id.clone(),
+                    weapon.use_cnt,
+                    weapon.cooldown_timer,
+                    weapon.uses,
+                    weapon.drop_behavior.clone(),
+                ),
+                RespawningItemKind::Item(item) => (
+                    item.id.clone(),
+                    item.use_cnt,
+                    item.duration_timer,
+                    item.uses,
+                    item.drop_behavior.clone(),
+                ),
+            };
+
+            let saved = SavedItem {
+                item_id,
+                transform: transform.into(),
+                body: body.into(),
+                owner: owner_of(entity),
+                use_cnt,
+                timer,
+                uses,
+                drop_behavior,
+                respawn_info: Some(respawning_item.info),
+                respawn_timer: Some(respawning_item.timer),
+                // The ammo and alternate attack of a weapon is reset when it respawns
+                ammo: None,
+                alt_fire: None,
+            };
+
+            entities.push(SavedEntity {
+                id: entity.id(),
+                kind: SavedEntityKind::Item(saved),
+            });
+        }
+
+        for (entity, (effect, transform, body)) in world
+            .query::<(&TriggeredEffect, &Transform, &PhysicsBody)>()
+            .iter()
+        {
+            let saved = SavedTriggeredEffect {
+                meta: effect.meta.clone(),
+                owner: effect.owner.id(),
+                triggered_by: effect.triggered_by.map(|e| e.id()),
+                source_id: effect.source_id.clone(),
+                transform: transform.into(),
+                body: body.into(),
+                should_override_delay: effect.should_override_delay,
+                is_triggered: effect.is_triggered,
+                kick_delay_timer: effect.kick_delay_timer,
+                activation_timer: effect.activation_timer,
+                trigger_delay_timer: effect.trigger_delay_timer,
+                timed_trigger_timer: effect.timed_trigger_timer,
+            };
+
+            entities.push(SavedEntity {
+                id: entity.id(),
+                kind: SavedEntityKind::TriggeredEffect(saved),
+            });
+        }
+
+        for (entity, (projectile, transform, body)) in world
+            .query::<(&Projectile, &Transform, &RigidBody)>()
+            .iter()
+        {
+            let saved = SavedProjectile {
+                owner: projectile.owner.id(),
+                kind: projectile.kind.clone(),
+                origin: projectile.origin,
+                range: projectile.range,
+                is_lethal: projectile.is_lethal,
+                passive_effects: projectile.passive_effects.clone(),
+                status_effects: projectile.status_effects.clone(),
+                particle_effects: projectile.particle_effects.clone(),
+                source_id: projectile.source_id.clone(),
+                behavior: projectile.behavior.clone(),
+                distance_traveled: projectile.distance_traveled,
+                bounce_cnt: projectile.bounce_cnt,
+                hit_players: projectile.hit_players.iter().map(|e| e.id()).collect(),
+                transform: transform.into(),
+                velocity: body.velocity,
+            };
+
+            entities.push(SavedEntity {
+                id: entity.id(),
+                kind: SavedEntityKind::Projectile(saved),
+            });
+        }
+
+        for (entity, (zone, transform)) in world.query::<(&Zone, &Transform)>().iter() {
+            let saved = SavedZone {
+                meta: zone.meta.clone(),
+                owner: zone.owner.id(),
+                source_id: zone.source_id.clone(),
+                position: transform.position,
+                timer: zone.timer,
+                tick_timer: zone.tick_timer,
+            };
+
+            entities.push(SavedEntity {
+                id: entity.id(),
+                kind: SavedEntityKind::Zone(saved),
+            });
+        }
+
+        for (entity, effect) in world.query::<&DelayedEffect>().iter() {
+            let saved = SavedDelayedEffect {
+                meta: effect.meta.clone(),
+                owner: effect.owner.id(),
+                spawner: effect.spawner.id(),
+                origin: effect.origin,
+                offset: effect.offset,
+                is_facing_left: effect.is_facing_left,
+                source_id: effect.source_id.clone(),
+                timer: effect.timer,
+            };
+
+            entities.push(SavedEntity {
+                id: entity.id(),
+                kind: SavedEntityKind::DelayedEffect(saved),
+            });
+        }
+
+        for (entity, (crab, transform, body)) in
+            world.query::<(&Crab, &Transform, &PhysicsBody)>().iter()
+        {
+            let state = match crab.state {
+                CrabState::Paused => SavedCrabState::Paused,
+                CrabState::Walking { left } => SavedCrabState::Walking { left },
+                CrabState::Running { scared_of } => SavedCrabState::Running {
+                    scared_of: scared_of.id(),
+                },
+            };
+
+            let saved = SavedCrab {
+                transform: transform.into(),
+                body: body.into(),
+                spawn_position: crab.spawn_position,
+                state,
+                state_timer: crab.state_timer,
+            };
+
+            entities.push(SavedEntity {
+                id: entity.id(),
+                kind: SavedEntityKind::Crab(saved),
+            });
+        }
+
+        for (entity, (spawner, transform)) in world.query::<(&ItemSpawner, &Transform)>().iter() {
+            let saved = SavedItemSpawner {
+                position: transform.position,
+                loot_table: spawner.loot_table.clone(),
+                spawn_interval: spawner.spawn_interval,
+                max_count: spawner.max_count,
+                spawn_timer: spawner.spawn_timer,
+                spawned: spawner.spawned.iter().map(|e| e.id()).collect(),
+            };
+
+            entities.push(SavedEntity {
+                id: entity.id(),
+                kind: SavedEntityKind::ItemSpawner(saved),
+            });
+        }
+
+        SavedMatch {
+            rng,
+            map,
+            entities,
+            stats,
+        }
+    }
+
+    /// Restore the match in a new world. The players will be given the controllers found at their
+    /// indices in `controllers`.
+    /// This returns the world along with the player entities.
+    pub fn restore(self, controllers: &[PlayerControllerKind]) -> Result<(World, Vec<Entity>)> {
+        let SavedMatch {
+            rng,
+            map,
+            entities: saved_entities,
+            stats,
+        } = self;
+
+        let mut world = Game::create_world(&map, rng.clone());
+
+        for layer in map.layers.values() {
+            if layer.is_visible && layer.kind == MapLayerKind::ObjectLayer {
+                for map_object in &layer.objects {
+                    let is_cosmetic = match map_object.kind {
+                        MapObjectKind::Decoration => true,
+                        MapObjectKind::Environment => {
+                            matches!(map_object.id.as_str(), "sproinger" | "fish_school")
+                        }
+                        MapObjectKind::Item => false,
+                    };
+
+                    if is_cosmetic {
+                        spawn_map_object(&mut world, map_object)?;
+                    }
+                }
+            }
+        }
+
+        let mut entities = HashMap::new();
+        let mut players = Vec::new();
+
+        // Players and items are spawned first, as the other entities can reference them
+        for saved in &saved_entities {
+            match &saved.kind {
+                SavedEntityKind::Player(player) => {
+                    let controller = controllers
+                        .get(player.player.index as usize)
+                        .cloned()
+                        .ok_or_else(|| {
+                            formaterr!(
+                                ErrorKind::General,
+                                "SavedMatch: No controller for player {}",
+                                player.player.index
+                            )
+                        })?;
+
+                    let entity = restore_player(&mut world, player, controller)?;
+
+                    entities.insert(saved.id, entity);
+                    players.push((player.player.index, entity));
+                }
+                SavedEntityKind::Item(item) => {
+                    let entity = restore_item(&mut world, item)?;
+                    entities.insert(saved.id, entity);
+                }
+                _ => {}
+            }
+        }
+
+        let get_entity = |id: u32| {
+            entities.get(&id).copied().ok_or_else(|| {
+                formaterr!(
+                    ErrorKind::General,
+                    "SavedMatch: Reference to unknown entity '{}'",
+                    id
+                )
+            })
+        };
+
+        for saved in saved_entities {
+            match saved.kind {
+                SavedEntityKind::Player(player) => {
+                    let entity = get_entity(saved.id)?;
+
+                    {
+                        let mut inventory = world.get_mut::<PlayerInventory>(entity)?;
+
+                        inventory.weapon = player.weapon.map(get_entity).transpose()?;
+                        inventory.hat = player.hat.map(get_entity).transpose()?;
+                        inventory.items = player
+                            .items
+                            .into_iter()
+                            .map(get_entity)
+                            .collect::<Result<_>>()?;
+                    }
+
+                    let mut passive_effects = Vec::new();
+                    for effect in player.passive_effects {
+                        let item = effect.item.map(get_entity).transpose()?;
+
+                        let mut instance = PassiveEffectInstance::new(item, effect.meta);
+                        instance.use_cnt = effect.use_cnt;
+                        instance.duration_timer = effect.duration_timer;
+
+                        passive_effects.push(instance);
+                    }
+
+                    world.get_mut::<Player>(entity)?.passive_effects = passive_effects;
+                }
+                SavedEntityKind::Item(item) => {
+                    if let Some(owner) = item.owner {
+                        let owner = get_entity(owner)?;
+                        world.insert_one(get_entity(saved.id)?, Owner(owner))?;
+                    }
+                }
+                SavedEntityKind::TriggeredEffect(effect) => {
+                    let owner = get_entity(effect.owner)?;
+
+                    let entity = spawn_triggered_effect(
+                        &mut world,
+                        owner,
+                        effect.transform.position,
+                        false,
+                        effect.meta,
+                    )?;
+
+                    apply_saved_body(&mut world, entity, effect.transform, effect.body)?;
+
+                    let mut triggered_effect = world.get_mut::<TriggeredEffect>(entity)?;
+
+                    // Triggers can be despawned along with the entity that triggered them, so a
+                    // missing reference is not treated as an error here
+                    triggered_effect.triggered_by = effect
+                        .triggered_by
+                        .and_then(|id| entities.get(&id).copied());
+
+                    triggered_effect.source_id = effect.source_id;
+                    triggered_effect.should_override_delay = effect.should_override_delay;
+                    triggered_effect.is_triggered = effect.is_triggered;
+                    triggered_effect.kick_delay_timer = effect.kick_delay_timer;
+                    triggered_effect.activation_timer = effect.activation_timer;
+                    triggered_effect.trigger_delay_timer = effect.trigger_delay_timer;
+                    triggered_effect.timed_trigger_timer = effect.timed_trigger_timer;
+                }
+                SavedEntityKind::Projectile(projectile) => {
+                    let owner = get_entity(projectile.owner)?;
+
+                    let entity = spawn_projectile(
+                        &mut world,
+                        owner,
+                        projectile.kind,
+                        projectile.origin,
+                        projectile.velocity,
+                        projectile.range,
+                        ProjectileParams {
+                            is_lethal: projectile.is_lethal,
+                            passive_effects: projectile.passive_effects,
+                            status_effects: projectile.status_effects,
+                            particle_effects: projectile.particle_effects,
+                            source_id: projectile.source_id,
+                            behavior: projectile.behavior,
+                        },
+                    );
+
+                    *world.get_mut::<Transform>(entity)? = projectile.transform.into();
+
+                    let mut projectile_component = world.get_mut::<Projectile>(entity)?;
+                    projectile_component.distance_traveled = projectile.distance_traveled;
+                    projectile_component.bounce_cnt = projectile.bounce_cnt;
+                    projectile_component.hit_players = projectile
+                        .hit_players
+                        .iter()
+                        .filter_map(|id| entities.get(id).copied())
+                        .collect();
+                }
+                SavedEntityKind::Zone(zone) => {
+                    let owner = get_entity(zone.owner)?;
+
+                    // The offset was already applied to the saved position
+                    let meta = ZoneMetadata {
+                        offset: Vec2::ZERO,
+                        ..zone.meta
+                    };
+
+                    let entity = spawn_zone(&mut world, owner, zone.position, false, meta);
+
+                    let mut zone_component = world.get_mut::<Zone>(entity)?;
+                    zone_component.source_id = zone.source_id;
+                    zone_component.timer = zone.timer;
+                    zone_component.tick_timer = zone.tick_timer;
+                }
+                SavedEntityKind::DelayedEffect(effect) => {
+                    let owner = get_entity(effect.owner)?;
+
+                    // The spawner might have been despawned before the match was saved, like a
+                    // triggered effect, in which case the owner is used as spawner in stead
+                    let spawner = entities.get(&effect.spawner).copied().unwrap_or(owner);
+
+                    let entity = spawn_delayed_effect(
+                        &mut world,
+                        owner,
+                        spawner,
+                        effect.origin,
+                        effect.source_id,
+                        effect.meta,
+                    );
+
+                    let mut delayed_effect = world.get_mut::<DelayedEffect>(entity)?;
+                    delayed_effect.origin = effect.origin;
+                    delayed_effect.offset = effect.offset;
+                    delayed_effect.is_facing_left = effect.is_facing_left;
+                    delayed_effect.timer = effect.timer;
+                }
+                SavedEntityKind::Crab(crab) => {
+                    let entity = spawn_crab(&mut world, crab.spawn_position)?;
+
+                    apply_saved_body(&mut world, entity, crab.transform, crab.body)?;
+
+                    let state = match crab.state {
+                        SavedCrabState::Paused => CrabState::Paused,
+                        SavedCrabState::Walking { left } => CrabState::Walking { left },
+                        // The crab will calm down if whatever scared it is gone
+                        SavedCrabState::Running { scared_of } => entities
+                            .get(&scared_of)
+                            .map(|&scared_of| CrabState::Running { scared_of })
+                            .unwrap_or_default(),
+                    };
+
+                    let mut crab_component = world.get_mut::<Crab>(entity)?;
+                    crab_component.state = state;
+                    crab_component.state_timer = crab.state_timer;
+                }
+                SavedEntityKind::ItemSpawner(spawner) => {
+                    let mut spawner_component = ItemSpawner::new(
+                        spawner.loot_table,
+                        spawner.spawn_interval,
+                        spawner.max_count,
+                    );
+
+                    spawner_component.spawn_timer = spawner.spawn_timer;
+                    spawner_component.spawned = spawner
+                        .spawned
+                        .into_iter()
+                        .map(get_entity)
+                        .collect::<Result<_>>()?;
+
+                    world.spawn((spawner_component, Transform::from(spawner.position)));
+                }
+            }
+        }
+
+        insert_resource(&mut world, map);
+        insert_resource(&mut world, stats);
+
+        // The cosmetic map objects use the random number generator when they are spawned, so it
+        // is reset to the saved state once they have been
+        insert_resource(&mut world, rng);
+
+        players.sort_by_key(|&(index, _)| index);
+        let players = players.into_iter().map(|(_, entity)| entity).collect();
+
+        Ok((world, players))
+    }
+
+    #[cfg(any(target_family = "unix", target_family = "windows"))]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SavedMatch> {
+        let json = std::fs::read_to_string(path)?;
+        let res = serde_json::from_str(&json)?;
+        Ok(res)
+    }
+
+    #[cfg(target_family = "wasm")]
+    pub fn load<P: AsRef<Path>>(_: P) -> Result<SavedMatch> {
+        Err(formaterr!(
+            ErrorKind::File,
+            "SavedMatch: Loading saved matches is not supported on this platform"
+        ))
+    }
+
+    #[cfg(any(target_family = "unix", target_family = "windows"))]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let json = serde_json::to_string(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    #[cfg(target_family = "wasm")]
+    pub fn save<P: AsRef<Path>>(&self, _: P) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn apply_saved_body(
+    world: &mut World,
+    entity: Entity,
+    transform: SavedTransform,
+    saved: SavedPhysicsBody,
+) -> Result<()> {
+    let mut body = world.get_mut::<PhysicsBody>(entity)?;
+
+    body.velocity = saved.velocity;
+    body.angular_velocity = saved.angular_velocity;
+    body.is_on_ground = saved.is_on_ground;
+    body.was_on_ground = saved.was_on_ground;
+    body.is_on_platform = saved.is_on_platform;
+    body.has_mass = saved.has_mass;
+    body.has_friction = saved.has_friction;
+    body.is_deactivated = saved.is_deactivated;
+    body.is_in_water = saved.is_in_water;
+
+    get_resource_mut::<CollisionWorld>(world)
+        .set_actor_position(body.actor, transform.position + body.offset);
+
+    *world.get_mut::<Transform>(entity)? = transform.into();
+
+    Ok(())
+}
+
+fn restore_player(
+    world: &mut World,
+    saved: &SavedPlayer,
+    controller: PlayerControllerKind,
+) -> Result<Entity> {
+    let character = storage::get::<Resources>()
+        .player_characters
+        .get(&saved.character_id)
+        .cloned()
+        .ok_or_else(|| {
+            formaterr!(
+                ErrorKind::General,
+                "SavedMatch: Unable to find player character '{}'",
+                &saved.character_id
+            )
+        })?;
+
+    let entity = spawn_player(
+        world,
+        saved.player.index,
+        saved.transform.position,
+        controller,
+        character,
+    );
+
+    apply_saved_body(world, entity, saved.transform, saved.body)?;
+
+    *world.get_mut::<Player>(entity)? = Player {
+        passive_effects: Vec::new(),
+        ..saved.player
+    };
+
+    world.get_mut::<PlayerAttributes>(entity)?.stat_modifiers = saved.stat_modifiers.clone();
+    *world.get_mut::<PlayerStatusEffects>(entity)? = saved.status_effects.clone();
+
+    Ok(entity)
+}
+
+fn restore_item(world: &mut World, saved: &SavedItem) -> Result<Entity> {
+    let meta = storage::get::<Resources>()
+        .items
+        .get(&saved.item_id)
+        .cloned()
+        .ok_or_else(|| {
+            formaterr!(
+                ErrorKind::General,
+                "SavedMatch: Unable to find item '{}'",
+                &saved.item_id
+            )
+        })?;
+
+    let entity = spawn_item(world, saved.transform.position, meta)?;
+
+    apply_saved_body(world, entity, saved.transform, saved.body)?;
+
+    if let Ok(mut item) = world.get_mut::<Item>(entity) {
+        item.use_cnt = saved.use_cnt;
+        item.duration_timer = saved.timer;
+        item.uses = saved.uses;
+        item.drop_behavior = saved.drop_behavior.clone();
+        item.respawn_info = saved.respawn_info;
+    }
+
+    if let Ok(mut weapon) = world.get_mut::<Weapon>(entity) {
+        weapon.use_cnt = saved.use_cnt;
+        weapon.cooldown_timer = saved.timer;
+        weapon.uses = saved.uses;
+        weapon.drop_behavior = saved.drop_behavior.clone();
+        weapon.respawn_info = saved.respawn_info;
+
+        if let Some(ammo) = &saved.ammo {
+            weapon.magazine_ammo = ammo.magazine;
+            weapon.reserve_ammo = ammo.reserve;
+            weapon.is_reloading = ammo.reload_timer.is_some();
+            weapon.reload_timer = ammo.reload_timer.unwrap_or_default();
+        }
+
+        if let Some(alt_fire) = &saved.alt_fire {
+            weapon.alt_use_cnt = alt_fire.use_cnt;
+            weapon.alt_cooldown_timer = alt_fire.cooldown_timer;
+        }
+    }
+
+    if let (Some(info), Some(timer)) = (saved.respawn_info, saved.respawn_timer) {
+        let kind = if let Ok(weapon) = world.remove_one::<Weapon>(entity) {
+            RespawningItemKind::Weapon(weapon)
+        } else {
+            RespawningItemKind::Item(world.remove_one::<Item>(entity)?)
+        };
+
+        world.insert_one(entity, RespawningItem { timer, info, kind })?;
+    }
+
+    Ok(entity)
+}
@@ -0,0 +1,511 @@
+//! Balance simulations run a number of bot-vs-bot matches on a map, without rendering, and
+//! aggregate the results by the weapon each bot was given. They are started from the command line,
+//! with `--simulate`, and the results are printed and saved to the stats directory.
+//!
+//! Every bot is given a random weapon from the weapon set, which it is given again whenever it is
+//! unarmed, and the items and item spawners placed on the map are not spawned, so that the results
+//! only reflect the weapons being compared. A match ends when a player reaches the kill limit, or when the time
+//! limit is reached, and is won by the player with the most kills. Matches where the lead is
+//! shared are counted as draws.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use macroquad::experimental::collections::storage;
+
+use hecs::World;
+
+use serde::{Deserialize, Serialize};
+
+use core::error::ErrorKind;
+use core::input::PlayerInput;
+use core::{formaterr, Result, Transform};
+
+use crate::ecs::get_resource;
+use crate::game::rng::Rng;
+use crate::game::sound::set_sound_effects_muted;
+use crate::game::stats::{get_stats_dir, MatchStats, STATS_FILE_EXTENSION};
+use crate::game::{Game, GameMode};
+use crate::items::{spawn_item, MapItemKind, MapItemMetadata};
+use crate::map::{Map, ITEM_SPAWNER_ID};
+use crate::player::{
+    Player, PlayerCharacterMetadata, PlayerControllerKind, PlayerInventory, PlayerParams,
+    PlayerState,
+};
+use crate::{MapObjectKind, Resources};
+
+const SIMULATE_ARG: &str = "--simulate";
+const MAP_ARG: &str = "--map";
+const WEAPONS_ARG: &str = "--weapons";
+const MATCHES_ARG: &str = "--matches";
+const PLAYERS_ARG: &str = "--players";
+const KILL_LIMIT_ARG: &str = "--kill-limit";
+const TIME_LIMIT_ARG: &str = "--time-limit";
+const SEED_ARG: &str = "--seed";
+
+const DEFAULT_MATCH_CNT: u32 = 1000;
+const DEFAULT_PLAYER_CNT: u8 = 2;
+const DEFAULT_KILL_LIMIT: u32 = 5;
+const DEFAULT_TIME_LIMIT: f32 = 120.0;
+
+/// The time step that matches are simulated at. This matches the rate of fixed updates, as a
+/// single update and a single fixed update is executed for every step.
+pub const SIMULATION_TIME_STEP: f32 = 1.0 / 60.0;
+
+#[derive(Debug, Clone)]
+pub struct SimulationParams {
+    /// The id (path) of the map to simulate matches on. The first map will be used if this is
+    /// not specified.
+    pub map_id: Option<String>,
+    /// The ids of the weapons to compare. All weapons will be compared if this is empty.
+    pub weapon_ids: Vec<String>,
+    pub match_cnt: u32,
+    pub player_cnt: u8,
+    pub kill_limit: u32,
+    /// The maximum duration of a match, in seconds
+    pub time_limit: f32,
+    /// Every match is seeded with this, plus the index of the match. This defaults to zero, so
+    /// that the results of different runs can be compared.
+    pub seed: u64,
+}
+
+impl Default for SimulationParams {
+    fn default() -> Self {
+        SimulationParams {
+            map_id: None,
+            weapon_ids: Vec::new(),
+            match_cnt: DEFAULT_MATCH_CNT,
+            player_cnt: DEFAULT_PLAYER_CNT,
+            kill_limit: DEFAULT_KILL_LIMIT,
+            time_limit: DEFAULT_TIME_LIMIT,
+            seed: 0,
+        }
+    }
+}
+
+fn parse_arg_value<T: FromStr>(arg: &str, value: Option<String>) -> Result<T> {
+    value.and_then(|value| value.parse().ok()).ok_or_else(|| {
+        formaterr!(
+            ErrorKind::Input,
+            "Simulation: Missing or invalid value for argument '{}'",
+            arg
+        )
+    })
+}
+
+impl SimulationParams {
+    /// Parse the simulation parameters from the command line arguments, excluding the name of
+    /// the executable. This will return `None` if the first argument is not `--simulate`.
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> Result<Option<Self>> {
+        let mut args = args.peekable();
+
+        if args.peek().map(|arg| arg.as_str()) != Some(SIMULATE_ARG) {
+            return Ok(None);
+        }
+
+        args.next();
+
+        let mut params = SimulationParams::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                MAP_ARG => params.map_id = Some(parse_arg_value(&arg, args.next())?),
+                WEAPONS_ARG => {
+                    let value: String = parse_arg_value(&arg, args.next())?;
+                    params.weapon_ids = value.split(',').map(|id| id.trim().to_string()).collect();
+                }
+                MATCHES_ARG => params.match_cnt = parse_arg_value(&arg, args.next())?,
+                PLAYERS_ARG => params.player_cnt = parse_arg_value(&arg, args.next())?,
+                KILL_LIMIT_ARG => params.kill_limit = parse_arg_value(&arg, args.next())?,
+                TIME_LIMIT_ARG => params.time_limit = parse_arg_value(&arg, args.next())?,
+                SEED_ARG => params.seed = parse_arg_value(&arg, args.next())?,
+                _ => {
+                    return Err(formaterr!(
+                        ErrorKind::Input,
+                        "Simulation: Invalid argument '{}'",
+                        arg
+                    ));
+                }
+            }
+        }
+
+        if params.player_cnt < 2 {
+            return Err(formaterr!(
+                ErrorKind::Input,
+                "Simulation: At least two players are required"
+            ));
+        }
+
+        if !params.time_limit.is_finite() || params.time_limit <= 0.0 {
+            return Err(formaterr!(
+                ErrorKind::Input,
+                "Simulation: The time limit must be a positive number of seconds"
+            ));
+        }
+
+        Ok(Some(params))
+    }
+}
+
+/// The aggregated results of a weapon, over all the matches it was given to a bot in
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeaponSimulationStats {
+    /// The number of times the weapon was given to a bot at the start of a match
+    pub matches: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub shots_fired: u32,
+    pub hits: u32,
+}
+
+impl WeaponSimulationStats {
+    pub fn win_rate(&self) -> f32 {
+        if self.matches > 0 {
+            self.wins as f32 / self.matches as f32
+        } else {
+            0.0
+        }
+    }
+
+    pub fn kills_per_match(&self) -> f32 {
+        if self.matches > 0 {
+            self.kills as f32 / self.matches as f32
+        } else {
+            0.0
+        }
+    }
+
+    pub fn hit_rate(&self) -> f32 {
+        if self.shots_fired > 0 {
+            self.hits as f32 / self.shots_fired as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimulationResults {
+    pub map_id: String,
+    pub match_cnt: u32,
+    pub player_cnt: u8,
+    pub kill_limit: u32,
+    pub time_limit: f32,
+    pub seed: u64,
+    /// The aggregated results of each weapon in the weapon set, by weapon id
+    pub weapons: BTreeMap<String, WeaponSimulationStats>,
+}
+
+impl SimulationResults {
+    fn new(params: &SimulationParams, map_id: &str, weapon_ids: &[String]) -> Self {
+        let weapons = weapon_ids
+            .iter()
+            .map(|id| (id.clone(), WeaponSimulationStats::default()))
+            .collect();
+
+        SimulationResults {
+            map_id: map_id.to_string(),
+            match_cnt: 0,
+            player_cnt: params.player_cnt,
+            kill_limit: params.kill_limit,
+            time_limit: params.time_limit,
+            seed: params.seed,
+            weapons,
+        }
+    }
+
+    /// Add the stats of a match, where `weapon_ids` holds the id of the weapon given to each
+    /// player, by player index
+    fn add_match(&mut self, stats: &MatchStats, weapon_ids: &[String]) {
+        self.match_cnt += 1;
+
+        for player_stats in &stats.players {
+            let weapon_id = &weapon_ids[player_stats.index as usize];
+            let weapon = self.weapons.entry(weapon_id.clone()).or_default();
+
+            weapon.matches += 1;
+            weapon.deaths += player_stats.deaths;
+
+            match stats.winner {
+                Some(index) if index == player_stats.index => weapon.wins += 1,
+                None => weapon.draws += 1,
+                _ => {}
+            }
+
+            if let Some(weapon_stats) = player_stats.weapons.get(weapon_id) {
+                weapon.kills += weapon_stats.kills;
+                weapon.shots_fired += weapon_stats.shots_fired;
+                weapon.hits += weapon_stats.hits;
+            }
+        }
+    }
+
+    #[cfg(any(target_family = "unix", target_family = "windows"))]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    #[cfg(target_family = "wasm")]
+    pub fn save<P: AsRef<Path>>(&self, _: P) -> Result<()> {
+        Ok(())
+    }
+
+    /// Save the results to the stats directory, with a file name based on the current time
+    pub fn save_to_stats_dir(&self) -> Result<PathBuf> {
+        let dir = get_stats_dir();
+
+        #[cfg(any(target_family = "unix", target_family = "windows"))]
+        std::fs::create_dir_all(&dir)?;
+
+        let timestamp = macroquad::miniquad::date::now() as u64;
+
+        let path = dir
+            .join(format!("simulation_{}", timestamp))
+            .with_extension(STATS_FILE_EXTENSION);
+
+        self.save(&path)?;
+
+        Ok(path)
+    }
+}
+
+impl fmt::Display for SimulationResults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} matches on '{}' ({} players, kill limit {}, time limit {}s, seed {})",
+            self.match_cnt,
+            &self.map_id,
+            self.player_cnt,
+            self.kill_limit,
+            self.time_limit,
+            self.seed,
+        )?;
+
+        writeln!(
+            f,
+            "{:<24} {:>8} {:>8} {:>8} {:>8} {:>10} {:>8}",
+            "weapon", "matches", "win %", "draws", "kills", "kills/m", "hit %"
+        )?;
+
+        for (id, weapon) in &self.weapons {
+            writeln!(
+                f,
+                "{:<24} {:>8} {:>8.1} {:>8} {:>8} {:>10.2} {:>8.1}",
+                id,
+                weapon.matches,
+                weapon.win_rate() * 100.0,
+                weapon.draws,
+                weapon.kills,
+                weapon.kills_per_match(),
+                weapon.hit_rate() * 100.0,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn is_match_over(stats: &MatchStats, params: &SimulationParams) -> bool {
+    stats.duration >= params.time_limit
+        || stats
+            .players
+            .iter()
+            .any(|player| player.kills >= params.kill_limit)
+}
+
+/// Give every living player that is unarmed the weapon they were assigned, by player index
+fn equip_weapons(world: &mut World, weapons: &[MapItemMetadata]) -> Result<()> {
+    let unarmed = world
+        .query::<(&Player, &PlayerInventory, &Transform)>()
+        .iter()
+        .filter(|(_, (player, inventory, _))| {
+            player.state != PlayerState::Dead
+                && inventory.weapon.is_none()
+                && inventory.pending_weapon_replacement.is_none()
+        })
+        .map(|(entity, (player, _, transform))| (entity, player.index, transform.position))
+        .collect::<Vec<_>>();
+
+    for (entity, index, position) in unarmed {
+        let weapon = spawn_item(world, position, weapons[index as usize].clone())?;
+        world
+            .get_mut::<PlayerInventory>(entity)?
+            .pending_weapon_replacement = Some(weapon);
+    }
+
+    Ok(())
+}
+
+fn print_progress(current: u32, total: u32) {
+    print!("\rSimulating match {} of {}", current, total);
+    io::stdout().flush().ok();
+
+    if current == total {
+        println!();
+    }
+}
+
+/// Run the simulation. This should be called after resources have been loaded.
+pub fn run_simulation(params: SimulationParams) -> Result<SimulationResults> {
+    let (map_id, mut map, weapons, characters) = {
+        let resources = storage::get::<Resources>();
+
+        let map_res = match &params.map_id {
+            Some(map_id) => resources.maps.iter().find(|res| &res.meta.path == map_id),
+            None => resources.maps.first(),
+        }
+        .ok_or_else(|| {
+            formaterr!(
+                ErrorKind::General,
+                "Simulation: Unable to find map '{}'",
+                params.map_id.as_deref().unwrap_or_default()
+            )
+        })?;
+
+        let weapon_ids = if params.weapon_ids.is_empty() {
+            let mut weapon_ids = resources
+                .items
+                .iter()
+                .filter(|(_, meta)| matches!(meta.kind, MapItemKind::Weapon { .. }))
+                .map(|(id, _)| id.clone())
+                .collect::<Vec<_>>();
+
+            weapon_ids.sort();
+            weapon_ids
+        } else {
+            params.weapon_ids.clone()
+        };
+
+        let mut weapons = Vec::new();
+
+        for id in &weapon_ids {
+            match resources.items.get(id) {
+                Some(meta) if matches!(meta.kind, MapItemKind::Weapon { .. }) => {
+                    weapons.push(MapItemMetadata {
+                        // Weapons are given to the bots again, so they should not respawn
+                        respawn_delay: None,
+                        ..meta.clone()
+                    });
+                }
+                _ => {
+                    return Err(formaterr!(
+                        ErrorKind::General,
+                        "Simulation: Invalid weapon id '{}'",
+                        id
+                    ));
+                }
+            }
+        }
+
+        if weapons.is_empty() {
+            return Err(formaterr!(
+                ErrorKind::General,
+                "Simulation: No weapons to simulate"
+            ));
+        }
+
+        // The characters are sorted, so that they are picked the same way for the same seed
+        let mut characters = resources
+            .player_characters
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        characters.sort_by(|a, b| a.id.cmp(&b.id));
+
+        (
+            map_res.meta.path.clone(),
+            map_res.map.clone(),
+            weapons,
+            characters,
+        )
+    };
+
+    for layer in map.layers.values_mut() {
+        layer.objects.retain(|object| match object.kind {
+            MapObjectKind::Item => false,
+            MapObjectKind::Environment => object.id != ITEM_SPAWNER_ID,
+            MapObjectKind::Decoration => true,
+        });
+    }
+
+    let weapon_ids = weapons
+        .iter()
+        .map(|meta| meta.id.clone())
+        .collect::<Vec<_>>();
+
+    let mut results = SimulationResults::new(&params, &map_id, &weapon_ids);
+
+    // Sound effects are unmuted again before any error is returned
+    set_sound_effects_muted(true);
+    let res = simulate_matches(&params, &map, &weapons, &characters, &mut results);
+    set_sound_effects_muted(false);
+
+    res?;
+
+    Ok(results)
+}
+
+fn simulate_matches(
+    params: &SimulationParams,
+    map: &Map,
+    weapons: &[MapItemMetadata],
+    characters: &[PlayerCharacterMetadata],
+    results: &mut SimulationResults,
+) -> Result<()> {
+    for i in 0..params.match_cnt {
+        print_progress(i + 1, params.match_cnt);
+
+        let mut rng = Rng::new(params.seed + i as u64);
+
+        let mut players = Vec::new();
+        let mut assigned = Vec::new();
+
+        for index in 0..params.player_cnt {
+            let character = characters[rng.gen_range(0, characters.len())].clone();
+
+            players.push(PlayerParams {
+                index,
+                controller: PlayerControllerKind::Bot(PlayerInput::default()),
+                character,
+            });
+
+            assigned.push(weapons[rng.gen_range(0, weapons.len())].clone());
+        }
+
+        let seed = rng.next_u64();
+        let mut game = Game::new(GameMode::Simulation, map.clone(), &players, seed)?;
+
+        loop {
+            equip_weapons(game.world_mut(), &assigned)?;
+
+            game.step_simulation(SIMULATION_TIME_STEP);
+
+            if is_match_over(&get_resource::<MatchStats>(game.world()), params) {
+                game.end_round();
+                break;
+            }
+        }
+
+        let stats = get_resource::<MatchStats>(game.world());
+
+        let assigned_ids = assigned
+            .iter()
+            .map(|meta| meta.id.clone())
+            .collect::<Vec<_>>();
+
+        results.add_match(&stats, &assigned_ids);
+    }
+
+    Ok(())
+}
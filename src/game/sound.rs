@@ -1,18 +1,51 @@
-use macroquad::{audio::play_sound, prelude::collections::storage};
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use macroquad::audio::{play_sound, PlaySoundParams, Sound};
+use macroquad::prelude::collections::storage;
+
+use hecs::World;
+
+use crate::game::events::{get_game_events, GameEvent};
+use crate::player::{DEATH_SOUND_ID, PICKUP_SOUND_ID};
 use crate::Resources;
 
 /// This is a stand-in until we have volume settings
 pub const SOUND_EFFECT_VOLUME: f32 = 0.4;
 
+static IS_SOUND_EFFECTS_MUTED: AtomicBool = AtomicBool::new(false);
+
+/// Mute or unmute all sound effects, like when simulating matches without rendering
+pub fn set_sound_effects_muted(is_muted: bool) {
+    IS_SOUND_EFFECTS_MUTED.store(is_muted, Ordering::Relaxed);
+}
+
 pub fn play_sound_effect(sound_id: &str, volume_multiplier: f32) {
-    let resources = storage::get::<Resources>();
-    let sound = resources.sounds[sound_id];
+    let sound = storage::get::<Resources>().sounds[sound_id];
+    play_sound_effect_from(sound, volume_multiplier);
+}
+
+/// Play a sound effect that is not referenced by id, like the sound effect of a weapon
+pub fn play_sound_effect_from(sound: Sound, volume_multiplier: f32) {
+    if IS_SOUND_EFFECTS_MUTED.load(Ordering::Relaxed) {
+        return;
+    }
+
     play_sound(
         sound,
-        macroquad::audio::PlaySoundParams {
+        PlaySoundParams {
             looped: false,
             volume: SOUND_EFFECT_VOLUME * volume_multiplier,
         },
     );
 }
+
+/// Play sound effects in response to game events
+pub fn update_game_event_sounds(world: &mut World) {
+    for event in get_game_events(world) {
+        match event {
+            GameEvent::PlayerKilled { .. } => play_sound_effect(DEATH_SOUND_ID, 1.0),
+            GameEvent::ItemPickedUp { .. } => play_sound_effect(PICKUP_SOUND_ID, 0.4),
+            _ => {}
+        }
+    }
+}
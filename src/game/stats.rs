@@ -0,0 +1,286 @@
+//! Match stats are collected for every player during a match, from the game events and the state
+//! of the players. They are written to a JSON file in the stats directory when the match ends, and
+//! shown on the results screen, so that they can be used when balancing weapons and items.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use macroquad::prelude::*;
+
+use hecs::{Entity, World};
+
+use serde::{Deserialize, Serialize};
+
+use core::{Result, Transform};
+
+use crate::ecs::get_resource_mut;
+use crate::game::events::{get_game_events, GameEvent};
+use crate::game::get_delta_time;
+use crate::items::Weapon;
+use crate::player::{Player, PlayerAttributes, PlayerState};
+use crate::PhysicsBody;
+
+const STATS_DIR_ENV_VAR: &str = "FISHFIGHT_STATS";
+const STATS_DEFAULT_DIR: &str = "./stats";
+
+pub const STATS_FILE_EXTENSION: &str = "json";
+
+/// Returns the directory that match stats are saved to. This can be overridden with the
+/// `FISHFIGHT_STATS` environment variable.
+pub fn get_stats_dir() -> PathBuf {
+    std::env::var(STATS_DIR_ENV_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(STATS_DEFAULT_DIR))
+}
+
+/// Stats for a weapon or an item, as used by a single player
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeaponStats {
+    #[serde(default)]
+    pub shots_fired: u32,
+    #[serde(default)]
+    pub hits: u32,
+    #[serde(default)]
+    pub kills: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlayerStats {
+    pub index: u8,
+    pub character_id: String,
+    pub kills: u32,
+    pub deaths: u32,
+    /// Deaths that were not caused by another player, like falling out of the map or being hit
+    /// by your own weapon
+    pub suicides: u32,
+    /// The number of times the player hit another player
+    pub hits: u32,
+    pub items_picked_up: u32,
+    pub distance_travelled: f32,
+    pub time_airborne: f32,
+    /// Stats for the weapons and items used by the player, by their ids
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub weapons: BTreeMap<String, WeaponStats>,
+    /// The position of the player at the previous update, if alive, used to calculate distance
+    #[serde(skip)]
+    last_position: Option<Vec2>,
+}
+
+impl PlayerStats {
+    pub fn new(index: u8, character_id: &str) -> Self {
+        PlayerStats {
+            index,
+            character_id: character_id.to_string(),
+            kills: 0,
+            deaths: 0,
+            suicides: 0,
+            hits: 0,
+            items_picked_up: 0,
+            distance_travelled: 0.0,
+            time_airborne: 0.0,
+            weapons: BTreeMap::new(),
+            last_position: None,
+        }
+    }
+}
+
+/// This holds the stats of a match and is added to the world as a resource
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MatchStats {
+    /// The duration of the match, in seconds
+    pub duration: f32,
+    /// The index of the player that won the match, set when the round ends. This is `None` if
+    /// the round has not ended, or if it was a draw.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub winner: Option<u8>,
+    /// The stats of each player, ordered by player index
+    pub players: Vec<PlayerStats>,
+}
+
+impl MatchStats {
+    /// Returns the stats of the player with the specified index, adding them if they do not
+    /// exist yet
+    fn get_player_stats_mut(&mut self, index: u8, character_id: &str) -> &mut PlayerStats {
+        let i = match self
+            .players
+            .binary_search_by_key(&index, |stats| stats.index)
+        {
+            Ok(i) => i,
+            Err(i) => {
+                self.players
+                    .insert(i, PlayerStats::new(index, character_id));
+                i
+            }
+        };
+
+        &mut self.players[i]
+    }
+
+    /// Returns the index of the player with the most kills, or `None` if the lead is shared
+    pub fn get_leader(&self) -> Option<u8> {
+        let max_kills = self.players.iter().map(|player| player.kills).max()?;
+
+        let mut leaders = self
+            .players
+            .iter()
+            .filter(|player| player.kills == max_kills);
+
+        let leader = leaders.next()?;
+
+        if leaders.next().is_some() {
+            None
+        } else {
+            Some(leader.index)
+        }
+    }
+
+    #[cfg(any(target_family = "unix", target_family = "windows"))]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    #[cfg(target_family = "wasm")]
+    pub fn save<P: AsRef<Path>>(&self, _: P) -> Result<()> {
+        Ok(())
+    }
+
+    /// Save the stats to the stats directory, with a file name based on the current time
+    pub fn save_to_stats_dir(&self) -> Result<PathBuf> {
+        let dir = get_stats_dir();
+
+        #[cfg(any(target_family = "unix", target_family = "windows"))]
+        std::fs::create_dir_all(&dir)?;
+
+        let timestamp = macroquad::miniquad::date::now() as u64;
+
+        let path = dir
+            .join(format!("stats_{}", timestamp))
+            .with_extension(STATS_FILE_EXTENSION);
+
+        self.save(&path)?;
+
+        Ok(path)
+    }
+}
+
+fn get_player_key(world: &World, entity: Entity) -> Option<(u8, String)> {
+    let player = world.get::<Player>(entity).ok()?;
+    let attributes = world.get::<PlayerAttributes>(entity).ok()?;
+
+    Some((player.index, attributes.character_id.clone()))
+}
+
+pub fn update_match_stats(world: &mut World) {
+    let dt = get_delta_time(world);
+
+    let mut stats = get_resource_mut::<MatchStats>(world);
+
+    stats.duration += dt;
+
+    for event in get_game_events(world) {
+        match event {
+            GameEvent::PlayerKilled {
+                player,
+                killed_by,
+                source_id,
+            } => {
+                if let Some((index, character_id)) = get_player_key(world, player) {
+                    let player_stats = stats.get_player_stats_mut(index, &character_id);
+
+                    player_stats.deaths += 1;
+
+                    if killed_by.is_none() || killed_by == Some(player) {
+                        player_stats.suicides += 1;
+                    }
+                }
+
+                let killer = killed_by
+                    .filter(|&killer| killer != player)
+                    .and_then(|killer| get_player_key(world, killer));
+
+                if let Some((index, character_id)) = killer {
+                    let player_stats = stats.get_player_stats_mut(index, &character_id);
+
+                    player_stats.kills += 1;
+
+                    if let Some(source_id) = source_id {
+                        player_stats.weapons.entry(source_id).or_default().kills += 1;
+                    }
+                }
+            }
+            GameEvent::PlayerHit {
+                player,
+                hit_by,
+                source_id,
+            } => {
+                if hit_by == player {
+                    continue;
+                }
+
+                if let Some((index, character_id)) = get_player_key(world, hit_by) {
+                    let player_stats = stats.get_player_stats_mut(index, &character_id);
+
+                    player_stats.hits += 1;
+
+                    if let Some(source_id) = source_id {
+                        player_stats.weapons.entry(source_id).or_default().hits += 1;
+                    }
+                }
+            }
+            GameEvent::WeaponFired { player, weapon } => {
+                let weapon_id = world.get::<Weapon>(weapon).map(|weapon| weapon.id.clone());
+
+                if let (Some((index, character_id)), Ok(weapon_id)) =
+                    (get_player_key(world, player), weapon_id)
+                {
+                    let player_stats = stats.get_player_stats_mut(index, &character_id);
+                    player_stats
+                        .weapons
+                        .entry(weapon_id)
+                        .or_default()
+                        .shots_fired += 1;
+                }
+            }
+            GameEvent::ItemPickedUp { player, .. } => {
+                if let Some((index, character_id)) = get_player_key(world, player) {
+                    stats
+                        .get_player_stats_mut(index, &character_id)
+                        .items_picked_up += 1;
+                }
+            }
+            GameEvent::RoundEnded { winner } => {
+                stats.winner = winner
+                    .and_then(|winner| get_player_key(world, winner))
+                    .map(|(index, _)| index);
+            }
+            _ => {}
+        }
+    }
+
+    for (_, (player, attributes, transform, body)) in world
+        .query::<(&Player, &PlayerAttributes, &Transform, &PhysicsBody)>()
+        .iter()
+    {
+        let player_stats = stats.get_player_stats_mut(player.index, &attributes.character_id);
+
+        if player.state == PlayerState::Dead {
+            // This is reset while dead, so that respawning is not counted as movement
+            player_stats.last_position = None;
+        } else {
+            if let Some(last_position) = player_stats.last_position {
+                player_stats.distance_travelled += transform.position.distance(last_position);
+            }
+
+            player_stats.last_position = Some(transform.position);
+
+            if !body.is_on_ground {
+                player_stats.time_airborne += dt;
+            }
+        }
+    }
+}
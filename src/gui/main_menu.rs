@@ -1,4 +1,5 @@
 use std::borrow::BorrowMut;
+use std::path::PathBuf;
 
 use macroquad::{
     experimental::collections::storage,
@@ -10,6 +11,7 @@ use fishsticks::{Button, GamepadContext};
 
 use super::{draw_main_menu_background, GuiResources, Menu, MenuEntry, MenuResult, Panel};
 
+use crate::game::replay::{list_replays, Replay};
 use crate::player::{PlayerControllerKind, PlayerParams};
 use crate::{gui, EditorInputScheme, Map, Resources};
 use core::input::{is_gamepad_btn_pressed, update_gamepad_context, GameInputScheme};
@@ -24,8 +26,12 @@ const LOCAL_GAME_MENU_HEIGHT: f32 = 200.0;
 pub enum MainMenuResult {
     LocalGame {
         map: Box<Map>,
+        map_id: String,
         players: Vec<PlayerParams>,
     },
+    Replay {
+        replay: Box<Replay>,
+    },
     Editor {
         input_scheme: EditorInputScheme,
         is_new_map: bool,
@@ -41,6 +47,7 @@ enum MainMenuState {
     LocalGame,
     Settings,
     Editor(Menu),
+    Replays(Menu, Vec<PathBuf>),
     Credits,
 }
 
@@ -50,12 +57,15 @@ const ROOT_OPTION_EDITOR: usize = 1;
 // const ROOT_OPTION_SETTINGS: usize = 2;
 const ROOT_OPTION_RELOAD_RESOURCES: usize = 3;
 const ROOT_OPTION_CREDITS: usize = 4;
+const ROOT_OPTION_REPLAYS: usize = 5;
 
 const LOCAL_GAME_OPTION_SUBMIT: usize = 0;
 
 const EDITOR_OPTION_CREATE: usize = 0;
 const EDITOR_OPTION_LOAD: usize = 1;
 
+const REPLAYS_MENU_MAX_ENTRIES: usize = 10;
+
 fn build_main_menu() -> Menu {
     Menu::new(
         hash!("main_menu"),
@@ -71,6 +81,11 @@ fn build_main_menu() -> Menu {
                 title: "Editor".to_string(),
                 ..Default::default()
             },
+            MenuEntry {
+                index: ROOT_OPTION_REPLAYS,
+                title: "Replays".to_string(),
+                ..Default::default()
+            },
             // Settings haven't been implemented yet
             // MenuEntry {
             //     index: ROOT_OPTION_SETTINGS,
@@ -114,6 +129,33 @@ fn build_editor_menu() -> Menu {
     .with_cancel_button(Some("Cancel"))
 }
 
+fn build_replays_menu(paths: &[PathBuf]) -> Menu {
+    let mut entries = paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| MenuEntry {
+            index: i,
+            title: path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+    if entries.is_empty() {
+        entries.push(MenuEntry {
+            index: 0,
+            title: "No replays found".to_string(),
+            is_disabled: true,
+            ..Default::default()
+        });
+    }
+
+    Menu::new(hash!("main_menu", "replays"), MENU_WIDTH, &entries)
+        .with_cancel_button(Some("Cancel"))
+}
+
 pub async fn show_main_menu() -> MainMenuResult {
     let mut menu_state = MainMenuState::Root(build_main_menu());
 
@@ -151,6 +193,12 @@ pub async fn show_main_menu() -> MainMenuResult {
                         ROOT_OPTION_EDITOR => {
                             menu_state = MainMenuState::Editor(build_editor_menu());
                         }
+                        ROOT_OPTION_REPLAYS => {
+                            let mut paths = list_replays();
+                            paths.truncate(REPLAYS_MENU_MAX_ENTRIES);
+
+                            menu_state = MainMenuState::Replays(build_replays_menu(&paths), paths);
+                        }
                         ROOT_OPTION_RELOAD_RESOURCES => {
                             return MainMenuResult::ReloadResources;
                         }
@@ -199,6 +247,7 @@ pub async fn show_main_menu() -> MainMenuResult {
 
                             return MainMenuResult::LocalGame {
                                 map: Box::new(map_resource.map),
+                                map_id: map_resource.meta.path,
                                 players,
                             };
                         }
@@ -231,6 +280,25 @@ pub async fn show_main_menu() -> MainMenuResult {
                     }
                 }
             }
+            MainMenuState::Replays(menu_instance, paths) => {
+                if let Some(res) = menu_instance.ui(&mut *root_ui()) {
+                    if res.is_cancel() {
+                        menu_state = MainMenuState::Root(build_main_menu());
+                    } else if let Some(path) = paths.get(res.into_usize()) {
+                        match Replay::load(path).await {
+                            Ok(replay) => {
+                                return MainMenuResult::Replay {
+                                    replay: Box::new(replay),
+                                };
+                            }
+                            Err(err) => {
+                                #[cfg(debug_assertions)]
+                                println!("WARNING: Unable to load replay: {}", err);
+                            }
+                        }
+                    }
+                }
+            }
             MainMenuState::Settings => {
                 unreachable!("Settings is not implemented yet");
             }
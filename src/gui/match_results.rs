@@ -0,0 +1,103 @@
+use macroquad::{
+    experimental::collections::storage,
+    prelude::*,
+    ui::{hash, widgets, Ui},
+};
+
+use fishsticks::{Button, GamepadContext};
+
+use core::input::is_gamepad_btn_pressed;
+
+use crate::game::stats::MatchStats;
+
+use super::style::{FONT_SIZE, HEADER_FONT_SIZE, LABEL_MARGIN_V};
+use super::{GuiResources, Menu, Panel, WINDOW_MARGIN_V};
+
+const RESULTS_WIDTH: f32 = 720.0;
+
+const LINE_HEIGHT: f32 = FONT_SIZE + (LABEL_MARGIN_V * 2.0);
+const TITLE_HEIGHT: f32 = HEADER_FONT_SIZE + (LABEL_MARGIN_V * 2.0);
+
+const CONTINUE_BUTTON_TITLE: &str = "Continue";
+
+fn get_result_lines(stats: &MatchStats) -> Vec<String> {
+    let mut lines = vec![format!("Duration: {:.0}s", stats.duration)];
+
+    for player in &stats.players {
+        lines.push(String::new());
+
+        lines.push(format!(
+            "Player {} ({})",
+            player.index + 1,
+            &player.character_id
+        ));
+
+        lines.push(format!(
+            "Kills: {}  Deaths: {}  Suicides: {}  Hits: {}  Items: {}",
+            player.kills, player.deaths, player.suicides, player.hits, player.items_picked_up,
+        ));
+
+        lines.push(format!(
+            "Distance: {:.0}  Airborne: {:.1}s",
+            player.distance_travelled, player.time_airborne,
+        ));
+
+        for (id, weapon) in &player.weapons {
+            lines.push(format!(
+                "  {}: {} shots, {} hits, {} kills",
+                id, weapon.shots_fired, weapon.hits, weapon.kills,
+            ));
+        }
+    }
+
+    lines
+}
+
+/// Draw the results screen, shown at the end of a match. This will return `true` when the
+/// player chooses to continue.
+pub fn draw_match_results(ui: &mut Ui, stats: &MatchStats) -> bool {
+    let mut res = {
+        let gamepad_context = storage::get::<GamepadContext>();
+
+        is_key_pressed(KeyCode::Enter)
+            || is_gamepad_btn_pressed(Some(&gamepad_context), Button::South)
+    };
+
+    let lines = get_result_lines(stats);
+
+    let size = vec2(
+        RESULTS_WIDTH,
+        TITLE_HEIGHT
+            + (lines.len() as f32 * LINE_HEIGHT)
+            + Menu::ENTRY_HEIGHT
+            + (WINDOW_MARGIN_V * 3.0),
+    );
+
+    let position = (vec2(screen_width(), screen_height()) - size) / 2.0;
+
+    Panel::new(hash!(), size, position)
+        .with_title("Match Results", true)
+        .ui(ui, |ui, inner_size| {
+            for line in &lines {
+                ui.label(None, line);
+            }
+
+            let gui_resources = storage::get::<GuiResources>();
+            ui.push_skin(&gui_resources.skins.menu);
+
+            let button_position = vec2(0.0, inner_size.y - Menu::ENTRY_HEIGHT);
+            let button_size = vec2(inner_size.x, Menu::ENTRY_HEIGHT);
+
+            if widgets::Button::new(CONTINUE_BUTTON_TITLE)
+                .position(button_position)
+                .size(button_size)
+                .ui(ui)
+            {
+                res = true;
+            }
+
+            ui.pop_skin();
+        });
+
+    res
+}
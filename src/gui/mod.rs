@@ -4,6 +4,7 @@ mod create_map;
 mod credits;
 mod game_menu;
 mod main_menu;
+mod match_results;
 mod menu;
 mod panel;
 mod select_character;
@@ -26,6 +27,7 @@ pub use game_menu::{
     GAME_MENU_RESULT_MAIN_MENU, GAME_MENU_RESULT_QUIT,
 };
 pub use main_menu::{show_main_menu, MainMenuResult};
+pub use match_results::draw_match_results;
 pub use menu::{Menu, MenuEntry, MenuResult};
 pub use panel::{NewPanel, Panel};
 pub use select_character::show_select_characters_menu;
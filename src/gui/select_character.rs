@@ -135,7 +135,7 @@ pub async fn show_select_characters_menu(
                     .ui(&mut *root_ui(), |ui, inner_size| {
                         let animation_player = &mut animated_sprites[i];
 
-                        update_one_animated_sprite(animation_player);
+                        update_one_animated_sprite(animation_player, get_frame_time());
 
                         // TODO: Calculate scale from a fixed target size, based on ui layout
                         animation_player.scale = 2.0;
@@ -1,25 +1,32 @@
 //! Things available to spawn from the level editor
 //! Proto-mods, eventually some of the items will move to some sort of a wasm runtime
 
+use std::collections::HashMap;
+
 use hecs::{Entity, World};
-use macroquad::audio::{play_sound, PlaySoundParams, Sound};
+use macroquad::audio::Sound;
 use macroquad::experimental::collections::storage;
 use macroquad::prelude::*;
 
 use serde::{Deserialize, Serialize};
 
-use crate::game::sound::SOUND_EFFECT_VOLUME;
+use crate::game::events::{publish_game_event, GameEvent};
+use crate::game::get_delta_time;
+use crate::game::sound::play_sound_effect_from;
+use crate::map::{get_number_property, MapProperty};
 use crate::utils::timer::Timer;
 use crate::{
     ActiveEffectMetadata, AnimatedSprite, AnimatedSpriteMetadata, CollisionWorld, Drawable, Owner,
     PassiveEffectMetadata, PhysicsBody, QueuedAnimationAction, Resources,
 };
 
+use core::math::{deg_to_rad, rotate_vector};
 use core::{Result, Transform};
 
+use crate::ecs::get_resource_mut;
 use crate::effects::active::spawn_active_effect;
 use crate::particles::{ParticleEmitter, ParticleEmitterMetadata};
-use crate::physics::PhysicsBodyParams;
+use crate::physics::{self, PhysicsBodyParams};
 use crate::player::{Player, PlayerInventory, IDLE_ANIMATION_ID};
 
 pub const ITEMS_DRAW_ORDER: u32 = 1;
@@ -29,6 +36,21 @@ pub const EFFECT_ANIMATED_SPRITE_ID: &str = "effect";
 
 pub const GROUND_ANIMATION_ID: &str = "ground";
 pub const ATTACK_ANIMATION_ID: &str = "attack";
+pub const ALT_ATTACK_ANIMATION_ID: &str = "alt_attack";
+pub const CHARGE_ANIMATION_ID: &str = "charge";
+pub const RELOAD_ANIMATION_ID: &str = "reload";
+
+/// Map object property that overrides the `uses` of an item placement
+pub const ITEM_USES_PROPERTY: &str = "uses";
+/// Map object property that overrides the `respawn_delay` of an item placement. A negative delay
+/// will disable respawning.
+pub const ITEM_RESPAWN_DELAY_PROPERTY: &str = "respawn_delay";
+/// Map object property that overrides the `drop_behavior` of an item placement
+pub const ITEM_DROP_BEHAVIOR_PROPERTY: &str = "drop_behavior";
+/// Map object property holding the initial velocity of an item placement
+pub const ITEM_VELOCITY_PROPERTY: &str = "velocity";
+/// Map object property that will make an item placement face left, if `true`
+pub const ITEM_FACING_LEFT_PROPERTY: &str = "is_facing_left";
 
 /// This dictates what happens to an item when it is dropped, either manually or on death.
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -42,6 +64,33 @@ pub enum ItemDropBehavior {
     Destroy,
 }
 
+impl ItemDropBehavior {
+    const CLEAR_STATE: &'static str = "clear_state";
+    const PERSIST_STATE: &'static str = "persist_state";
+    const DESTROY: &'static str = "destroy";
+
+    pub fn options() -> &'static [&'static str] {
+        &[Self::CLEAR_STATE, Self::PERSIST_STATE, Self::DESTROY]
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            Self::CLEAR_STATE => Some(Self::ClearState),
+            Self::PERSIST_STATE => Some(Self::PersistState),
+            Self::DESTROY => Some(Self::Destroy),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::ClearState => Self::CLEAR_STATE,
+            Self::PersistState => Self::PERSIST_STATE,
+            Self::Destroy => Self::DESTROY,
+        }
+    }
+}
+
 impl Default for ItemDropBehavior {
     fn default() -> Self {
         ItemDropBehavior::ClearState
@@ -90,6 +139,7 @@ pub struct ItemParams {
     pub drop_behavior: ItemDropBehavior,
     pub deplete_behavior: ItemDepleteBehavior,
     pub is_hat: bool,
+    pub ammo: Option<u32>,
     pub respawn_info: Option<RespawnInfo>,
 }
 
@@ -104,6 +154,9 @@ pub struct Item {
     pub drop_behavior: ItemDropBehavior,
     pub deplete_behavior: ItemDepleteBehavior,
     pub is_hat: bool,
+    /// If this is set, the item is an ammo pickup that will add this amount of reserve ammo to
+    /// the weapon of the player that picks it up, in stead of being equipped
+    pub ammo: Option<u32>,
     pub duration_timer: f32,
     pub use_cnt: u32,
     pub respawn_info: Option<RespawnInfo>,
@@ -122,6 +175,7 @@ impl Item {
             deplete_behavior: params.deplete_behavior,
             respawn_info: params.respawn_info,
             is_hat: params.is_hat,
+            ammo: params.ammo,
             duration_timer: 0.0,
             use_cnt: 0,
         }
@@ -141,6 +195,11 @@ pub struct ItemMetadata {
     /// If this is `true` the item will be treated as a hat
     #[serde(default, rename = "hat", skip_serializing_if = "core::json::is_false")]
     pub is_hat: bool,
+    /// If this is specified, the item will be an ammo pickup. It will add this amount of reserve
+    /// ammo to the weapon of the player that picks it up, if the weapon has a magazine with a
+    /// limited reserve, and it is then destroyed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ammo: Option<u32>,
 }
 
 // NOTE: We would prefer to `serde(deny_unknown_fields)` here, but we are blocked by this issue:
@@ -173,12 +232,84 @@ pub struct MapItemMetadata {
     /// The parameters for the `AnimationPlayer` that will be used to draw the item
     #[serde(alias = "animation")]
     pub sprite: AnimatedSpriteMetadata,
+    /// This decides whether the item floats or sinks in water. Items that are denser than water,
+    /// which has a density of `1.0`, will sink, while items that are less dense will float.
+    #[serde(default = "default_item_density")]
+    pub density: f32,
 }
 
 fn default_respawn_delay() -> Option<f32> {
     Some(3.0)
 }
 
+fn default_item_density() -> f32 {
+    physics::DEFAULT_DENSITY
+}
+
+/// Spawn an item that is placed in a map. The metadata of the item, as well as its initial state,
+/// can be overridden for each placement, by the properties of the map object.
+pub fn spawn_map_item(
+    world: &mut World,
+    position: Vec2,
+    mut meta: MapItemMetadata,
+    properties: &HashMap<String, MapProperty>,
+) -> Result<Entity> {
+    if let Some(uses) = properties
+        .get(ITEM_USES_PROPERTY)
+        .and_then(get_number_property)
+    {
+        meta.uses = Some(uses as u32);
+    }
+
+    if let Some(respawn_delay) = properties
+        .get(ITEM_RESPAWN_DELAY_PROPERTY)
+        .and_then(get_number_property)
+    {
+        meta.respawn_delay = if respawn_delay < 0.0 {
+            None
+        } else {
+            Some(respawn_delay)
+        };
+    }
+
+    if let Some(id) = properties
+        .get(ITEM_DROP_BEHAVIOR_PROPERTY)
+        .and_then(|property| property.get_value::<String>())
+    {
+        if let Some(drop_behavior) = ItemDropBehavior::from_id(id) {
+            meta.drop_behavior = drop_behavior;
+        } else {
+            #[cfg(debug_assertions)]
+            println!("WARNING: Invalid item drop behavior '{}'", id);
+        }
+    }
+
+    let entity = spawn_item(world, position, meta)?;
+
+    if let Some(velocity) = properties
+        .get(ITEM_VELOCITY_PROPERTY)
+        .and_then(|property| property.get_value::<Vec2>())
+    {
+        world.get_mut::<PhysicsBody>(entity)?.velocity = *velocity;
+    }
+
+    let is_facing_left = properties
+        .get(ITEM_FACING_LEFT_PROPERTY)
+        .and_then(|property| property.get_value::<bool>())
+        .copied()
+        .unwrap_or_default();
+
+    if is_facing_left {
+        if let Ok(mut drawable) = world.get_mut::<Drawable>(entity) {
+            if let Some(sprite_set) = drawable.get_animated_sprite_set_mut() {
+                sprite_set.flip_all_x(true);
+            }
+        }
+    }
+
+    Ok(entity)
+}
+
 pub fn spawn_item(world: &mut World, position: Vec2, meta: MapItemMetadata) -> Result<Entity> {
     let mut sprites = Vec::new();
 
@@ -188,10 +319,11 @@ pub fn spawn_item(world: &mut World, position: Vec2, meta: MapItemMetadata) -> R
         drop_behavior,
         deplete_behavior,
         mount_offset,
+        density,
         ..
     } = meta;
 
-    let actor = storage::get_mut::<CollisionWorld>().add_actor(
+    let actor = get_resource_mut::<CollisionWorld>(world).add_actor(
         position,
         collider_size.x as i32,
         collider_size.y as i32,
@@ -214,6 +346,7 @@ pub fn spawn_item(world: &mut World, position: Vec2, meta: MapItemMetadata) -> R
                 has_mass: true,
                 has_friction: true,
                 can_rotate: meta.can_rotate,
+                density,
                 ..Default::default()
             },
         ),
@@ -233,6 +366,7 @@ pub fn spawn_item(world: &mut World, position: Vec2, meta: MapItemMetadata) -> R
                 effects,
                 duration,
                 is_hat,
+                ammo,
             } = meta;
 
             world.insert_one(
@@ -248,6 +382,7 @@ pub fn spawn_item(world: &mut World, position: Vec2, meta: MapItemMetadata) -> R
                         drop_behavior,
                         deplete_behavior,
                         is_hat,
+                        ammo,
                         respawn_info,
                     },
                 ),
@@ -268,6 +403,27 @@ pub fn spawn_item(world: &mut World, position: Vec2, meta: MapItemMetadata) -> R
                 sound_effect = storage::get::<Resources>().sounds.get(id).copied();
             }
 
+            let aim = meta.aim.clone();
+            let trajectory_preview = meta.trajectory_preview.clone();
+
+            let mut alt_sound_effect = None;
+            if let Some(id) = meta
+                .alt_fire
+                .as_ref()
+                .and_then(|alt_fire| alt_fire.sound_effect_id.as_ref())
+            {
+                alt_sound_effect = storage::get::<Resources>().sounds.get(id).copied();
+            }
+
+            let mut reload_sound_effect = None;
+            if let Some(id) = meta
+                .magazine
+                .as_ref()
+                .and_then(|magazine| magazine.sound_effect_id.as_ref())
+            {
+                reload_sound_effect = storage::get::<Resources>().sounds.get(id).copied();
+            }
+
             if let Some(effect_sprite) = meta.effect_sprite {
                 let mut sprite: AnimatedSprite = effect_sprite.into();
                 sprite.is_deactivated = true;
@@ -296,6 +452,13 @@ pub fn spawn_item(world: &mut World, position: Vec2, meta: MapItemMetadata) -> R
                 drop_behavior,
                 deplete_behavior,
                 respawn_info,
+                charge: meta.charge,
+                magazine: meta.magazine,
+                reload_sound_effect,
+                alt_fire: meta.alt_fire,
+                alt_sound_effect,
+                aim,
+                trajectory_preview,
             };
 
             world.insert_one(
@@ -325,6 +488,13 @@ pub struct WeaponParams {
     pub drop_behavior: ItemDropBehavior,
     pub deplete_behavior: ItemDepleteBehavior,
     pub respawn_info: Option<RespawnInfo>,
+    pub charge: Option<WeaponChargeMetadata>,
+    pub magazine: Option<WeaponMagazineMetadata>,
+    pub reload_sound_effect: Option<Sound>,
+    pub alt_fire: Option<WeaponAltFireMetadata>,
+    pub alt_sound_effect: Option<Sound>,
+    pub aim: Option<WeaponAimMetadata>,
+    pub trajectory_preview: Option<WeaponTrajectoryPreviewMetadata>,
 }
 
 impl Default for WeaponParams {
@@ -339,6 +509,13 @@ impl Default for WeaponParams {
             drop_behavior: Default::default(),
             deplete_behavior: Default::default(),
             respawn_info: None,
+            charge: None,
+            magazine: None,
+            reload_sound_effect: None,
+            alt_fire: None,
+            alt_sound_effect: None,
+            aim: None,
+            trajectory_preview: None,
         }
     }
 }
@@ -360,6 +537,35 @@ pub struct Weapon {
     pub cooldown_timer: f32,
     pub use_cnt: u32,
     pub respawn_info: Option<RespawnInfo>,
+    /// If this is set, the weapon must be charged, by holding the fire button, and it will be
+    /// fired when the button is released
+    pub charge: Option<WeaponChargeMetadata>,
+    pub is_charging: bool,
+    pub charge_timer: f32,
+    /// The entity holding the particle emitters that are active while the weapon is charging
+    pub charge_particle_entity: Option<Entity>,
+    /// If this is set, the weapon fires from a magazine that must be reloaded when it is empty
+    pub magazine: Option<WeaponMagazineMetadata>,
+    pub reload_sound_effect: Option<Sound>,
+    /// The ammo left in the magazine
+    pub magazine_ammo: u32,
+    /// The ammo left in reserve, used to reload the magazine. If this is `None` the reserve is
+    /// unlimited.
+    pub reserve_ammo: Option<u32>,
+    pub is_reloading: bool,
+    pub reload_timer: f32,
+    /// If this is set, the weapon has a secondary attack, with its own effects, cooldown and uses
+    pub alt_fire: Option<WeaponAltFireMetadata>,
+    pub alt_sound_effect: Option<Sound>,
+    pub alt_cooldown_timer: f32,
+    pub alt_use_cnt: u32,
+    /// If this is set, the weapon can be aimed up and down
+    pub aim: Option<WeaponAimMetadata>,
+    /// The current vertical aim angle, in degrees, relative to the direction the owner is facing
+    pub aim_angle: f32,
+    /// If this is set, the trajectory of the weapon's triggered effects is previewed while the
+    /// weapon is equipped
+    pub trajectory_preview: Option<WeaponTrajectoryPreviewMetadata>,
 }
 
 impl Weapon {
@@ -370,6 +576,23 @@ impl Weapon {
         attack_duration: f32,
         params: WeaponParams,
     ) -> Self {
+        let magazine_ammo = params
+            .magazine
+            .as_ref()
+            .map(|magazine| magazine.size)
+            .unwrap_or_default();
+
+        let reserve_ammo = params
+            .magazine
+            .as_ref()
+            .and_then(|magazine| magazine.reserve);
+
+        let alt_cooldown = params
+            .alt_fire
+            .as_ref()
+            .map(|alt_fire| alt_fire.cooldown)
+            .unwrap_or_default();
+
         Weapon {
             id: id.to_string(),
             name: params.name,
@@ -384,61 +607,247 @@ impl Weapon {
             drop_behavior: params.drop_behavior,
             deplete_behavior: params.deplete_behavior,
             respawn_info: params.respawn_info,
+            charge: params.charge,
             cooldown_timer: cooldown,
             use_cnt: 0,
+            is_charging: false,
+            charge_timer: 0.0,
+            charge_particle_entity: None,
+            magazine: params.magazine,
+            reload_sound_effect: params.reload_sound_effect,
+            magazine_ammo,
+            reserve_ammo,
+            is_reloading: false,
+            reload_timer: 0.0,
+            alt_fire: params.alt_fire,
+            alt_sound_effect: params.alt_sound_effect,
+            alt_cooldown_timer: alt_cooldown,
+            alt_use_cnt: 0,
+            aim: params.aim,
+            aim_angle: 0.0,
+            trajectory_preview: params.trajectory_preview,
+        }
+    }
+
+    /// Returns the current charge level of the weapon, from `0.0` to `1.0`, or `None` if the
+    /// weapon is not a charge weapon or it has not been charged for long enough to fire
+    pub fn get_charge_level(&self) -> Option<f32> {
+        self.charge
+            .as_ref()
+            .and_then(|charge| charge.get_level(self.charge_timer))
+    }
+
+    /// Stops charging the weapon, without firing it. This returns the entity holding the charge
+    /// particle emitters, if any, which should be despawned.
+    pub fn cancel_charge(&mut self) -> Option<Entity> {
+        self.is_charging = false;
+        self.charge_timer = 0.0;
+        self.charge_particle_entity.take()
+    }
+
+    /// Returns `true` if the weapon can be fired, as far as its magazine is concerned
+    pub fn has_ammo(&self) -> bool {
+        self.magazine.is_none() || (!self.is_reloading && self.magazine_ammo > 0)
+    }
+
+    /// Returns `true` if both the magazine and the reserve is empty
+    pub fn is_out_of_ammo(&self) -> bool {
+        self.magazine.is_some()
+            && !self.is_reloading
+            && self.magazine_ammo == 0
+            && self.reserve_ammo == Some(0)
+    }
+
+    /// Returns `true` if the magazine is not full and there is ammo left in the reserve
+    pub fn can_reload(&self) -> bool {
+        if let Some(magazine) = &self.magazine {
+            !self.is_reloading && self.magazine_ammo < magazine.size && self.reserve_ammo != Some(0)
+        } else {
+            false
         }
     }
+
+    /// Starts reloading the weapon, if it can be reloaded. Returns `true` if a reload was started.
+    pub fn start_reload(&mut self) -> bool {
+        if self.can_reload() {
+            self.is_reloading = true;
+            self.reload_timer = 0.0;
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Moves ammo from the reserve to the magazine and ends the reload
+    pub fn finish_reload(&mut self) {
+        if let Some(magazine) = &self.magazine {
+            let mut amount = magazine.size.saturating_sub(self.magazine_ammo);
+
+            if let Some(reserve_ammo) = &mut self.reserve_ammo {
+                amount = amount.min(*reserve_ammo);
+                *reserve_ammo -= amount;
+            }
+
+            self.magazine_ammo += amount;
+        }
+
+        self.is_reloading = false;
+        self.reload_timer = 0.0;
+    }
+
+    pub fn cancel_reload(&mut self) {
+        self.is_reloading = false;
+        self.reload_timer = 0.0;
+    }
+
+    /// Adds ammo to the reserve, up to the maximum reserve. Returns `true` if any ammo was added,
+    /// which will not be the case if the weapon has no magazine or an unlimited reserve.
+    pub fn add_reserve_ammo(&mut self, amount: u32) -> bool {
+        if let (Some(magazine), Some(reserve_ammo)) = (&self.magazine, &mut self.reserve_ammo) {
+            let mut new_reserve_ammo = reserve_ammo.saturating_add(amount);
+            if let Some(max_reserve) = magazine.max_reserve {
+                new_reserve_ammo = new_reserve_ammo.min(max_reserve);
+            }
+
+            if new_reserve_ammo > *reserve_ammo {
+                *reserve_ammo = new_reserve_ammo;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Returns `true` if the weapon has an alternate attack that is off cooldown and not depleted
+    pub fn can_alt_fire(&self) -> bool {
+        if let Some(alt_fire) = &self.alt_fire {
+            let is_depleted = alt_fire
+                .uses
+                .map(|uses| self.alt_use_cnt >= uses)
+                .unwrap_or_default();
+
+            !is_depleted && self.alt_cooldown_timer >= alt_fire.cooldown
+        } else {
+            false
+        }
+    }
+
+    /// Returns the rotation of the weapon, in radians, for its current aim angle. This is applied
+    /// to the weapon sprite, as well as to the direction of its effects.
+    pub fn get_aim_rotation(&self, is_facing_left: bool) -> f32 {
+        let rad = deg_to_rad(self.aim_angle);
+
+        if is_facing_left {
+            rad
+        } else {
+            -rad
+        }
+    }
+
+    /// Returns the origin of the weapon's effects, when it is held by a player at `owner_position`
+    pub fn get_effect_origin(
+        &self,
+        owner_position: Vec2,
+        owner_inventory: &PlayerInventory,
+        is_facing_left: bool,
+        is_upside_down: bool,
+    ) -> Vec2 {
+        let origin =
+            owner_position + owner_inventory.get_weapon_mount(is_facing_left, is_upside_down);
+
+        let mut offset = self.mount_offset + self.effect_offset;
+        if is_facing_left {
+            offset.x = -offset.x;
+        }
+
+        // The weapon is tilted around the weapon mount, when it is aimed
+        if self.aim.is_some() {
+            offset = rotate_vector(offset, self.get_aim_rotation(is_facing_left));
+        }
+
+        origin + offset
+    }
+
+    /// Fills the magazine and restores the initial reserve
+    pub fn reset_ammo(&mut self) {
+        if let Some(magazine) = &self.magazine {
+            self.magazine_ammo = magazine.size;
+            self.reserve_ammo = magazine.reserve;
+        }
+
+        self.cancel_reload();
+    }
 }
 
-pub fn fire_weapon(world: &mut World, entity: Entity, owner: Entity) -> Result<()> {
+/// This decides which of a weapon's effects are instantiated when it is fired
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WeaponFireMode {
+    Primary,
+    /// Fire the weapon's `alt_fire` effects
+    Alt,
+}
+
+pub fn fire_weapon(
+    world: &mut World,
+    entity: Entity,
+    owner: Entity,
+    mode: WeaponFireMode,
+) -> Result<()> {
     let mut effects = Vec::new();
 
     let mut origin = Vec2::ZERO;
 
+    let mut is_fired = false;
+
     {
         let mut weapon = world.get_mut::<Weapon>(entity).unwrap();
 
-        if weapon.cooldown_timer >= weapon.cooldown {
+        let can_fire = match mode {
+            WeaponFireMode::Primary => {
+                weapon.cooldown_timer >= weapon.cooldown && weapon.has_ammo()
+            }
+            WeaponFireMode::Alt => weapon.can_alt_fire(),
+        };
+
+        if can_fire {
+            is_fired = true;
+
+            let (recoil, attack_duration, sound_effect) = match &weapon.alt_fire {
+                Some(alt_fire) if mode == WeaponFireMode::Alt => (
+                    alt_fire.recoil,
+                    alt_fire.attack_duration,
+                    weapon.alt_sound_effect,
+                ),
+                _ => (weapon.recoil, weapon.attack_duration, weapon.sound_effect),
+            };
+
             let mut player = world.get_mut::<Player>(owner).unwrap();
 
             {
                 let mut owner_body = world.get_mut::<PhysicsBody>(owner).unwrap();
 
                 if player.is_facing_left {
-                    owner_body.velocity.x = weapon.recoil;
+                    owner_body.velocity.x = recoil;
                 } else {
-                    owner_body.velocity.x = -weapon.recoil;
+                    owner_body.velocity.x = -recoil;
                 }
 
                 let owner_transform = world.get::<Transform>(owner).unwrap();
                 let owner_inventory = world.get::<PlayerInventory>(owner).unwrap();
 
-                origin = owner_transform.position
-                    + owner_inventory
-                        .get_weapon_mount(player.is_facing_left, player.is_upside_down);
-
-                let mut offset = weapon.mount_offset + weapon.effect_offset;
-                if player.is_facing_left {
-                    offset.x = -offset.x;
-                }
-
-                origin += offset;
+                origin = weapon.get_effect_origin(
+                    owner_transform.position,
+                    &owner_inventory,
+                    player.is_facing_left,
+                    player.is_upside_down,
+                );
             }
 
-            player.attack_timer = weapon.attack_duration;
-
-            weapon.use_cnt += 1;
+            player.attack_timer = attack_duration;
 
-            weapon.cooldown_timer = 0.0;
-
-            if let Some(sound) = weapon.sound_effect {
-                play_sound(
-                    sound,
-                    PlaySoundParams {
-                        looped: false,
-                        volume: SOUND_EFFECT_VOLUME,
-                    },
-                );
+            if let Some(sound) = sound_effect {
+                play_sound_effect_from(sound, 1.0);
             }
 
             let mut drawable = world.get_mut::<Drawable>(entity).unwrap();
@@ -447,12 +856,21 @@ pub fn fire_weapon(world: &mut World, entity: Entity, owner: Entity) -> Result<(
 
                 {
                     let sprite = sprite_set.map.get_mut(SPRITE_ANIMATED_SPRITE_ID).unwrap();
+
+                    let animation_id = if mode == WeaponFireMode::Alt
+                        && sprite.get_animation(ALT_ATTACK_ANIMATION_ID).is_some()
+                    {
+                        ALT_ATTACK_ANIMATION_ID
+                    } else {
+                        ATTACK_ANIMATION_ID
+                    };
+
                     let is_looping = sprite
-                        .get_animation(ATTACK_ANIMATION_ID)
+                        .get_animation(animation_id)
                         .map(|a| a.is_looping)
                         .unwrap_or_default();
 
-                    sprite.set_animation(ATTACK_ANIMATION_ID, !is_looping);
+                    sprite.set_animation(animation_id, !is_looping);
                     sprite.queue_action(QueuedAnimationAction::Play(IDLE_ANIMATION_ID.to_string()));
                 }
 
@@ -475,10 +893,61 @@ pub fn fire_weapon(world: &mut World, entity: Entity, owner: Entity) -> Result<(
                 }
             }
 
-            effects = weapon.effects.clone();
+            match mode {
+                WeaponFireMode::Primary => {
+                    weapon.use_cnt += 1;
+
+                    if weapon.magazine.is_some() {
+                        weapon.magazine_ammo -= 1;
+                    }
+
+                    weapon.cooldown_timer = 0.0;
+
+                    effects = weapon.effects.clone();
+
+                    if let Some(charge) = &weapon.charge {
+                        let level = weapon.get_charge_level().unwrap_or_default();
+
+                        let speed_factor = charge.get_speed_factor(level);
+                        let size_factor = charge.get_size_factor(level);
+
+                        let scaled = effects
+                            .iter()
+                            .map(|effect| effect.scaled(speed_factor, size_factor))
+                            .collect::<Vec<_>>();
+
+                        effects = Vec::new();
+                        for _ in 0..charge.get_count(level) {
+                            effects.extend(scaled.iter().cloned());
+                        }
+                    }
+
+                    weapon.is_charging = false;
+                    weapon.charge_timer = 0.0;
+                }
+                WeaponFireMode::Alt => {
+                    weapon.alt_use_cnt += 1;
+
+                    weapon.alt_cooldown_timer = 0.0;
+
+                    if let Some(alt_fire) = &weapon.alt_fire {
+                        effects = alt_fire.effects.clone();
+                    }
+                }
+            }
         }
     }
 
+    if is_fired {
+        publish_game_event(
+            world,
+            GameEvent::WeaponFired {
+                player: owner,
+                weapon: entity,
+            },
+        );
+    }
+
     for params in effects {
         spawn_active_effect(world, owner, entity, origin, params)?;
     }
@@ -551,6 +1020,25 @@ pub struct WeaponMetadata {
     /// specified.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub effect_sprite: Option<AnimatedSpriteMetadata>,
+    /// If this is specified, the weapon must be charged by holding the fire button, and it will be
+    /// fired when the button is released.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charge: Option<WeaponChargeMetadata>,
+    /// If this is specified, the weapon fires from a magazine, which must be reloaded from the
+    /// reserve ammo when it is empty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub magazine: Option<WeaponMagazineMetadata>,
+    /// If this is specified, the weapon has an alternate attack, that is used with the alt fire
+    /// button.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt_fire: Option<WeaponAltFireMetadata>,
+    /// If this is specified, the weapon can be aimed up and down
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aim: Option<WeaponAimMetadata>,
+    /// If this is specified, the trajectory of the weapon's triggered effects, like grenades and
+    /// mines, is previewed while the weapon is equipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trajectory_preview: Option<WeaponTrajectoryPreviewMetadata>,
 }
 
 impl Default for WeaponMetadata {
@@ -565,12 +1053,222 @@ impl Default for WeaponMetadata {
             attack_duration: 0.0,
             recoil: 0.0,
             effect_sprite: None,
+            charge: None,
+            magazine: None,
+            alt_fire: None,
+            aim: None,
+            trajectory_preview: None,
         }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+/// This holds the parameters of a charge weapon. The effects of the weapon are scaled by the
+/// charge level, which goes from `0.0`, at `min_time`, to `1.0`, at `max_time`. The factors are
+/// the values applied at full charge.
+/// If the weapon sprite has an animation with the id `"charge"`, it will be played while the
+/// weapon is charging.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeaponChargeMetadata {
+    /// The time the weapon must be charged before it will fire, when the button is released
+    #[serde(default)]
+    pub min_time: f32,
+    /// The time it takes to fully charge the weapon
+    pub max_time: f32,
+    /// The speed of projectiles and triggered effects is multiplied by this at full charge
+    #[serde(default = "default_charge_factor")]
+    pub speed_factor: f32,
+    /// The size of colliders, projectiles, beams and zones is multiplied by this at full charge
+    #[serde(default = "default_charge_factor")]
+    pub size_factor: f32,
+    /// The number of times the effects are instantiated at full charge. This is best combined
+    /// with a `spread` on projectiles.
+    #[serde(default = "default_charge_count")]
+    pub max_count: u32,
+    /// Particle effects that will be active while the weapon is charging
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub particles: Vec<ParticleEmitterMetadata>,
+}
+
+impl WeaponChargeMetadata {
+    /// Returns the charge level for the given charge time, or `None` if the weapon has not been
+    /// charged for long enough to fire
+    pub fn get_level(&self, time: f32) -> Option<f32> {
+        if time < self.min_time {
+            None
+        } else if self.max_time <= self.min_time {
+            Some(1.0)
+        } else {
+            Some(((time - self.min_time) / (self.max_time - self.min_time)).clamp(0.0, 1.0))
+        }
+    }
+
+    pub fn get_speed_factor(&self, level: f32) -> f32 {
+        1.0 + (self.speed_factor - 1.0) * level
+    }
+
+    pub fn get_size_factor(&self, level: f32) -> f32 {
+        1.0 + (self.size_factor - 1.0) * level
+    }
+
+    pub fn get_count(&self, level: f32) -> u32 {
+        let extra = self.max_count.saturating_sub(1) as f32 * level;
+        1 + extra.round() as u32
+    }
+}
+
+fn default_charge_factor() -> f32 {
+    1.0
+}
+
+fn default_charge_count() -> u32 {
+    1
+}
+
+/// This holds the parameters of a weapon magazine. The magazine is reloaded automatically when it
+/// is empty, or when the player presses the reload button.
+/// If the weapon sprite has an animation with the id `"reload"`, it will be played while the
+/// weapon is reloading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeaponMagazineMetadata {
+    /// The amount of ammo that the magazine holds
+    pub size: u32,
+    /// The time it takes to reload the magazine
+    #[serde(default)]
+    pub reload_time: f32,
+    /// The initial amount of reserve ammo, used to reload the magazine. If this is not specified,
+    /// the reserve will be unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reserve: Option<u32>,
+    /// The maximum amount of reserve ammo that can be held, when picking up ammo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_reserve: Option<u32>,
+    /// This can specify an id of a sound effect that is played when the weapon starts reloading
+    #[serde(
+        default,
+        rename = "sound_effect",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sound_effect_id: Option<String>,
+}
+
+/// This holds the parameters of a weapon's alternate attack, like a bayonet stab on a shotgun.
+/// The alternate attack has its own cooldown and uses, and it does not use ammo from the magazine.
+/// If the weapon sprite has an animation with the id `"alt_attack"`, it will be played in stead
+/// of the `"attack"` animation.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeaponAltFireMetadata {
+    /// The effects to instantiate when the alternate attack is used
+    #[serde(default)]
+    pub effects: Vec<ActiveEffectMetadata>,
+    /// This can specify an id of a sound effect that is played when the alternate attack is used
+    #[serde(
+        default,
+        rename = "sound_effect",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sound_effect_id: Option<String>,
+    /// This can specify a maximum amount of alternate attacks. If no value is specified, the
+    /// alternate attack can be used an unlimited amount of times.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uses: Option<u32>,
+    /// The minimum interval between alternate attacks
+    #[serde(default)]
+    pub cooldown: f32,
+    /// The amount of time the player will be locked in an attack state after the alternate attack
+    #[serde(default)]
+    pub attack_duration: f32,
+    /// The force applied to the `Player` velocity, in the opposite direction of the attack
+    #[serde(default)]
+    pub recoil: f32,
+}
+
+/// This holds the parameters of a weapon that can be aimed up and down. The weapon is tilted
+/// around the weapon mount, and the velocity of its projectiles, beams and triggered effects is
+/// rotated by the aim angle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeaponAimMetadata {
+    /// The maximum aim angle, in degrees, both upwards and downwards
+    #[serde(default = "default_max_aim_angle")]
+    pub max_angle: f32,
+    /// If this is specified, the aim angle will be snapped to multiples of this, in degrees
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<f32>,
+}
+
+impl WeaponAimMetadata {
+    /// Returns the aim angle, in degrees, for the given input angle
+    pub fn get_angle(&self, input: f32) -> f32 {
+        let max_angle = self.max_angle.abs();
+
+        let mut angle = input.clamp(-max_angle, max_angle);
+
+        if let Some(step) = self.step {
+            if step > 0.0 {
+                angle = (angle / step).round() * step;
+
+                // Snapping might take the angle past the maximum angle, if it is not a multiple
+                // of the step
+                while angle.abs() > max_angle {
+                    angle -= step * angle.signum();
+                }
+            }
+        }
+
+        angle
+    }
+}
+
+fn default_max_aim_angle() -> f32 {
+    90.0
+}
+
+/// This holds the parameters of the trajectory preview of a weapon that deploys triggered effects.
+/// The trajectory is simulated with the same physics as the effects themselves, including bounces,
+/// and drawn as a dotted line while the weapon is equipped, unless trajectory previews are
+/// disabled in the accessibility options.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeaponTrajectoryPreviewMetadata {
+    /// The maximum number of fixed updates to simulate
+    #[serde(default = "default_trajectory_preview_steps")]
+    pub steps: u32,
+    /// The number of fixed updates between each dot
+    #[serde(default = "default_trajectory_preview_dot_interval")]
+    pub dot_interval: u32,
+    /// The radius of each dot
+    #[serde(default = "default_trajectory_preview_dot_radius")]
+    pub dot_radius: f32,
+    #[serde(
+        default = "default_trajectory_preview_color",
+        with = "core::json::ColorDef"
+    )]
+    pub color: Color,
+}
+
+fn default_trajectory_preview_steps() -> u32 {
+    90
+}
+
+fn default_trajectory_preview_dot_interval() -> u32 {
+    3
+}
+
+fn default_trajectory_preview_dot_radius() -> f32 {
+    1.5
+}
+
+fn default_trajectory_preview_color() -> Color {
+    Color::new(1.0, 1.0, 1.0, 0.6)
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RespawnInfo {
+    #[serde(with = "core::json::vec2_def")]
     pub position: Vec2,
     pub respawn_delay: f32,
 }
@@ -590,6 +1288,8 @@ pub enum RespawningItemKind {
 }
 
 pub fn update_respawning_items(world: &mut World) {
+    let dt = get_delta_time(world);
+
     let mut to_spawn = Vec::new();
 
     for (respawning_item_entity, (respawning_item, transform, body, drawable)) in world
@@ -605,7 +1305,7 @@ pub fn update_respawning_items(world: &mut World) {
         let body: &mut PhysicsBody = body;
         let drawable: &mut Drawable = drawable;
 
-        respawning_item.timer.tick_frame_time();
+        respawning_item.timer.tick(dt);
 
         if respawning_item.timer.has_finished() {
             transform.position = respawning_item.info.position;
@@ -643,6 +1343,8 @@ pub fn update_respawning_items(world: &mut World) {
         match respawning_item.kind {
             RespawningItemKind::Weapon(mut weapon) => {
                 weapon.use_cnt = 0;
+                weapon.alt_use_cnt = 0;
+                weapon.reset_ammo();
                 world.insert_one(entity, weapon)
             }
             RespawningItemKind::Item(mut item) => {
@@ -32,7 +32,6 @@ use editor::{Editor, EditorCamera, EditorInputScheme};
 
 use map::{Map, MapLayerKind, MapObjectKind};
 
-use core::network::Api;
 use core::Result;
 
 pub use core::Config;
@@ -48,9 +47,8 @@ pub use player::PlayerEvent;
 
 pub use ecs::Owner;
 
-use crate::effects::passive::init_passive_effects;
+use crate::game::simulation::{run_simulation, SimulationParams};
 use crate::game::GameMode;
-use crate::particles::Particles;
 use crate::resources::load_resources;
 pub use effects::{
     ActiveEffectKind, ActiveEffectMetadata, PassiveEffectInstance, PassiveEffectMetadata,
@@ -109,8 +107,25 @@ async fn init_game() -> Result<bool> {
     use gui::MainMenuResult;
 
     match gui::show_main_menu().await {
-        MainMenuResult::LocalGame { map, players } => {
-            let game = Game::new(GameMode::Local, *map, &players)?;
+        MainMenuResult::LocalGame {
+            map,
+            map_id,
+            players,
+        } => {
+            use game::replay::{generate_seed, ReplayRecorder};
+
+            let seed = generate_seed();
+
+            let recorder = ReplayRecorder::new(seed, &map_id, &players);
+
+            let game =
+                Game::new(GameMode::Local, *map, &players, seed)?.with_replay_recorder(recorder);
+            scene::add_node(game);
+
+            start_music("fish_tide");
+        }
+        MainMenuResult::Replay { replay } => {
+            let game = Game::from_replay(*replay)?;
             scene::add_node(game);
 
             start_music("fish_tide");
@@ -162,7 +177,7 @@ async fn init_game() -> Result<bool> {
 
     let player_ids = vec!["1".to_string(), "2".to_string()];
 
-    Api::init::<ultimate::UltimateApiBackend>(&player_ids[0], true).await?;
+    let api = Api::init::<ultimate::UltimateApiBackend>(&player_ids[0], true).await?;
 
     let (map, mut characters) = {
         let resources = storage::get::<Resources>();
@@ -190,7 +205,9 @@ async fn init_game() -> Result<bool> {
         },
     ];
 
-    let game = Game::new(GameMode::NetworkHost, map, &players)?;
+    let seed = game::replay::generate_seed();
+
+    let game = Game::new(GameMode::NetworkHost, map, &players, seed)?.with_api(api);
     scene::add_node(game);
 
     start_music("fish_tide");
@@ -198,6 +215,23 @@ async fn init_game() -> Result<bool> {
     Ok(false)
 }
 
+/// Close the API connection owned by the current game, if any
+#[cfg(feature = "ultimate")]
+async fn close_game_api() -> Result<()> {
+    let api = scene::find_node_by_type::<Game>().and_then(|mut game| game.take_api());
+
+    if let Some(api) = api {
+        api.close().await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "ultimate"))]
+async fn close_game_api() -> Result<()> {
+    Ok(())
+}
+
 #[macroquad::main(window_conf)]
 async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     use events::iter_events;
@@ -209,18 +243,24 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
 
     load_resources(&assets_dir, &mods_dir).await?;
 
-    {
-        let gamepad_context = fishsticks::GamepadContext::init().unwrap();
-        storage::store(gamepad_context);
+    if let Some(params) = SimulationParams::from_args(env::args().skip(1))? {
+        let results = run_simulation(params)?;
+
+        println!("{}", results);
+
+        match results.save_to_stats_dir() {
+            Ok(path) => println!("Results saved to '{}'", path.display()),
+            Err(err) => println!("WARNING: Unable to save simulation results: {}", err),
+        }
+
+        return Ok(());
     }
 
     {
-        let particles = Particles::new();
-        storage::store(particles);
+        let gamepad_context = fishsticks::GamepadContext::init().unwrap();
+        storage::store(gamepad_context);
     }
 
-    init_passive_effects();
-
     'outer: loop {
         if init_game().await? {
             continue 'outer;
@@ -247,12 +287,14 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
             next_frame().await;
         }
 
+        close_game_api().await?;
+
         scene::clear();
 
         stop_music();
     }
 
-    Api::close().await?;
+    close_game_api().await?;
 
     Ok(())
 }
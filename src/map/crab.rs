@@ -2,14 +2,14 @@ use core::Transform;
 
 use fishsticks::error::Result;
 use hecs::{Entity, With, World};
-use macroquad::{
-    prelude::{collections::storage, Vec2},
-    rand,
-};
+use macroquad::prelude::{collections::storage, Vec2};
 
 use crate::{
-    player::Player, utils::timer::Timer, Animation, CollisionWorld, Drawable, PhysicsBody,
-    PhysicsBodyParams, Resources,
+    ecs::get_resource_mut,
+    game::{get_delta_time, rng::Rng},
+    player::Player,
+    utils::timer::Timer,
+    Animation, CollisionWorld, Drawable, PhysicsBody, PhysicsBodyParams, Resources,
 };
 
 pub const CRAB_TEXTURE_ID: &str = "crab";
@@ -63,7 +63,7 @@ pub fn spawn_crab(world: &mut World, spawn_position: Vec2) -> Result<Entity> {
     let resources = storage::get::<Resources>();
     let texture_res = resources.textures.get(CRAB_TEXTURE_ID).unwrap();
     let size = texture_res.meta.size;
-    let actor = storage::get_mut::<CollisionWorld>().add_actor(
+    let actor = get_resource_mut::<CollisionWorld>(world).add_actor(
         spawn_position,
         size.x as i32,
         size.y as i32,
@@ -105,6 +105,8 @@ pub fn spawn_crab(world: &mut World, spawn_position: Vec2) -> Result<Entity> {
 }
 
 pub fn update_crabs(world: &mut World) {
+    let dt = get_delta_time(world);
+
     for (_, (crab, drawable, transform, body)) in world
         .query::<(&mut Crab, &mut Drawable, &Transform, &mut PhysicsBody)>()
         .iter()
@@ -116,8 +118,10 @@ pub fn update_crabs(world: &mut World) {
 
         let pos = transform.position;
 
-        let rand_bool = |true_bias: u8| rand::gen_range(0u8, 2 + true_bias) > 0;
-        let rand_delay = |min, max| Timer::new(rand::gen_range(min, max));
+        let rand_bool =
+            |true_bias: u8| get_resource_mut::<Rng>(world).gen_range(0u8, 2 + true_bias) > 0;
+        let rand_delay =
+            |min: f32, max: f32| Timer::new(get_resource_mut::<Rng>(world).gen_range(min, max));
 
         let next_scary_thing = || {
             for (scary_entity, transform) in world.query::<With<Player, &Transform>>().iter() {
@@ -150,7 +154,7 @@ pub fn update_crabs(world: &mut World) {
             }
         };
 
-        crab.state_timer.tick_frame_time();
+        crab.state_timer.tick(dt);
 
         // Perform any state transitions
         if crab.state_timer.has_finished() {
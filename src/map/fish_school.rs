@@ -2,12 +2,11 @@ use core::Transform;
 
 use fishsticks::error::Result;
 use hecs::{Entity, World};
-use macroquad::{
-    prelude::{collections::storage, vec2, Color, Rect, Vec2},
-    rand,
-};
+use macroquad::prelude::{collections::storage, vec2, Color, Rect, Vec2};
 
 use crate::{
+    ecs::get_resource_mut,
+    game::{get_delta_time, rng::Rng},
     player::Player,
     utils::{ease::Ease, timer::Timer},
     AnimatedSpriteParams, Animation, Drawable, PhysicsBody, Resources, RigidBody,
@@ -78,7 +77,7 @@ pub fn spawn_fish_school(world: &mut World, spawn_position: Vec2) -> Result<Enti
     let fish_school_icon_sprite = resources.textures.get(FISH_SCHOOL_ICON_TEXTURE_ID).unwrap();
     let fish_school_icon_sprite_size = fish_school_icon_sprite.meta.frame_size.unwrap();
 
-    let rand_bool = || rand::gen_range(0u8, 2) == 0;
+    let rand_bool = || get_resource_mut::<Rng>(world).gen_range(0u8, 2) == 0;
 
     let mut fish_count = FISH_COUNT_BASE as i32;
     if rand_bool() {
@@ -100,25 +99,33 @@ pub fn spawn_fish_school(world: &mut World, spawn_position: Vec2) -> Result<Enti
     let fish_spawn_max = spawn_position + Vec2::splat(FISH_SPAWN_RANGE);
 
     for _ in 0..fish_count {
+        let mut rng = get_resource_mut::<Rng>(world);
+
         let spawn_point = vec2(
-            rand::gen_range(fish_spawn_min.x, fish_spawn_max.x),
-            rand::gen_range(fish_spawn_min.y, fish_spawn_max.y),
+            rng.gen_range(fish_spawn_min.x, fish_spawn_max.x),
+            rng.gen_range(fish_spawn_min.y, fish_spawn_max.y),
         );
 
-        let texture_index = rand::gen_range(0, FISH_TEXTURE_IDS.len());
+        let texture_index = rng.gen_range(0, FISH_TEXTURE_IDS.len());
         let texture_id = FISH_TEXTURE_IDS[texture_index];
 
+        let state_delay = rng.gen_range(0.2, 1.0);
+        let draw_order = rng.gen_range(DRAW_ORDER_MIN, DRAW_ORDER_MAX + 1);
+        let is_flipped_x = rng.gen_range(0u8, 2) == 0;
+
+        drop(rng);
+
         let fish_entity = world.spawn((
             Fish {
                 state: FishState::Moving {
                     from: spawn_point,
                     to: spawn_point,
                 },
-                state_timer: Timer::new(rand::gen_range(0.2, 1.0)),
+                state_timer: Timer::new(state_delay),
             },
             Transform::from(spawn_point),
             Drawable::new_animated_sprite(
-                rand::gen_range(DRAW_ORDER_MIN, DRAW_ORDER_MAX + 1),
+                draw_order,
                 texture_id,
                 &[Animation {
                     id: "default".to_string(),
@@ -129,7 +136,7 @@ pub fn spawn_fish_school(world: &mut World, spawn_position: Vec2) -> Result<Enti
                     is_looping: true,
                 }],
                 AnimatedSpriteParams {
-                    is_flipped_x: rand_bool(),
+                    is_flipped_x,
                     ..Default::default()
                 },
             ),
@@ -144,6 +151,8 @@ pub fn spawn_fish_school(world: &mut World, spawn_position: Vec2) -> Result<Enti
 }
 
 pub fn update_fish_schools(world: &mut World) {
+    let dt = get_delta_time(world);
+
     let mut schools = Vec::new();
 
     for (_, school) in world.query::<&FishSchool>().iter() {
@@ -176,9 +185,10 @@ pub fn update_fish_schools(world: &mut World) {
         );
 
         for fish_entity in &school.fish_entities {
-            let (mut fish, drawable, transform) = world
-                .query_one_mut::<(&mut Fish, &mut Drawable, &mut Transform)>(*fish_entity)
+            let mut query = world
+                .query_one::<(&mut Fish, &mut Drawable, &mut Transform)>(*fish_entity)
                 .unwrap();
+            let (fish, drawable, transform) = query.get().unwrap();
             let sprite = drawable.get_animated_sprite_mut().unwrap();
             let pos: &mut Vec2 = &mut transform.position;
             let padding = 20.0;
@@ -197,12 +207,13 @@ pub fn update_fish_schools(world: &mut World) {
                 }
             }
 
-            let rand_bool = || rand::gen_range(0u8, 2) > 0;
-            let rand_delay = |min, max| Timer::new(rand::gen_range(min, max));
+            let gen_range = |min: f32, max: f32| get_resource_mut::<Rng>(world).gen_range(min, max);
+            let rand_bool = || get_resource_mut::<Rng>(world).gen_range(0u8, 2) > 0;
+            let rand_delay = |min, max| Timer::new(gen_range(min, max));
 
             let pick_next_move = || {
                 if !school.is_grouped {
-                    let target_point = pos.lerp(school.center, rand::gen_range(0.1, 0.4));
+                    let target_point = pos.lerp(school.center, gen_range(0.1, 0.4));
 
                     (
                         FishState::Moving {
@@ -213,8 +224,8 @@ pub fn update_fish_schools(world: &mut World) {
                     )
                 } else if rand_bool() {
                     let target_point = vec2(
-                        pos.x + rand::gen_range(-20.0, 20.0),
-                        pos.y + rand::gen_range(-20.0, 20.0),
+                        pos.x + gen_range(-20.0, 20.0),
+                        pos.y + gen_range(-20.0, 20.0),
                     );
                     (
                         FishState::Moving {
@@ -224,7 +235,7 @@ pub fn update_fish_schools(world: &mut World) {
                         rand_delay(0.5, 1.5),
                     )
                 } else {
-                    let target_point = pos.lerp(school.spawn_pos, rand::gen_range(0.10, 0.25));
+                    let target_point = pos.lerp(school.spawn_pos, gen_range(0.10, 0.25));
                     (
                         FishState::Moving {
                             from: *pos,
@@ -235,14 +246,14 @@ pub fn update_fish_schools(world: &mut World) {
                 }
             };
 
-            fish.state_timer.tick_frame_time();
+            fish.state_timer.tick(dt);
 
             if let Some(collision_rect) = collides_with {
                 let collision_center = collision_rect.point() + collision_rect.size() / 2.0;
                 let diff = *pos - collision_center;
                 fish.state = FishState::Moving {
                     from: *pos,
-                    to: *pos + diff.normalize() * rand::gen_range(30.0, 60.0),
+                    to: *pos + diff.normalize() * gen_range(30.0, 60.0),
                 };
                 fish.state_timer = rand_delay(0.2, 0.6);
 
@@ -250,7 +261,7 @@ pub fn update_fish_schools(world: &mut World) {
                 // immediately without waiting for an extra frame, because if we keep colliding we
                 // may just keep re-setting the timer and the fish get's stuck until it stops
                 // colliding.
-                fish.state_timer.tick_frame_time();
+                fish.state_timer.tick(dt);
             } else if fish.state_timer.has_finished() {
                 let (state, timer) = pick_next_move();
                 fish.state = state;
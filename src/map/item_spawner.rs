@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use macroquad::experimental::collections::storage;
+use macroquad::prelude::*;
+
+use hecs::{Entity, World};
+
+use serde::{Deserialize, Serialize};
+
+use core::{Result, Transform};
+
+use crate::ecs::get_resource_mut;
+use crate::game::get_delta_time;
+use crate::game::rng::Rng;
+use crate::items::{spawn_item, MapItemMetadata};
+use crate::map::{get_number_property, MapProperty};
+use crate::{Owner, Resources};
+
+pub const ITEM_SPAWNER_ID: &str = "item_spawner";
+
+/// The map object property holding the loot table. This can either be a map of item ids to
+/// weights or, for maps made in Tiled, a string like `"musket:3, sniper_rifle:1"`. Entries with no
+/// weight will be given a weight of one.
+pub const LOOT_TABLE_PROPERTY: &str = "loot_table";
+/// The map object property holding the time between spawns, in seconds
+pub const SPAWN_INTERVAL_PROPERTY: &str = "spawn_interval";
+/// The map object property holding the maximum number of items that can be waiting at the spawner
+pub const MAX_COUNT_PROPERTY: &str = "max_count";
+
+const DEFAULT_SPAWN_INTERVAL: f32 = 10.0;
+const DEFAULT_MAX_COUNT: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LootTableEntry {
+    pub item_id: String,
+    pub weight: f32,
+}
+
+/// Item spawners spawn random items from their loot table, at an interval, as long as there are
+/// fewer than `max_count` of their items waiting to be picked up.
+pub struct ItemSpawner {
+    pub loot_table: Vec<LootTableEntry>,
+    pub spawn_interval: f32,
+    pub max_count: u32,
+    /// The time since there was room for another item, or since the last item was spawned
+    pub spawn_timer: f32,
+    /// The items spawned by this spawner that have not been picked up or destroyed yet
+    pub spawned: Vec<Entity>,
+}
+
+impl ItemSpawner {
+    pub fn new(loot_table: Vec<LootTableEntry>, spawn_interval: f32, max_count: u32) -> Self {
+        ItemSpawner {
+            loot_table,
+            spawn_interval,
+            max_count,
+            // The first item is spawned right away
+            spawn_timer: spawn_interval,
+            spawned: Vec::new(),
+        }
+    }
+
+    /// Create a spawner from the properties of a map object. Loot table entries with an invalid
+    /// item id, or a weight that is not positive, are skipped.
+    pub fn from_properties(properties: &HashMap<String, MapProperty>) -> Self {
+        let mut loot_table = properties
+            .get(LOOT_TABLE_PROPERTY)
+            .map(parse_loot_table)
+            .unwrap_or_default();
+
+        {
+            let resources = storage::get::<Resources>();
+
+            loot_table.retain(|entry| {
+                let is_valid = resources.items.contains_key(&entry.item_id);
+
+                #[cfg(debug_assertions)]
+                if !is_valid {
+                    println!(
+                        "WARNING: Invalid item id '{}' in item spawner loot table",
+                        &entry.item_id
+                    );
+                }
+
+                is_valid && entry.weight > 0.0
+            });
+        }
+
+        let spawn_interval = properties
+            .get(SPAWN_INTERVAL_PROPERTY)
+            .and_then(get_number_property)
+            .unwrap_or(DEFAULT_SPAWN_INTERVAL);
+
+        let max_count = properties
+            .get(MAX_COUNT_PROPERTY)
+            .and_then(get_number_property)
+            .map(|max_count| max_count as u32)
+            .unwrap_or(DEFAULT_MAX_COUNT);
+
+        ItemSpawner::new(loot_table, spawn_interval, max_count)
+    }
+
+    /// Pick a random item id from the loot table, based on the weights of the entries
+    pub fn pick_item_id(&self, rng: &mut Rng) -> Option<&str> {
+        let total_weight: f32 = self.loot_table.iter().map(|entry| entry.weight).sum();
+
+        if total_weight <= 0.0 {
+            return None;
+        }
+
+        let mut value = rng.gen_range(0.0, total_weight);
+
+        for entry in &self.loot_table {
+            if value < entry.weight {
+                return Some(&entry.item_id);
+            }
+
+            value -= entry.weight;
+        }
+
+        self.loot_table.last().map(|entry| entry.item_id.as_str())
+    }
+}
+
+/// The entries are sorted by item id, so that the picks are the same for the same seed, regardless
+/// of the order of the properties
+fn parse_loot_table(property: &MapProperty) -> Vec<LootTableEntry> {
+    let mut res: Vec<LootTableEntry> = match property {
+        MapProperty::HashMap(entries) => entries
+            .iter()
+            .filter_map(|(item_id, weight)| {
+                get_number_property(weight).map(|weight| LootTableEntry {
+                    item_id: item_id.clone(),
+                    weight,
+                })
+            })
+            .collect(),
+        MapProperty::Vec(entries) => entries
+            .iter()
+            .filter_map(|entry| match entry {
+                MapProperty::String(item_id) => Some(LootTableEntry {
+                    item_id: item_id.clone(),
+                    weight: 1.0,
+                }),
+                _ => None,
+            })
+            .collect(),
+        MapProperty::String(entries) => entries
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(':');
+
+                let item_id = parts.next()?.trim();
+                if item_id.is_empty() {
+                    return None;
+                }
+
+                let weight = match parts.next() {
+                    Some(weight) => weight.trim().parse().ok()?,
+                    None => 1.0,
+                };
+
+                Some(LootTableEntry {
+                    item_id: item_id.to_string(),
+                    weight,
+                })
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    res.sort_by(|a, b| a.item_id.cmp(&b.item_id));
+
+    res
+}
+
+pub fn spawn_item_spawner(
+    world: &mut World,
+    position: Vec2,
+    properties: &HashMap<String, MapProperty>,
+) -> Result<Entity> {
+    let spawner = ItemSpawner::from_properties(properties);
+
+    let entity = world.spawn((spawner, Transform::from(position)));
+
+    Ok(entity)
+}
+
+pub fn update_item_spawners(world: &mut World) {
+    let dt = get_delta_time(world);
+
+    let mut to_spawn = Vec::new();
+
+    for (entity, (spawner, transform)) in world.query::<(&mut ItemSpawner, &Transform)>().iter() {
+        spawner
+            .spawned
+            .retain(|&item| world.contains(item) && world.get::<Owner>(item).is_err());
+
+        if spawner.spawned.len() as u32 >= spawner.max_count {
+            spawner.spawn_timer = 0.0;
+            continue;
+        }
+
+        spawner.spawn_timer += dt;
+
+        if spawner.spawn_timer >= spawner.spawn_interval {
+            spawner.spawn_timer = 0.0;
+
+            let mut rng = get_resource_mut::<Rng>(world);
+
+            if let Some(item_id) = spawner.pick_item_id(&mut rng) {
+                to_spawn.push((entity, transform.position, item_id.to_string()));
+            }
+        }
+    }
+
+    for (spawner_entity, position, item_id) in to_spawn {
+        let meta = storage::get::<Resources>().items.get(&item_id).cloned();
+
+        if let Some(meta) = meta {
+            // Items are replaced by the spawner, so they should not respawn on their own
+            let meta = MapItemMetadata {
+                respawn_delay: None,
+                ..meta
+            };
+
+            match spawn_item(world, position, meta) {
+                Ok(item) => {
+                    let mut spawner = world.get_mut::<ItemSpawner>(spawner_entity).unwrap();
+                    spawner.spawned.push(item);
+                }
+                Err(err) => {
+                    #[cfg(debug_assertions)]
+                    println!("WARNING: Unable to spawn item '{}': {}", &item_id, err);
+                }
+            }
+        }
+    }
+}
@@ -7,12 +7,14 @@ use serde::{Deserialize, Serialize};
 mod crab;
 mod decoration;
 mod fish_school;
+mod item_spawner;
 mod player_interaction;
 mod sproinger;
 
 pub use crab::*;
 pub use decoration::*;
 pub use fish_school::*;
+pub use item_spawner::*;
 pub use player_interaction::*;
 pub use sproinger::*;
 
@@ -22,6 +24,7 @@ use core::Result;
 
 use crate::{
     editor::gui::combobox::ComboBoxValue,
+    game::rng::Rng,
     json::{self, TiledMap},
     Resources,
 };
@@ -32,6 +35,22 @@ const EXTRA_PLAYABLE_WIDTH: f32 = 400.0;
 
 pub type MapProperty = core::json::GenericParam;
 
+/// Returns the value of a numeric map property. Numbers in map JSON will be parsed as integers if
+/// they have no fractional part, so this will accept any numeric property, as well as strings that
+/// hold a number.
+pub fn get_number_property(property: &MapProperty) -> Option<f32> {
+    match property {
+        MapProperty::Int(value) => Some(*value as f32),
+        MapProperty::UInt(value) => Some(*value as f32),
+        MapProperty::Float(value) => Some(*value),
+        MapProperty::String(value) => value.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// The ids of all the objects that are valid for `MapObjectKind::Environment`
+pub const ENVIRONMENT_OBJECT_IDS: &[&str] = &["sproinger", "crab", "fish_school", ITEM_SPAWNER_ID];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct MapBackgroundLayer {
@@ -71,6 +90,13 @@ pub struct Map {
 impl Map {
     pub const PLATFORM_TILE_ATTRIBUTE: &'static str = "jumpthrough";
 
+    /// Tiles with this attribute are water, and will not collide with anything
+    pub const WATER_TILE_ATTRIBUTE: &'static str = "water";
+
+    /// If a layer has this property set to `true`, all its tiles are water, and they will not
+    /// collide with anything. The layer can be hidden, to mark an invisible water region.
+    pub const WATER_LAYER_PROPERTY: &'static str = "is_water";
+
     // Padding added to colliders for collision checks since the collision system stops movement
     // before collision is registered, if not.
     pub const COLLIDER_PADDING: f32 = 8.0;
@@ -218,10 +244,13 @@ impl Map {
         let platform_attr = Self::PLATFORM_TILE_ATTRIBUTE.to_string();
 
         for layer in self.layers.values() {
-            if layer.is_visible && layer.has_collision {
+            if layer.is_visible && layer.has_collision && !layer.is_water() {
                 for (x, y, tile) in self.get_tiles(&layer.id, Some(grid)) {
                     if let Some(tile) = tile {
-                        if !(should_ignore_platforms && tile.attributes.contains(&platform_attr)) {
+                        if !tile.is_water()
+                            && !(should_ignore_platforms
+                                && tile.attributes.contains(&platform_attr))
+                        {
                             let tile_position = self.to_position(uvec2(x, y));
 
                             let tile_rect = Rect::new(
@@ -250,8 +279,12 @@ impl Map {
         };
 
         for layer in self.layers.values() {
-            if layer.is_visible && layer.has_collision {
+            if layer.is_visible && layer.has_collision && !layer.is_water() {
                 if let Some(Some(tile)) = layer.tiles.get(index) {
+                    if tile.is_water() {
+                        continue;
+                    }
+
                     return !(should_ignore_platforms
                         && tile
                             .attributes
@@ -263,6 +296,26 @@ impl Map {
         false
     }
 
+    /// Returns `true` if `position` is inside a water volume
+    pub fn is_water_at(&self, position: Vec2) -> bool {
+        if !self.contains(position) {
+            return false;
+        }
+
+        let index = {
+            let coords = self.to_coords(position);
+            self.to_index(coords)
+        };
+
+        self.layers.values().any(|layer| {
+            if let Some(Some(tile)) = layer.tiles.get(index) {
+                layer.is_water() || tile.is_water()
+            } else {
+                false
+            }
+        })
+    }
+
     fn background_parallax(texture: Texture2D, depth: f32, camera_pos: Vec2) -> Rect {
         let w = texture.width();
         let h = texture.height();
@@ -410,8 +463,8 @@ impl Map {
         Ok(())
     }
 
-    pub fn get_random_spawn_point(&self) -> Vec2 {
-        let i = rand::gen_range(0, self.spawn_points.len()) as usize;
+    pub fn get_random_spawn_point(&self, rng: &mut Rng) -> Vec2 {
+        let i = rng.gen_range(0, self.spawn_points.len());
         self.spawn_points[i]
     }
 }
@@ -536,6 +589,15 @@ impl MapLayer {
             ..Default::default()
         }
     }
+
+    /// Returns `true` if all the tiles of the layer are water
+    pub fn is_water(&self) -> bool {
+        self.properties
+            .get(Map::WATER_LAYER_PROPERTY)
+            .and_then(|property| property.get_value::<bool>())
+            .copied()
+            .unwrap_or_default()
+    }
 }
 
 impl Default for MapLayer {
@@ -565,6 +627,13 @@ pub struct MapTile {
     pub attributes: Vec<String>,
 }
 
+impl MapTile {
+    pub fn is_water(&self) -> bool {
+        self.attributes
+            .contains(&Map::WATER_TILE_ATTRIBUTE.to_string())
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "snake_case")]
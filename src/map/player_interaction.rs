@@ -1,9 +1,10 @@
 use core::Transform;
 
 use hecs::{Entity, World};
-use macroquad::prelude::collections::storage;
 
 use crate::{
+    ecs::get_resource,
+    game::events::{publish_game_event, GameEvent},
     items::{RespawnInfo, RespawningItem, RespawningItemKind, Weapon},
     player::{Player, PlayerState},
     utils::timer::Timer,
@@ -13,10 +14,12 @@ use crate::{
 use super::Map;
 
 pub fn update_map_kill_zone(world: &mut World) {
-    let map = storage::get::<Map>();
+    let playable_area = get_resource::<Map>(world).get_playable_area();
+
+    let mut killed = Vec::new();
 
     // Kill players out of bounds
-    for (_, (player, transform, body)) in world
+    for (entity, (player, transform, body)) in world
         .query::<(&mut Player, &Transform, &PhysicsBody)>()
         .iter()
     {
@@ -26,11 +29,23 @@ pub fn update_map_kill_zone(world: &mut World) {
 
         let player_rect = body.as_rect(transform.position);
 
-        if !map.get_playable_area().overlaps(&player_rect) {
+        if !playable_area.overlaps(&player_rect) && player.state != PlayerState::Dead {
             player.state = PlayerState::Dead;
+            killed.push(entity);
         }
     }
 
+    for player in killed {
+        publish_game_event(
+            world,
+            GameEvent::PlayerKilled {
+                player,
+                killed_by: None,
+                source_id: None,
+            },
+        );
+    }
+
     struct ToDestroy {
         entity: Entity,
         respawn_info: Option<RespawnInfo>,
@@ -48,7 +63,7 @@ pub fn update_map_kill_zone(world: &mut World) {
 
         let item_rect = body.as_rect(transform.position);
 
-        if !map.get_playable_area().overlaps(&item_rect) {
+        if !playable_area.overlaps(&item_rect) {
             to_destroy.push(ToDestroy {
                 entity,
                 respawn_info: item.respawn_info,
@@ -67,7 +82,7 @@ pub fn update_map_kill_zone(world: &mut World) {
 
         let item_rect = body.as_rect(transform.position);
 
-        if !map.get_playable_area().overlaps(&item_rect) {
+        if !playable_area.overlaps(&item_rect) {
             to_destroy.push(ToDestroy {
                 entity,
                 respawn_info: weapon.respawn_info,
@@ -6,7 +6,7 @@ use hecs::{Entity, World};
 use core::Result;
 use core::Transform;
 
-use crate::game::play_sound_effect;
+use crate::game::{get_delta_time, play_sound_effect};
 use crate::{Animation, Drawable, PhysicsBody, QueuedAnimationAction};
 
 const SPROINGER_DRAW_ORDER: u32 = 2;
@@ -82,7 +82,7 @@ pub fn spawn_sproinger(world: &mut World, position: Vec2) -> Result<Entity> {
 }
 
 pub fn fixed_update_sproingers(world: &mut World) {
-    let dt = get_frame_time();
+    let dt = get_delta_time(world);
 
     let bodies = world
         .query::<(&Transform, &PhysicsBody)>()
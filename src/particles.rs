@@ -11,6 +11,7 @@ use serde::{Deserialize, Serialize};
 use core::math::IsZero;
 use core::Transform;
 
+use crate::ecs::get_resource_mut;
 use crate::{AnimatedSpriteMetadata, Resources};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -114,6 +115,7 @@ impl From<ParticleEmitterMetadata> for ParticleEmitter {
 }
 
 pub fn update_one_particle_emitter(
+    particles: &mut Particles,
     mut position: Vec2,
     rotation: f32,
     emitter: &mut ParticleEmitter,
@@ -147,7 +149,6 @@ pub fn update_one_particle_emitter(
                 );
             }
 
-            let mut particles = storage::get_mut::<Particles>();
             let cache = particles
                 .cache_map
                 .get_mut(&emitter.particle_effect_id)
@@ -167,19 +168,34 @@ pub fn update_one_particle_emitter(
 }
 
 pub fn update_particle_emitters(world: &mut World) {
-    for (_, (transform, emitter)) in world.query_mut::<(&Transform, &mut ParticleEmitter)>() {
-        update_one_particle_emitter(transform.position, transform.rotation, emitter);
+    let mut particles = get_resource_mut::<Particles>(world);
+
+    for (_, (transform, emitter)) in world.query::<(&Transform, &mut ParticleEmitter)>().iter() {
+        update_one_particle_emitter(
+            &mut particles,
+            transform.position,
+            transform.rotation,
+            emitter,
+        );
     }
 
-    for (_, (transform, emitters)) in world.query_mut::<(&Transform, &mut Vec<ParticleEmitter>)>() {
+    for (_, (transform, emitters)) in world
+        .query::<(&Transform, &mut Vec<ParticleEmitter>)>()
+        .iter()
+    {
         for emitter in emitters.iter_mut() {
-            update_one_particle_emitter(transform.position, transform.rotation, emitter);
+            update_one_particle_emitter(
+                &mut particles,
+                transform.position,
+                transform.rotation,
+                emitter,
+            );
         }
     }
 }
 
-pub fn draw_particles(_world: &mut World) {
-    let mut particles = storage::get_mut::<Particles>();
+pub fn draw_particles(world: &mut World) {
+    let mut particles = get_resource_mut::<Particles>(world);
 
     for cache in particles.cache_map.values_mut() {
         cache.draw();
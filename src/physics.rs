@@ -1,5 +1,4 @@
 use macroquad::color;
-use macroquad::experimental::collections::storage;
 use macroquad::prelude::*;
 
 use macroquad_platformer::{Actor, Tile};
@@ -8,6 +7,9 @@ use serde::{Deserialize, Serialize};
 
 use hecs::World;
 
+use crate::ecs::{get_resource, get_resource_mut};
+use crate::game::get_delta_time;
+use crate::particles::Particles;
 use crate::{CollisionWorld, Map};
 use core::{
     math::{deg_to_rad, IsZero},
@@ -17,6 +19,22 @@ use core::{
 pub const GRAVITY: f32 = 2.5;
 pub const TERMINAL_VELOCITY: f32 = 10.0;
 
+/// The density of water. Bodies that are denser than this will sink in water, while bodies that
+/// are less dense will float.
+pub const WATER_DENSITY: f32 = 1.0;
+/// The density of bodies that do not specify one
+pub const DEFAULT_DENSITY: f32 = 2.0;
+
+/// Gravity is multiplied by this, for bodies inside water
+const WATER_GRAVITY_FACTOR: f32 = 0.3;
+/// The velocity of bodies inside water is multiplied by this, on every fixed update
+const WATER_DRAG: f32 = 0.92;
+/// Densities are clamped to this, to avoid dividing by zero when calculating buoyancy
+const MIN_DENSITY: f32 = 0.01;
+
+/// The particle effect that is spawned when a body enters or leaves water
+const SPLASH_PARTICLE_EFFECT_ID: &str = "splash";
+
 pub fn create_collision_world(map: &Map) -> CollisionWorld {
     let tile_cnt = (map.grid_size.x * map.grid_size.y) as usize;
     let mut static_colliders = Vec::with_capacity(tile_cnt);
@@ -26,9 +44,13 @@ pub fn create_collision_world(map: &Map) -> CollisionWorld {
 
     for layer_id in &map.draw_order {
         let layer = map.layers.get(layer_id).unwrap();
-        if layer.has_collision {
+        if layer.has_collision && !layer.is_water() {
             for (i, (_, _, tile)) in map.get_tiles(layer_id, None).enumerate() {
                 if let Some(tile) = tile {
+                    if tile.is_water() {
+                        continue;
+                    }
+
                     if tile
                         .attributes
                         .contains(&Map::PLATFORM_TILE_ATTRIBUTE.to_string())
@@ -67,6 +89,7 @@ pub struct PhysicsBodyParams {
     pub angular_velocity: f32,
     pub bouncyness: f32,
     pub gravity: f32,
+    pub density: f32,
 }
 
 impl Default for PhysicsBodyParams {
@@ -80,6 +103,7 @@ impl Default for PhysicsBodyParams {
             angular_velocity: 0.0,
             bouncyness: 0.0,
             gravity: GRAVITY,
+            density: DEFAULT_DENSITY,
         }
     }
 }
@@ -105,6 +129,10 @@ pub struct PhysicsBody {
     pub bouncyness: f32,
     pub is_deactivated: bool,
     pub gravity: f32,
+    /// This decides whether the body floats or sinks in water, compared to `WATER_DENSITY`
+    pub density: f32,
+    /// Will be `true` if the center of the body is inside a water volume
+    pub is_in_water: bool,
 }
 
 impl PhysicsBody {
@@ -130,6 +158,8 @@ impl PhysicsBody {
             bouncyness: params.bouncyness,
             is_deactivated: false,
             gravity: params.gravity,
+            density: params.density,
+            is_in_water: false,
         }
     }
 
@@ -140,66 +170,120 @@ impl PhysicsBody {
 }
 
 pub fn fixed_update_physics_bodies(world: &mut World) {
-    let mut collision_world = storage::get_mut::<CollisionWorld>();
+    let mut collision_world = get_resource_mut::<CollisionWorld>(world);
+    let map = get_resource::<Map>(world);
 
-    for (_, (transform, body)) in world.query_mut::<(&mut Transform, &mut PhysicsBody)>() {
-        collision_world.set_actor_position(body.actor, transform.position + body.offset);
+    let dt = get_delta_time(world);
 
-        if !body.is_deactivated {
-            let position = collision_world.actor_pos(body.actor);
+    let mut splashes = Vec::new();
 
-            {
-                let position = position + vec2(0.0, 1.0);
+    for (_, (transform, body)) in world.query::<(&mut Transform, &mut PhysicsBody)>().iter() {
+        if body.is_deactivated {
+            collision_world.set_actor_position(body.actor, transform.position + body.offset);
+        } else {
+            let was_in_water = body.is_in_water;
 
-                body.was_on_ground = body.is_on_ground;
+            step_physics_body(&mut collision_world, &map, body, transform, dt);
 
-                body.is_on_ground = collision_world.collide_check(body.actor, position);
+            if body.is_in_water != was_in_water {
+                splashes.push(transform.position + body.offset + body.size / 2.0);
+            }
+        }
+    }
 
-                // FIXME: Using this to set `is_on_ground` caused weird glitching behavior when jumping up through platforms
-                let tile = collision_world.collide_solids(
-                    position,
-                    body.size.x as i32,
-                    body.size.y as i32,
-                );
+    if !splashes.is_empty() {
+        let mut particles = get_resource_mut::<Particles>(world);
 
-                body.is_on_platform = tile == Tile::JumpThrough;
+        if let Some(cache) = particles.cache_map.get_mut(SPLASH_PARTICLE_EFFECT_ID) {
+            for position in splashes {
+                cache.spawn(position);
             }
+        } else {
+            #[cfg(debug_assertions)]
+            println!(
+                "WARNING: Invalid particle effect id '{}'",
+                SPLASH_PARTICLE_EFFECT_ID
+            );
+        }
+    }
+}
 
-            if !body.is_on_ground && body.has_mass {
-                body.velocity.y += body.gravity;
+/// Moves a physics body, and rotates it if it can rotate, by one fixed update, updating its transform.
+/// This is also used to simulate the trajectories of bodies that have not been spawned yet, so
+/// that they match the movement of the actual bodies exactly.
+pub fn step_physics_body(
+    collision_world: &mut CollisionWorld,
+    map: &Map,
+    body: &mut PhysicsBody,
+    transform: &mut Transform,
+    dt: f32,
+) {
+    collision_world.set_actor_position(body.actor, transform.position + body.offset);
 
-                if body.velocity.y > TERMINAL_VELOCITY {
-                    body.velocity.y = TERMINAL_VELOCITY;
-                }
-            }
+    body.is_in_water = map.is_water_at(transform.position + body.offset + body.size / 2.0);
 
-            if !collision_world.move_h(body.actor, body.velocity.x) {
-                body.velocity.x *= -body.bouncyness;
-            }
+    let position = collision_world.actor_pos(body.actor);
 
-            if !collision_world.move_v(body.actor, body.velocity.y) {
-                body.velocity.y *= -body.bouncyness;
-            }
+    {
+        let position = position + vec2(0.0, 1.0);
 
-            if body.can_rotate {
-                apply_rotation(
-                    transform,
-                    &mut body.velocity,
-                    body.angular_velocity,
-                    body.is_on_ground,
-                );
-            }
+        body.was_on_ground = body.is_on_ground;
 
-            if body.is_on_ground && body.has_friction {
-                body.velocity.x *= FRICTION_LERP;
-                if body.velocity.x.abs() <= STOP_THRESHOLD {
-                    body.velocity.x = 0.0;
-                }
-            }
+        body.is_on_ground = collision_world.collide_check(body.actor, position);
+
+        // FIXME: Using this to set `is_on_ground` caused weird glitching behavior when jumping up through platforms
+        let tile = collision_world.collide_solids(position, body.size.x as i32, body.size.y as i32);
+
+        body.is_on_platform = tile == Tile::JumpThrough;
+    }
+
+    if body.has_mass {
+        if body.is_in_water {
+            // Buoyancy counteracts the reduced gravity, so bodies that are less dense than water
+            // will rise, even from the ground
+            let gravity = body.gravity * WATER_GRAVITY_FACTOR;
+            let buoyancy = gravity * WATER_DENSITY / body.density.max(MIN_DENSITY);
+
+            body.velocity.y += gravity - buoyancy;
+        } else if !body.is_on_ground {
+            body.velocity.y += body.gravity;
+        }
+
+        if body.velocity.y > TERMINAL_VELOCITY {
+            body.velocity.y = TERMINAL_VELOCITY;
+        }
+    }
 
-            transform.position = collision_world.actor_pos(body.actor) - body.offset;
+    if body.is_in_water {
+        body.velocity *= WATER_DRAG;
+    }
+
+    if !collision_world.move_h(body.actor, body.velocity.x) {
+        body.velocity.x *= -body.bouncyness;
+    }
+
+    if !collision_world.move_v(body.actor, body.velocity.y) {
+        body.velocity.y *= -body.bouncyness;
+    }
+
+    if body.can_rotate {
+        apply_rotation(
+            transform,
+            &mut body.velocity,
+            body.angular_velocity,
+            body.is_on_ground,
+            dt,
+        );
+    }
+
+    if body.is_on_ground && body.has_friction {
+        body.velocity.x *= FRICTION_LERP;
+        if body.velocity.x.abs() <= STOP_THRESHOLD {
+            body.velocity.x = 0.0;
         }
     }
+
+    transform.position = collision_world.actor_pos(body.actor) - body.offset;
 }
 
 pub fn debug_draw_physics_bodies(world: &mut World) {
@@ -269,12 +353,14 @@ impl RigidBody {
     }
 }
 
-pub fn fixed_update_rigid_bodies(world: &mut World) {
-    for (_, (transform, body)) in world.query_mut::<(&mut Transform, &mut RigidBody)>() {
+pub fn fixed_update_rigid_bodies(world: &World) {
+    let dt = get_delta_time(world);
+
+    for (_, (transform, body)) in world.query::<(&mut Transform, &mut RigidBody)>().iter() {
         transform.position += body.velocity;
 
         if body.can_rotate {
-            apply_rotation(transform, &mut body.velocity, 0.0, false);
+            apply_rotation(transform, &mut body.velocity, 0.0, false, dt);
         }
     }
 }
@@ -292,9 +378,10 @@ fn apply_rotation(
     velocity: &mut Vec2,
     angular_velocity: f32,
     is_on_ground: bool,
+    dt: f32,
 ) {
     if !angular_velocity.is_zero() {
-        transform.rotation += deg_to_rad(angular_velocity * get_frame_time());
+        transform.rotation += deg_to_rad(angular_velocity * dt);
     } else if !is_on_ground {
         transform.rotation += velocity.x.abs() * 0.00045 + velocity.y.abs() * 0.00015;
     } else {
@@ -351,9 +351,10 @@ impl PlayerAnimations {
     }
 }
 
-pub fn update_player_animations(world: &mut World) {
-    for (_, (player, inventory, body, drawable)) in
-        world.query_mut::<(&Player, &mut PlayerInventory, &PhysicsBody, &mut Drawable)>()
+pub fn update_player_animations(world: &World) {
+    for (_, (player, inventory, body, drawable)) in world
+        .query::<(&Player, &mut PlayerInventory, &PhysicsBody, &mut Drawable)>()
+        .iter()
     {
         let sprite_set = drawable.get_animated_sprite_set_mut().unwrap();
 
@@ -368,12 +369,24 @@ pub fn update_player_animations(world: &mut World) {
                     DEATH_FORWARD_ANIMATION_ID
                 }
             }
+            // Characters have no dedicated animation for being stunned or frozen, so they are
+            // shown idle, or falling, if they are incapacitated in the air
             PlayerState::Incapacitated => {
-                // TODO: implement incapacitated
-                unimplemented!();
+                if body.is_on_ground {
+                    IDLE_ANIMATION_ID
+                } else {
+                    FALL_ANIMATION_ID
+                }
             }
             PlayerState::Sliding => SLIDE_ANIMATION_ID,
             PlayerState::Crouching => CROUCH_ANIMATION_ID,
+            PlayerState::Swimming => {
+                if body.velocity == Vec2::ZERO {
+                    IDLE_ANIMATION_ID
+                } else {
+                    MOVE_ANIMATION_ID
+                }
+            }
             _ => {
                 if body.is_on_ground {
                     if !player.is_attacking && body.velocity.x != 0.0 {
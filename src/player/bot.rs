@@ -0,0 +1,152 @@
+//! Bots are players that are controlled by the game. Their input is decided by `update_bots`, for
+//! every update, and then applied like the input of any other player, so they are bound by the
+//! same rules as human players.
+
+use macroquad::prelude::*;
+
+use hecs::{Entity, With, Without, World};
+
+use core::input::PlayerInput;
+use core::Transform;
+
+use crate::ecs::get_resource_mut;
+use crate::game::rng::Rng;
+use crate::items::Weapon;
+use crate::player::{Player, PlayerController, PlayerControllerKind, PlayerInventory, PlayerState};
+use crate::{Owner, PhysicsBody};
+
+/// The maximum vertical distance to a target, for a bot to fire at it
+const FIRE_DISTANCE_Y: f32 = 32.0;
+/// The maximum horizontal distance to a target, for a bot to fire at it
+const FIRE_DISTANCE_X: f32 = 600.0;
+/// Bots that are armed will stop approaching their target at this horizontal distance
+const PREFERRED_DISTANCE_X: f32 = 160.0;
+/// The distance to a weapon at which a bot will try to pick it up
+const PICKUP_DISTANCE: f32 = 32.0;
+/// Bots will jump, or drop through platforms, when their target is further away than this,
+/// vertically
+const CLIMB_DISTANCE_Y: f32 = 48.0;
+/// The chance, in percent, that a bot will jump on any given update, so that they do not get
+/// stuck on walls
+const RANDOM_JUMP_CHANCE: i32 = 2;
+
+fn get_nearest(position: Vec2, candidates: &[(Entity, Vec2)], exclude: Entity) -> Option<Vec2> {
+    candidates
+        .iter()
+        .filter(|(entity, _)| *entity != exclude)
+        .map(|(_, candidate)| *candidate)
+        .min_by(|a, b| {
+            a.distance_squared(position)
+                .partial_cmp(&b.distance_squared(position))
+                .unwrap()
+        })
+}
+
+fn get_bot_input(
+    player: &Player,
+    weapon: Option<&Weapon>,
+    position: Vec2,
+    body: &PhysicsBody,
+    target: Vec2,
+    rng: &mut Rng,
+) -> PlayerInput {
+    let mut input = PlayerInput::default();
+
+    let diff = target - position;
+    let is_target_left = diff.x < 0.0;
+
+    let mut should_approach = true;
+
+    if let Some(weapon) = weapon {
+        let is_in_line = diff.y.abs() <= FIRE_DISTANCE_Y && diff.x.abs() <= FIRE_DISTANCE_X;
+
+        // Bots that are facing away from their target will keep moving towards it, to turn
+        if is_in_line && player.is_facing_left == is_target_left {
+            // Charge weapons are fired when the button is released, so it is released once the
+            // weapon is fully charged
+            input.fire = weapon.get_charge_level() != Some(1.0);
+            should_approach = diff.x.abs() > PREFERRED_DISTANCE_X;
+        }
+    } else if diff.length() <= PICKUP_DISTANCE {
+        input.pickup = true;
+        should_approach = false;
+    }
+
+    if should_approach {
+        input.left = is_target_left;
+        input.right = !is_target_left;
+    }
+
+    if body.is_on_ground {
+        if diff.y < -CLIMB_DISTANCE_Y || rng.gen_range(0, 100) < RANDOM_JUMP_CHANCE {
+            input.jump = true;
+        } else if body.is_on_platform && diff.y > CLIMB_DISTANCE_Y {
+            // Jumping while crouching on a platform will drop the player through it
+            input.crouch = true;
+            input.jump = true;
+        }
+    } else if diff.y < -CLIMB_DISTANCE_Y {
+        input.float = true;
+    } else if diff.y > CLIMB_DISTANCE_Y {
+        input.crouch = true;
+    }
+
+    input
+}
+
+/// Decide the input of all players that are controlled by bots. Bots that are unarmed will go for
+/// the nearest weapon that is not owned by another player, while armed bots will go for the
+/// nearest player and fire at them, when they are in line.
+pub fn update_bots(world: &mut World) {
+    let players = world
+        .query::<(&Player, &Transform)>()
+        .iter()
+        .filter(|(_, (player, _))| player.state != PlayerState::Dead)
+        .map(|(entity, (_, transform))| (entity, transform.position))
+        .collect::<Vec<_>>();
+
+    let weapons = world
+        .query::<With<Weapon, Without<Owner, &Transform>>>()
+        .iter()
+        .map(|(entity, transform)| (entity, transform.position))
+        .collect::<Vec<_>>();
+
+    let mut rng = get_resource_mut::<Rng>(world);
+
+    for (entity, (controller, player, inventory, transform, body)) in world
+        .query::<(
+            &mut PlayerController,
+            &Player,
+            &PlayerInventory,
+            &Transform,
+            &PhysicsBody,
+        )>()
+        .iter()
+    {
+        if !matches!(controller.kind, PlayerControllerKind::Bot(..)) {
+            continue;
+        }
+
+        let position = transform.position;
+
+        let weapon = inventory
+            .weapon
+            .and_then(|weapon| world.get::<Weapon>(weapon).ok());
+
+        let target = if weapon.is_some() {
+            get_nearest(position, &players, entity)
+        } else {
+            get_nearest(position, &weapons, entity)
+                .or_else(|| get_nearest(position, &players, entity))
+        };
+
+        let input = match target {
+            Some(target) if player.state != PlayerState::Dead => {
+                get_bot_input(player, weapon.as_deref(), position, body, target, &mut rng)
+            }
+            _ => PlayerInput::default(),
+        };
+
+        controller.kind = PlayerControllerKind::Bot(input);
+    }
+}
@@ -10,6 +10,10 @@ use core::input::{collect_local_input, GameInputScheme, PlayerInput};
 pub enum PlayerControllerKind {
     LocalInput(GameInputScheme),
     Network(PlayerId),
+    /// Input is read from a replay and set, for every update, by the replay playback
+    Replay(PlayerInput),
+    /// Input is decided and set, for every update, by `update_bots`
+    Bot(PlayerInput),
 }
 
 impl PlayerControllerKind {
@@ -22,46 +26,66 @@ impl PlayerControllerKind {
 pub struct PlayerController {
     pub kind: PlayerControllerKind,
 
-    /// No vertical movement is possible now but you never know what the future holds :)
+    /// The input that was last applied. This is kept so that it can be recorded to replays.
+    pub input: PlayerInput,
+
+    /// Vertical movement is only possible while swimming, where it is controlled by holding the
+    /// jump and crouch buttons. Vertical aim is held separately, in `aim`.
     pub move_direction: Vec2,
 
+    /// The vertical aim angle, in degrees, relative to the direction the player is facing.
+    /// This is only used by weapons that can be aimed.
+    pub aim: f32,
+
     pub should_crouch: bool,
     pub should_jump: bool,
     pub should_float: bool,
     pub should_pickup: bool,
     pub should_attack: bool,
     pub should_slide: bool,
+    pub should_reload: bool,
+    pub should_alt_attack: bool,
 }
 
 impl From<PlayerControllerKind> for PlayerController {
     fn from(kind: PlayerControllerKind) -> Self {
         PlayerController {
             kind,
+            input: PlayerInput::default(),
             move_direction: Vec2::ZERO,
+            aim: 0.0,
             should_crouch: false,
             should_jump: false,
             should_float: false,
             should_pickup: false,
             should_attack: false,
             should_slide: false,
+            should_reload: false,
+            should_alt_attack: false,
         }
     }
 }
 
 impl PlayerController {
     pub fn clear(&mut self) {
+        self.input = PlayerInput::default();
         self.move_direction = Vec2::ZERO;
+        self.aim = 0.0;
         self.should_crouch = false;
         self.should_jump = false;
         self.should_float = false;
         self.should_pickup = false;
         self.should_attack = false;
         self.should_slide = false;
+        self.should_reload = false;
+        self.should_alt_attack = false;
     }
 
     pub fn apply_input(&mut self, input: PlayerInput) {
         self.clear();
 
+        self.input = input;
+
         if input.left {
             self.move_direction.x -= 1.0;
         }
@@ -70,12 +94,24 @@ impl PlayerController {
             self.move_direction.x += 1.0;
         }
 
+        if input.float {
+            self.move_direction.y -= 1.0;
+        }
+
+        if input.crouch {
+            self.move_direction.y += 1.0;
+        }
+
+        self.aim = input.aim as f32;
+
         self.should_crouch = input.crouch;
         self.should_jump = input.jump;
         self.should_float = input.float;
         self.should_pickup = input.pickup;
         self.should_attack = input.fire;
         self.should_slide = input.slide;
+        self.should_reload = input.reload;
+        self.should_alt_attack = input.alt_fire;
     }
 }
 
@@ -84,6 +120,7 @@ pub fn update_player_controllers(world: &mut World) {
         let input = match &controller.kind {
             PlayerControllerKind::LocalInput(input_scheme) => collect_local_input(*input_scheme),
             PlayerControllerKind::Network(_player_id) => PlayerInput::default(),
+            PlayerControllerKind::Replay(input) | PlayerControllerKind::Bot(input) => *input,
         };
 
         controller.apply_input(input);
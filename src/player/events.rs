@@ -1,6 +1,7 @@
 use hecs::{Entity, World};
-use macroquad::time::get_frame_time;
 
+use crate::game::events::{publish_game_event, GameEvent};
+use crate::game::get_delta_time;
 use crate::player::{Player, PlayerState};
 use serde::{Deserialize, Serialize};
 
@@ -23,6 +24,8 @@ pub enum PlayerEvent {
     ReceiveDamage {
         is_from_left: bool,
         damage_from: Option<Entity>,
+        /// The id of the weapon or item that caused the damage, if any
+        source_id: Option<String>,
     },
     GiveDamage {
         damage_to: Option<Entity>,
@@ -68,9 +71,11 @@ impl From<&PlayerEvent> for PlayerEventKind {
 }
 
 pub fn update_player_events(world: &mut World) {
-    for (_, (player, events)) in world.query_mut::<(&mut Player, &mut PlayerEventQueue)>() {
-        let dt = get_frame_time();
+    let dt = get_delta_time(world);
 
+    let mut killed = Vec::new();
+
+    for (entity, (player, events)) in world.query_mut::<(&mut Player, &mut PlayerEventQueue)>() {
         events.queue.push(PlayerEvent::Update { dt });
 
         let mut damage_blocked_left = false;
@@ -84,14 +89,34 @@ pub fn update_player_events(world: &mut World) {
         }
 
         while let Some(event) = events.queue.pop() {
-            if let PlayerEvent::ReceiveDamage { is_from_left, .. } = event {
+            if let PlayerEvent::ReceiveDamage {
+                is_from_left,
+                damage_from,
+                source_id,
+            } = event
+            {
                 if (is_from_left && !damage_blocked_left)
                     || (!is_from_left && !damage_blocked_right)
                 {
+                    if player.state != PlayerState::Dead {
+                        killed.push((entity, damage_from, source_id));
+                    }
+
                     player.state = PlayerState::Dead;
                     player.damage_from_left = is_from_left;
                 }
             }
         }
     }
+
+    for (player, killed_by, source_id) in killed {
+        publish_game_event(
+            world,
+            GameEvent::PlayerKilled {
+                player,
+                killed_by,
+                source_id,
+            },
+        );
+    }
 }
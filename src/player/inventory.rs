@@ -1,18 +1,30 @@
+use macroquad::experimental::collections::storage;
 use macroquad::prelude::*;
 
 use hecs::{Entity, With, Without, World};
 
-use core::Transform;
+use core::math::rotate_vector;
+use core::{Config, Transform};
 
+use crate::ecs::{get_resource, get_resource_mut};
+use crate::effects::active::triggered::{
+    simulate_triggered_effect_trajectory, TriggeredEffectMetadata,
+};
+use crate::game::events::{publish_game_event, GameEvent};
+use crate::game::get_delta_time;
+use crate::game::sound::play_sound_effect_from;
 use crate::items::{
     fire_weapon, ItemDepleteBehavior, ItemDropBehavior, RespawnInfo, RespawningItem,
-    RespawningItemKind, Weapon, EFFECT_ANIMATED_SPRITE_ID, GROUND_ANIMATION_ID, ITEMS_DRAW_ORDER,
-    SPRITE_ANIMATED_SPRITE_ID,
+    RespawningItemKind, Weapon, WeaponFireMode, CHARGE_ANIMATION_ID, EFFECT_ANIMATED_SPRITE_ID,
+    GROUND_ANIMATION_ID, ITEMS_DRAW_ORDER, RELOAD_ANIMATION_ID, SPRITE_ANIMATED_SPRITE_ID,
 };
 use crate::particles::ParticleEmitter;
 use crate::player::{Player, PlayerController, PlayerState, IDLE_ANIMATION_ID, PICKUP_GRACE_TIME};
 use crate::utils::timer::Timer;
-use crate::{Drawable, Item, Owner, PassiveEffectInstance, PhysicsBody};
+use crate::{
+    ActiveEffectKind, ActiveEffectMetadata, Drawable, Item, Owner, PassiveEffectInstance,
+    PhysicsBody,
+};
 
 const THROW_FORCE: f32 = 5.0;
 
@@ -59,6 +71,8 @@ impl PlayerInventory {
 }
 
 pub fn update_player_inventory(world: &mut World) {
+    let dt = get_delta_time(world);
+
     let mut item_colliders = world
         .query::<With<Item, Without<Owner, (&Transform, &PhysicsBody)>>>()
         .iter()
@@ -75,6 +89,10 @@ pub fn update_player_inventory(world: &mut World) {
 
     let mut to_drop = Vec::new();
     let mut to_fire = Vec::new();
+    let mut to_start_charge = Vec::new();
+    let mut to_end_charge = Vec::new();
+    let mut to_start_reload = Vec::new();
+    let mut to_end_reload = Vec::new();
     struct ToDestroy {
         entity: Entity,
         respawn_info: Option<RespawnInfo>,
@@ -109,6 +127,32 @@ pub fn update_player_inventory(world: &mut World) {
                 if player_rect.overlaps(&rect) {
                     let item = world.get::<Item>(item_entity).unwrap();
 
+                    if let Some(ammo) = item.ammo {
+                        // Ammo pickups are only picked up if the player's weapon can hold more
+                        // reserve ammo
+                        let is_picked_up = inventory
+                            .weapon
+                            .and_then(|weapon_entity| world.get_mut::<Weapon>(weapon_entity).ok())
+                            .map(|mut weapon| weapon.add_reserve_ammo(ammo))
+                            .unwrap_or_default();
+
+                        if is_picked_up {
+                            to_destroy.push(ToDestroy {
+                                entity: item_entity,
+                                respawn_info: item.respawn_info,
+                            });
+
+                            item_colliders.remove(i);
+
+                            let mut body = world.get_mut::<PhysicsBody>(item_entity).unwrap();
+                            body.is_deactivated = true;
+                        } else {
+                            i += 1;
+                        }
+
+                        continue;
+                    }
+
                     if item.is_hat {
                         if player.pickup_grace_timer < PICKUP_GRACE_TIME {
                             i += 1;
@@ -195,7 +239,33 @@ pub fn update_player_inventory(world: &mut World) {
                     .get_mut::<Weapon>(entity)
                     .map(|weapon| (entity, weapon))
             }) {
-                weapon.cooldown_timer += get_frame_time();
+                weapon.cooldown_timer += dt;
+                weapon.alt_cooldown_timer += dt;
+
+                if weapon.is_reloading {
+                    weapon.reload_timer += dt;
+
+                    let reload_time = weapon
+                        .magazine
+                        .as_ref()
+                        .map(|magazine| magazine.reload_time)
+                        .unwrap_or_default();
+
+                    if weapon.reload_timer >= reload_time {
+                        weapon.finish_reload();
+                        to_end_reload.push(weapon_entity);
+                    }
+                } else if weapon.magazine.is_some()
+                    && !weapon.is_charging
+                    && (weapon.magazine_ammo == 0 || controller.should_reload)
+                    && weapon.start_reload()
+                {
+                    to_start_reload.push(weapon_entity);
+                }
+
+                if let Some(aim) = &weapon.aim {
+                    weapon.aim_angle = aim.get_angle(controller.aim);
+                }
 
                 let mut weapon_transform = world.get_mut::<Transform>(weapon_entity).unwrap();
 
@@ -211,6 +281,13 @@ pub fn update_player_inventory(world: &mut World) {
                     sprite_set.flip_all_x(player.is_facing_left);
                     sprite_set.flip_all_y(player.is_upside_down);
 
+                    // Weapons that can be aimed are tilted around the weapon mount
+                    if weapon.aim.is_some() {
+                        for sprite in sprite_set.map.values_mut() {
+                            sprite.pivot = Some(weapon_mount);
+                        }
+                    }
+
                     sprite_set
                         .map
                         .get(SPRITE_ANIMATED_SPRITE_ID)
@@ -227,28 +304,47 @@ pub fn update_player_inventory(world: &mut World) {
 
                 weapon_transform.position += mount_offset;
 
+                let mut effect_offset = weapon.effect_offset;
+
+                if player.is_facing_left {
+                    effect_offset.x = frame_size.x - effect_offset.x;
+                }
+
+                if player.is_upside_down {
+                    effect_offset.y = frame_size.y - effect_offset.y;
+                }
+
+                if weapon.aim.is_some() {
+                    let rotation = weapon.get_aim_rotation(player.is_facing_left);
+
+                    weapon_transform.rotation = rotation;
+
+                    let effect_position = weapon_transform.position + effect_offset;
+                    effect_offset = weapon_mount
+                        + rotate_vector(effect_position - weapon_mount, rotation)
+                        - weapon_transform.position;
+                }
+
                 if let Ok(mut particle_emitters) =
                     world.get_mut::<Vec<ParticleEmitter>>(weapon_entity)
                 {
-                    let mut offset = weapon.effect_offset;
-
-                    if player.is_facing_left {
-                        offset.x = frame_size.x - offset.x;
-                    }
-
-                    if player.is_upside_down {
-                        offset.y = frame_size.y - offset.y;
+                    for emitter in particle_emitters.iter_mut() {
+                        emitter.offset = effect_offset;
                     }
+                }
 
-                    for emitter in particle_emitters.iter_mut() {
-                        emitter.offset = offset;
+                if let Some(particle_entity) = weapon.charge_particle_entity {
+                    if let Ok(mut particle_transform) = world.get_mut::<Transform>(particle_entity)
+                    {
+                        particle_transform.position = weapon_transform.position + effect_offset;
                     }
                 }
 
-                let is_depleted = weapon
-                    .uses
-                    .map(|uses| weapon.use_cnt >= uses)
-                    .unwrap_or_default();
+                let is_depleted = weapon.is_out_of_ammo()
+                    || weapon
+                        .uses
+                        .map(|uses| weapon.use_cnt >= uses)
+                        .unwrap_or_default();
 
                 if is_depleted {
                     match weapon.deplete_behavior {
@@ -265,8 +361,35 @@ pub fn update_player_inventory(world: &mut World) {
                         }
                         _ => {}
                     }
+                } else if controller.should_alt_attack
+                    && !weapon.is_charging
+                    && weapon.can_alt_fire()
+                {
+                    to_fire.push((weapon_entity, entity, WeaponFireMode::Alt));
+                } else if weapon.charge.is_some() {
+                    if controller.should_attack
+                        && weapon.cooldown_timer >= weapon.cooldown
+                        && weapon.has_ammo()
+                    {
+                        if !weapon.is_charging {
+                            weapon.is_charging = true;
+                            weapon.charge_timer = 0.0;
+
+                            to_start_charge.push(weapon_entity);
+                        }
+
+                        weapon.charge_timer += dt;
+                    } else if weapon.is_charging {
+                        // The weapon is fired when the button is released, if it has been charged
+                        // for long enough
+                        if weapon.get_charge_level().is_some() {
+                            to_fire.push((weapon_entity, entity, WeaponFireMode::Primary));
+                        }
+
+                        to_end_charge.push(weapon_entity);
+                    }
                 } else if controller.should_attack {
-                    to_fire.push((weapon_entity, entity));
+                    to_fire.push((weapon_entity, entity, WeaponFireMode::Primary));
                 }
             }
 
@@ -275,7 +398,7 @@ pub fn update_player_inventory(world: &mut World) {
 
                 let mut item = world.get_mut::<Item>(item_entity).unwrap();
 
-                item.duration_timer += get_frame_time();
+                item.duration_timer += dt;
 
                 let mut is_depleted = false;
 
@@ -363,6 +486,14 @@ pub fn update_player_inventory(world: &mut World) {
     for (player_entity, item_entity) in picked_up {
         world.insert_one(item_entity, Owner(player_entity)).unwrap();
 
+        publish_game_event(
+            world,
+            GameEvent::ItemPickedUp {
+                player: player_entity,
+                item: item_entity,
+            },
+        );
+
         let player_draw_order = world
             .get::<Drawable>(player_entity)
             .map(|drawable| drawable.draw_order)
@@ -381,16 +512,37 @@ pub fn update_player_inventory(world: &mut World) {
         }
     }
 
+    let mut charge_particles_to_despawn = Vec::new();
+    let mut to_reset_aim = Vec::new();
+
     for entity in to_drop {
         world.remove_one::<Owner>(entity).unwrap();
 
         let mut should_destroy = None;
 
         if let Ok(mut weapon) = world.get_mut::<Weapon>(entity) {
+            if let Some(particle_entity) = weapon.cancel_charge() {
+                charge_particles_to_despawn.push(particle_entity);
+            }
+
+            weapon.cancel_reload();
+
+            if weapon.aim.is_some() {
+                weapon.aim_angle = 0.0;
+                to_reset_aim.push(entity);
+            }
+
             match weapon.drop_behavior {
                 ItemDropBehavior::ClearState => {
                     weapon.use_cnt = 0;
                     weapon.cooldown_timer = weapon.cooldown;
+                    weapon.alt_use_cnt = 0;
+                    weapon.alt_cooldown_timer = weapon
+                        .alt_fire
+                        .as_ref()
+                        .map(|alt_fire| alt_fire.cooldown)
+                        .unwrap_or_default();
+                    weapon.reset_ammo();
                 }
                 ItemDropBehavior::Destroy => {
                     should_destroy = Some(ToDestroy {
@@ -486,8 +638,105 @@ pub fn update_player_inventory(world: &mut World) {
         }
     }
 
-    for (entity, owner) in to_fire.drain(0..) {
-        if let Err(err) = fire_weapon(world, entity, owner) {
+    // Weapons that are destroyed on drop will already have been despawned
+    for entity in to_reset_aim {
+        if let Ok(mut transform) = world.get_mut::<Transform>(entity) {
+            transform.rotation = 0.0;
+        }
+
+        if let Ok(mut drawable) = world.get_mut::<Drawable>(entity) {
+            if let Some(sprite_set) = drawable.get_animated_sprite_set_mut() {
+                for sprite in sprite_set.map.values_mut() {
+                    sprite.pivot = None;
+                }
+            }
+        }
+    }
+
+    for entity in to_start_charge {
+        let particles = {
+            let weapon = world.get::<Weapon>(entity).unwrap();
+            weapon
+                .charge
+                .as_ref()
+                .map(|charge| charge.particles.clone())
+                .unwrap_or_default()
+        };
+
+        if !particles.is_empty() {
+            let position = world.get::<Transform>(entity).unwrap().position;
+
+            let particle_emitters = particles
+                .into_iter()
+                .map(|meta| {
+                    let mut emitter = ParticleEmitter::from(meta);
+                    emitter.is_active = true;
+                    emitter
+                })
+                .collect::<Vec<_>>();
+
+            let particle_entity = world.spawn((Transform::from(position), particle_emitters));
+
+            let mut weapon = world.get_mut::<Weapon>(entity).unwrap();
+            weapon.charge_particle_entity = Some(particle_entity);
+        }
+
+        let mut drawable = world.get_mut::<Drawable>(entity).unwrap();
+        let sprite_set = drawable.get_animated_sprite_set_mut().unwrap();
+        let sprite = sprite_set.map.get_mut(SPRITE_ANIMATED_SPRITE_ID).unwrap();
+
+        if sprite.get_animation(CHARGE_ANIMATION_ID).is_some() {
+            sprite.set_animation(CHARGE_ANIMATION_ID, true);
+        }
+    }
+
+    for entity in to_end_charge {
+        let particle_entity = {
+            let mut weapon = world.get_mut::<Weapon>(entity).unwrap();
+            weapon.is_charging = false;
+            weapon.charge_particle_entity.take()
+        };
+
+        if let Some(particle_entity) = particle_entity {
+            charge_particles_to_despawn.push(particle_entity);
+        }
+
+        let mut drawable = world.get_mut::<Drawable>(entity).unwrap();
+        let sprite_set = drawable.get_animated_sprite_set_mut().unwrap();
+        let sprite = sprite_set.map.get_mut(SPRITE_ANIMATED_SPRITE_ID).unwrap();
+
+        // If the weapon is fired, the attack animation will be set by `fire_weapon`
+        if sprite.current_animation().id == CHARGE_ANIMATION_ID {
+            sprite.set_animation(IDLE_ANIMATION_ID, true);
+        }
+    }
+
+    for entity in to_start_reload {
+        if let Some(sound) = world.get::<Weapon>(entity).unwrap().reload_sound_effect {
+            play_sound_effect_from(sound, 1.0);
+        }
+
+        let mut drawable = world.get_mut::<Drawable>(entity).unwrap();
+        let sprite_set = drawable.get_animated_sprite_set_mut().unwrap();
+        let sprite = sprite_set.map.get_mut(SPRITE_ANIMATED_SPRITE_ID).unwrap();
+
+        if sprite.get_animation(RELOAD_ANIMATION_ID).is_some() {
+            sprite.set_animation(RELOAD_ANIMATION_ID, true);
+        }
+    }
+
+    for entity in to_end_reload {
+        let mut drawable = world.get_mut::<Drawable>(entity).unwrap();
+        let sprite_set = drawable.get_animated_sprite_set_mut().unwrap();
+        let sprite = sprite_set.map.get_mut(SPRITE_ANIMATED_SPRITE_ID).unwrap();
+
+        if sprite.current_animation().id == RELOAD_ANIMATION_ID {
+            sprite.set_animation(IDLE_ANIMATION_ID, true);
+        }
+    }
+
+    for (entity, owner, mode) in to_fire.drain(0..) {
+        if let Err(err) = fire_weapon(world, entity, owner, mode) {
             #[cfg(debug_assertions)]
             println!("WARNING: {}", err);
         }
@@ -525,6 +774,10 @@ pub fn update_player_inventory(world: &mut World) {
             println!("WARNING: {}", err);
         }
     }
+
+    for entity in charge_particles_to_despawn {
+        let _ = world.despawn(entity);
+    }
 }
 
 const HUD_OFFSET_Y: f32 = 16.0;
@@ -545,58 +798,206 @@ const HUD_USE_COUNT_COLOR_EMPTY: Color = Color {
     a: 0.8,
 };
 
+const HUD_RELOAD_BAR_WIDTH: f32 = 32.0;
+
+const HUD_RELOAD_BAR_HEIGHT: f32 = 4.0;
+
 pub fn draw_weapons_hud(world: &mut World) {
     for (_, (transform, inventory)) in world.query::<(&Transform, &PlayerInventory)>().iter() {
         if let Some(Ok(weapon)) = inventory.weapon.map(|entity| world.get::<Weapon>(entity)) {
-            if let Some(uses) = weapon.uses {
+            let mut position = transform.position;
+            position.y -= HUD_OFFSET_Y;
+
+            if let Some(magazine) = &weapon.magazine {
+                if weapon.is_reloading {
+                    let progress = if magazine.reload_time > 0.0 {
+                        (weapon.reload_timer / magazine.reload_time).clamp(0.0, 1.0)
+                    } else {
+                        1.0
+                    };
+
+                    let x = position.x - HUD_RELOAD_BAR_WIDTH / 2.0;
+                    let y = position.y - 4.0 - HUD_RELOAD_BAR_HEIGHT / 2.0;
+
+                    draw_rectangle_lines(
+                        x,
+                        y,
+                        HUD_RELOAD_BAR_WIDTH,
+                        HUD_RELOAD_BAR_HEIGHT,
+                        1.0,
+                        HUD_USE_COUNT_COLOR_EMPTY,
+                    );
+
+                    draw_rectangle(
+                        x,
+                        y,
+                        HUD_RELOAD_BAR_WIDTH * progress,
+                        HUD_RELOAD_BAR_HEIGHT,
+                        HUD_USE_COUNT_COLOR_FULL,
+                    );
+                } else {
+                    draw_use_count(position, magazine.size, weapon.magazine_ammo);
+                }
+            } else if let Some(uses) = weapon.uses {
                 let is_destroyed_on_depletion =
                     weapon.deplete_behavior == ItemDepleteBehavior::Destroy;
 
                 if !is_destroyed_on_depletion || uses > 1 {
-                    let remaining = uses - weapon.use_cnt;
-
-                    let mut position = transform.position;
-                    position.y -= HUD_OFFSET_Y;
-
-                    if uses >= HUD_CONDENSED_USE_COUNT_THRESHOLD {
-                        let x = position.x - ((4.0 * uses as f32) / 2.0);
-
-                        for i in 0..uses {
-                            draw_rectangle(
-                                x + 4.0 * i as f32,
-                                position.y - 12.0,
-                                2.0,
-                                12.0,
-                                if i >= remaining {
-                                    HUD_USE_COUNT_COLOR_EMPTY
-                                } else {
-                                    HUD_USE_COUNT_COLOR_FULL
-                                },
-                            )
-                        }
-                    } else {
-                        let x = position.x - (uses as f32 * 14.0) / 2.0;
-
-                        for i in 0..uses {
-                            let x = x + 14.0 * i as f32;
-
-                            if i >= remaining {
-                                draw_circle_lines(
-                                    x,
-                                    position.y - 4.0,
-                                    4.0,
-                                    2.0,
-                                    HUD_USE_COUNT_COLOR_EMPTY,
-                                );
-                            } else {
-                                draw_circle(x, position.y - 4.0, 4.0, HUD_USE_COUNT_COLOR_FULL);
-                            };
+                    draw_use_count(position, uses, uses - weapon.use_cnt);
+                }
+            }
+        }
+    }
+}
+
+fn draw_use_count(position: Vec2, uses: u32, remaining: u32) {
+    if uses >= HUD_CONDENSED_USE_COUNT_THRESHOLD {
+        let x = position.x - ((4.0 * uses as f32) / 2.0);
+
+        for i in 0..uses {
+            draw_rectangle(
+                x + 4.0 * i as f32,
+                position.y - 12.0,
+                2.0,
+                12.0,
+                if i >= remaining {
+                    HUD_USE_COUNT_COLOR_EMPTY
+                } else {
+                    HUD_USE_COUNT_COLOR_FULL
+                },
+            )
+        }
+    } else {
+        let x = position.x - (uses as f32 * 14.0) / 2.0;
+
+        for i in 0..uses {
+            let x = x + 14.0 * i as f32;
+
+            if i >= remaining {
+                draw_circle_lines(x, position.y - 4.0, 4.0, 2.0, HUD_USE_COUNT_COLOR_EMPTY);
+            } else {
+                draw_circle(x, position.y - 4.0, 4.0, HUD_USE_COUNT_COLOR_FULL);
+            };
+        }
+    }
+}
+
+/// A trajectory preview that has been simulated, ready to be drawn
+pub struct TrajectoryPreview {
+    pub dots: Vec<Vec2>,
+    pub dot_radius: f32,
+    pub color: Color,
+}
+
+/// This holds the trajectory previews of the current update and is added to the world as a
+/// resource. The trajectories are simulated by `update_trajectory_previews`, as this moves actors
+/// in the collision world, and they are only drawn by `draw_trajectory_previews`.
+#[derive(Default)]
+pub struct TrajectoryPreviews(pub Vec<TrajectoryPreview>);
+
+/// Simulates the trajectory of the triggered effects of equipped weapons that have a trajectory
+/// preview
+pub fn update_trajectory_previews(world: &mut World) {
+    let mut previews = Vec::new();
+
+    let is_enabled = storage::get::<Config>()
+        .accessibility
+        .show_trajectory_previews;
+
+    if is_enabled {
+        for (_, (player, transform, inventory)) in world
+            .query::<(&Player, &Transform, &PlayerInventory)>()
+            .iter()
+        {
+            if player.state == PlayerState::Dead {
+                continue;
+            }
+
+            if let Some(Ok(weapon)) = inventory.weapon.map(|entity| world.get::<Weapon>(entity)) {
+                if let Some(preview) = &weapon.trajectory_preview {
+                    if !weapon.has_ammo() {
+                        continue;
+                    }
+
+                    let origin = weapon.get_effect_origin(
+                        transform.position,
+                        inventory,
+                        player.is_facing_left,
+                        player.is_upside_down,
+                    );
+
+                    let speed_factor = weapon
+                        .charge
+                        .as_ref()
+                        .map(|charge| {
+                            charge.get_speed_factor(weapon.get_charge_level().unwrap_or_default())
+                        })
+                        .unwrap_or(1.0);
+
+                    let mut effects = Vec::new();
+                    collect_triggered_effects(&weapon.effects, &mut effects);
+
+                    for meta in effects {
+                        // This matches how the velocity is rotated and flipped when the effect is
+                        // instantiated
+                        let mut velocity = rotate_vector(
+                            meta.velocity * speed_factor,
+                            weapon.get_aim_rotation(false),
+                        );
+                        if player.is_facing_left {
+                            velocity.x = -velocity.x;
                         }
+
+                        let points = simulate_triggered_effect_trajectory(
+                            world,
+                            origin,
+                            velocity,
+                            meta,
+                            preview.steps,
+                        );
+
+                        let dot_interval = preview.dot_interval.max(1) as usize;
+
+                        previews.push(TrajectoryPreview {
+                            dots: points
+                                .into_iter()
+                                .skip(dot_interval - 1)
+                                .step_by(dot_interval)
+                                .collect(),
+                            dot_radius: preview.dot_radius,
+                            color: preview.color,
+                        });
                     }
                 }
             }
         }
     }
+
+    get_resource_mut::<TrajectoryPreviews>(world).0 = previews;
+}
+
+pub fn draw_trajectory_previews(world: &mut World) {
+    let previews = get_resource::<TrajectoryPreviews>(world);
+
+    for preview in &previews.0 {
+        for dot in &preview.dots {
+            draw_circle(dot.x, dot.y, preview.dot_radius, preview.color);
+        }
+    }
+}
+
+/// Collects the triggered effects in `effects`, including the ones in sequences
+fn collect_triggered_effects<'a>(
+    effects: &'a [ActiveEffectMetadata],
+    res: &mut Vec<&'a TriggeredEffectMetadata>,
+) {
+    for effect in effects {
+        match effect.kind.as_ref() {
+            ActiveEffectKind::TriggeredEffect { meta } => res.push(meta),
+            ActiveEffectKind::Sequence { effects } => collect_triggered_effects(effects, res),
+            _ => {}
+        }
+    }
 }
 
 pub fn flip_offset<S: Into<Option<Vec2>>>(
@@ -3,28 +3,37 @@ use macroquad::prelude::*;
 
 use hecs::{Entity, World};
 
+use serde::{Deserialize, Serialize};
+
 use core::Transform;
 
+use crate::ecs::get_resource_mut;
 use crate::{
     AnimatedSprite, AnimatedSpriteMetadata, AnimatedSpriteParams, CollisionWorld, Drawable,
     GameCamera, PassiveEffectInstance, PhysicsBody, Resources,
 };
 
 mod animation;
+mod bot;
 mod character;
 mod controller;
 mod events;
 mod inventory;
+mod modifiers;
 mod state;
+mod status;
 
 pub use animation::*;
+pub use bot::*;
 pub use character::*;
 pub use controller::*;
 pub use events::*;
 pub use inventory::*;
+pub use modifiers::*;
 pub use state::*;
+pub use status::*;
 
-use crate::physics::PhysicsBodyParams;
+use crate::physics::{PhysicsBodyParams, WATER_DENSITY};
 
 pub const BODY_ANIMATED_SPRITE_ID: &str = "body";
 pub const LEFT_FIN_ANIMATED_SPRITE_ID: &str = "left_fin";
@@ -45,6 +54,8 @@ pub const HAT_MOUNT_TWEEN_ID: &str = "hat_mount";
 
 pub const JUMP_SOUND_ID: &str = "jump";
 pub const LAND_SOUND_ID: &str = "land";
+pub const DEATH_SOUND_ID: &str = "death";
+pub const PICKUP_SOUND_ID: &str = "pickup";
 
 pub const RESPAWN_DELAY: f32 = 2.5;
 pub const PICKUP_GRACE_TIME: f32 = 0.25;
@@ -56,6 +67,9 @@ pub struct PlayerParams {
     pub character: PlayerCharacterMetadata,
 }
 
+/// Passive effects are skipped when serializing, as they are saved separately by `game::save`
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Player {
     pub index: u8,
     pub state: PlayerState,
@@ -64,12 +78,22 @@ pub struct Player {
     pub is_upside_down: bool,
     pub is_attacking: bool,
     pub jump_frame_counter: u16,
+    /// The number of jumps made since the player left the ground, used by the `double_jump`
+    /// passive effect
+    #[serde(default)]
+    pub air_jump_cnt: u32,
     pub pickup_grace_timer: f32,
     pub incapacitation_timer: f32,
     pub attack_timer: f32,
     pub respawn_timer: f32,
+    #[serde(with = "core::json::RectDef")]
     pub camera_box: Rect,
+    #[serde(skip)]
     pub passive_effects: Vec<PassiveEffectInstance>,
+    /// This is set while the player has an invisibility passive effect, so that their opacity can
+    /// be restored once it is removed
+    #[serde(skip)]
+    pub is_invisible: bool,
     pub was_on_ground: bool,
 }
 
@@ -85,7 +109,9 @@ impl Player {
             is_upside_down: false,
             is_attacking: false,
             was_on_ground: false,
+            is_invisible: false,
             jump_frame_counter: 0,
+            air_jump_cnt: 0,
             pickup_grace_timer: 0.0,
             attack_timer: 0.0,
             incapacitation_timer: 0.0,
@@ -96,8 +122,10 @@ impl Player {
     }
 }
 
-pub fn update_player_camera_box(world: &mut World) {
-    for (_, (transform, player)) in world.query_mut::<(&Transform, &mut Player)>() {
+pub fn update_player_camera_box(world: &World) {
+    let mut camera = get_resource_mut::<GameCamera>(world);
+
+    for (_, (transform, player)) in world.query::<(&Transform, &mut Player)>().iter() {
         let rect = Rect::new(transform.position.x, transform.position.y, 32.0, 60.0);
 
         if rect.x < player.camera_box.x {
@@ -116,34 +144,52 @@ pub fn update_player_camera_box(world: &mut World) {
             player.camera_box.y = rect.y + rect.h - player.camera_box.h;
         }
 
-        let mut camera = storage::get_mut::<GameCamera>();
         camera.add_player_rect(player.camera_box);
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct PlayerAttributes {
+    /// The id of the character that the attributes were taken from
+    pub character_id: String,
     pub head_threshold: f32,
     pub legs_threshold: f32,
     pub weapon_mount: Vec2,
-    pub jump_force: f32,
-    pub move_speed: f32,
-    pub slide_speed_factor: f32,
-    pub incapacitation_duration: f32,
-    pub float_gravity_factor: f32,
+    /// The stats of the player, with all stat modifiers applied. These are the values that
+    /// should be used by systems and they are updated by `update_player_stats`.
+    pub stats: PlayerAttributeValues,
+    /// The stats of the player character, before any stat modifiers are applied
+    pub base_stats: PlayerAttributeValues,
+    /// Stat modifiers that are not tied to a passive effect. Modifiers that belong to passive
+    /// effects are applied while the effect is active, without being added here.
+    pub stat_modifiers: Vec<StatModifier>,
+}
+
+impl PlayerAttributes {
+    pub fn add_stat_modifier<M: Into<StatModifier>>(&mut self, modifier: M) {
+        self.stat_modifiers.push(modifier.into());
+    }
 }
 
 impl From<&PlayerCharacterMetadata> for PlayerAttributes {
     fn from(params: &PlayerCharacterMetadata) -> Self {
+        let stats = PlayerAttributeValues {
+            move_speed: params.move_speed,
+            jump_force: params.jump_force,
+            gravity: params.gravity,
+            slide_speed_factor: params.slide_speed_factor,
+            float_gravity_factor: params.float_gravity_factor,
+            incapacitation_duration: params.incapacitation_duration,
+        };
+
         PlayerAttributes {
+            character_id: params.id.clone(),
             head_threshold: params.head_threshold,
             legs_threshold: params.legs_threshold,
             weapon_mount: params.weapon_mount,
-            jump_force: params.jump_force,
-            move_speed: params.move_speed,
-            slide_speed_factor: params.slide_speed_factor,
-            incapacitation_duration: params.incapacitation_duration,
-            float_gravity_factor: params.float_gravity_factor,
+            stats,
+            base_stats: stats,
+            stat_modifiers: Vec::new(),
         }
     }
 }
@@ -202,7 +248,7 @@ pub fn spawn_player(
     let draw_order = (index as u32 + 1) * 10;
 
     let size = character.collider_size.as_i32();
-    let actor = storage::get_mut::<CollisionWorld>().add_actor(position, size.x, size.y);
+    let actor = get_resource_mut::<CollisionWorld>(world).add_actor(position, size.x, size.y);
 
     let body_params = PhysicsBodyParams {
         offset: vec2(-character.collider_size.x / 2.0, 0.0),
@@ -210,6 +256,8 @@ pub fn spawn_player(
         has_friction: false,
         can_rotate: false,
         gravity: character.gravity,
+        // Players are neutrally buoyant, so that they can swim freely
+        density: WATER_DENSITY,
         ..Default::default()
     };
 
@@ -220,6 +268,7 @@ pub fn spawn_player(
         PlayerAttributes::from(&character),
         PlayerInventory::new(weapon_mount, item_mount, hat_mount),
         PlayerEventQueue::new(),
+        PlayerStatusEffects::new(),
         Drawable::new_animated_sprite_set(draw_order, &sprites),
         PhysicsBody::new(actor, None, body_params),
     ))
@@ -0,0 +1,183 @@
+use hecs::World;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::get_delta_time;
+use crate::player::{Player, PlayerAttributes, PlayerState, PlayerStatusEffects};
+use crate::PhysicsBody;
+
+/// The player attributes that can be changed by stat modifiers
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayerStat {
+    MoveSpeed,
+    JumpForce,
+    Gravity,
+    SlideSpeedFactor,
+    FloatGravityFactor,
+    IncapacitationDuration,
+}
+
+/// Modifiers with the same priority are applied in the order that the operations are declared in
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatModifierOperation {
+    Override,
+    Add,
+    Multiply,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatModifierMetadata {
+    pub stat: PlayerStat,
+    pub operation: StatModifierOperation,
+    pub value: f32,
+    /// Modifiers are applied in order of priority, lowest first, so a modifier with a higher
+    /// priority will be applied on top of the ones with lower priorities.
+    #[serde(default)]
+    pub priority: i32,
+    /// The duration of the modifier. If this is not set, the modifier will last until it is
+    /// removed or, if it belongs to a passive effect, until the effect is removed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f32>,
+}
+
+impl StatModifierMetadata {
+    pub fn is_expired(&self, timer: f32) -> bool {
+        if let Some(duration) = self.duration {
+            timer >= duration
+        } else {
+            false
+        }
+    }
+}
+
+/// A stat modifier that is not tied to a passive effect, like a debuff from an attack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatModifier {
+    pub meta: StatModifierMetadata,
+    pub timer: f32,
+}
+
+impl StatModifier {
+    pub fn new(meta: StatModifierMetadata) -> Self {
+        StatModifier { meta, timer: 0.0 }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.meta.is_expired(self.timer)
+    }
+}
+
+impl From<StatModifierMetadata> for StatModifier {
+    fn from(meta: StatModifierMetadata) -> Self {
+        StatModifier::new(meta)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PlayerAttributeValues {
+    pub move_speed: f32,
+    pub jump_force: f32,
+    pub gravity: f32,
+    pub slide_speed_factor: f32,
+    pub float_gravity_factor: f32,
+    pub incapacitation_duration: f32,
+}
+
+impl PlayerAttributeValues {
+    pub fn get(&self, stat: PlayerStat) -> f32 {
+        match stat {
+            PlayerStat::MoveSpeed => self.move_speed,
+            PlayerStat::JumpForce => self.jump_force,
+            PlayerStat::Gravity => self.gravity,
+            PlayerStat::SlideSpeedFactor => self.slide_speed_factor,
+            PlayerStat::FloatGravityFactor => self.float_gravity_factor,
+            PlayerStat::IncapacitationDuration => self.incapacitation_duration,
+        }
+    }
+
+    pub fn get_mut(&mut self, stat: PlayerStat) -> &mut f32 {
+        match stat {
+            PlayerStat::MoveSpeed => &mut self.move_speed,
+            PlayerStat::JumpForce => &mut self.jump_force,
+            PlayerStat::Gravity => &mut self.gravity,
+            PlayerStat::SlideSpeedFactor => &mut self.slide_speed_factor,
+            PlayerStat::FloatGravityFactor => &mut self.float_gravity_factor,
+            PlayerStat::IncapacitationDuration => &mut self.incapacitation_duration,
+        }
+    }
+
+    pub fn apply_modifier(&mut self, modifier: &StatModifierMetadata) {
+        let value = self.get_mut(modifier.stat);
+
+        match modifier.operation {
+            StatModifierOperation::Override => *value = modifier.value,
+            StatModifierOperation::Add => *value += modifier.value,
+            StatModifierOperation::Multiply => *value *= modifier.value,
+        }
+    }
+}
+
+/// This recalculates the stats of all players from their base stats, their stat modifiers and
+/// the stat modifiers of their passive effects and status effects.
+pub fn update_player_stats(world: &mut World) {
+    let dt = get_delta_time(world);
+
+    for (_, (player, attributes, status_effects, body)) in world.query_mut::<(
+        &Player,
+        &mut PlayerAttributes,
+        &PlayerStatusEffects,
+        &mut PhysicsBody,
+    )>() {
+        if player.state == PlayerState::Dead {
+            attributes.stat_modifiers.clear();
+        }
+
+        for modifier in &mut attributes.stat_modifiers {
+            modifier.timer += dt;
+        }
+
+        attributes
+            .stat_modifiers
+            .retain(|modifier| !modifier.is_expired());
+
+        let status_effect_modifiers = status_effects
+            .active
+            .iter()
+            .filter_map(|effect| effect.meta.get_stat_modifier())
+            .collect::<Vec<_>>();
+
+        let mut modifiers = attributes
+            .stat_modifiers
+            .iter()
+            .map(|modifier| &modifier.meta)
+            .collect::<Vec<_>>();
+
+        for effect in &player.passive_effects {
+            for modifier in &effect.stat_modifiers {
+                if !modifier.is_expired(effect.duration_timer) {
+                    modifiers.push(modifier);
+                }
+            }
+        }
+
+        modifiers.extend(status_effect_modifiers.iter());
+
+        // This is a stable sort, so modifiers with the same priority and operation are applied in
+        // the order they were added
+        modifiers.sort_by_key(|modifier| (modifier.priority, modifier.operation));
+
+        let mut stats = attributes.base_stats;
+
+        for modifier in modifiers {
+            stats.apply_modifier(modifier);
+        }
+
+        attributes.stats = stats;
+
+        body.gravity = stats.gravity;
+    }
+}
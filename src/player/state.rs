@@ -1,28 +1,38 @@
-use macroquad::experimental::collections::storage;
 use macroquad::prelude::*;
 
 use hecs::{Entity, World};
 
+use serde::{Deserialize, Serialize};
+
 use core::Transform;
 
-use crate::game::play_sound_effect;
+use crate::ecs::{get_resource, get_resource_mut};
+use crate::effects::passive::{restore_opacity, INVISIBILITY_EFFECT_FUNCTION_ID};
+use crate::game::events::{publish_game_event, GameEvent};
+use crate::game::rng::Rng;
+use crate::game::{get_delta_time, play_sound_effect};
 use crate::player::{
-    Player, PlayerAttributes, PlayerController, PlayerEventQueue, JUMP_SOUND_ID, LAND_SOUND_ID,
-    RESPAWN_DELAY,
+    shatter_frozen_player, Player, PlayerAttributes, PlayerController, PlayerEventQueue,
+    PlayerStatusEffects, JUMP_SOUND_ID, LAND_SOUND_ID, RESPAWN_DELAY,
 };
 use crate::{CollisionWorld, Drawable, DrawableKind, Item, Map, PhysicsBody, PlayerEvent};
 
 const SLIDE_STOP_THRESHOLD: f32 = 2.0;
 const JUMP_FRAME_COUNT: u16 = 8;
 const PLATFORM_JUMP_FORCE_MULTIPLIER: f32 = 0.2;
+/// The move speed of a player is multiplied by this while swimming
+const SWIM_SPEED_FACTOR: f32 = 0.6;
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PlayerState {
     None,
     Jumping,
     Floating,
     Crouching,
     Sliding,
+    /// The player is inside water, where it can move freely in all directions
+    Swimming,
     Incapacitated,
     Dead,
 }
@@ -34,17 +44,21 @@ impl Default for PlayerState {
 }
 
 pub fn update_player_states(world: &mut World) {
-    let query = world.query_mut::<(
+    let dt = get_delta_time(world);
+
+    let mut respawned = Vec::new();
+
+    let mut query = world.query::<(
         &mut Transform,
         &mut Player,
         &PlayerController,
         &PlayerAttributes,
+        &PlayerStatusEffects,
         &mut PhysicsBody,
     )>();
-    for (_, (transform, player, controller, attributes, body)) in query {
+    for (entity, (transform, player, controller, attributes, status_effects, body)) in query.iter()
+    {
         // Timers
-        let dt = get_frame_time();
-
         player.attack_timer -= dt;
         if player.attack_timer <= 0.0 {
             player.attack_timer = 0.0;
@@ -67,13 +81,16 @@ pub fn update_player_states(world: &mut World) {
                 player.state = PlayerState::None;
                 player.respawn_timer = 0.0;
 
-                let map = storage::get::<Map>();
-                transform.position = map.get_random_spawn_point();
+                let map = get_resource::<Map>(world);
+                let mut rng = get_resource_mut::<Rng>(world);
+                transform.position = map.get_random_spawn_point(&mut rng);
+
+                respawned.push(entity);
             }
         } else if player.state == PlayerState::Incapacitated {
             player.incapacitation_timer += dt;
 
-            if player.incapacitation_timer >= attributes.incapacitation_duration {
+            if player.incapacitation_timer >= attributes.stats.incapacitation_duration {
                 player.state = PlayerState::None;
                 player.incapacitation_timer = 0.0;
             }
@@ -84,8 +101,26 @@ pub fn update_player_states(world: &mut World) {
             player.state = PlayerState::None;
         }
 
+        let swim_speed = attributes.stats.move_speed * SWIM_SPEED_FACTOR;
+
+        if body.is_in_water {
+            // A player that jumps while swimming keeps jumping until it slows down to swim speed,
+            // so that it is possible to jump out of the water
+            let is_jumping_out =
+                player.state == PlayerState::Jumping && body.velocity.y < -swim_speed;
+
+            if !is_jumping_out
+                && !matches!(player.state, PlayerState::Dead | PlayerState::Incapacitated)
+            {
+                player.state = PlayerState::Swimming;
+            }
+        } else if player.state == PlayerState::Swimming {
+            player.state = PlayerState::None;
+        }
+
         // Integration
         if player.is_attacking
+            || status_effects.is_knocked_back()
             || matches!(
                 player.state,
                 PlayerState::Dead | PlayerState::Incapacitated | PlayerState::Sliding
@@ -104,8 +139,23 @@ pub fn update_player_states(world: &mut World) {
                 player.is_facing_left = false;
             }
 
-            if controller.should_slide {
-                let velocity = attributes.move_speed * attributes.slide_speed_factor;
+            if player.state == PlayerState::Swimming {
+                body.velocity.x = controller.move_direction.x * swim_speed;
+
+                if controller.should_jump {
+                    body.velocity.y = -attributes.stats.jump_force;
+
+                    player.state = PlayerState::Jumping;
+
+                    play_sound_effect(JUMP_SOUND_ID, 0.4);
+                } else {
+                    body.velocity.y = controller.move_direction.y * swim_speed;
+                }
+
+                player.jump_frame_counter = 0;
+                body.has_mass = true;
+            } else if controller.should_slide {
+                let velocity = attributes.stats.move_speed * attributes.stats.slide_speed_factor;
 
                 if player.is_facing_left {
                     body.velocity.x = -velocity;
@@ -116,9 +166,9 @@ pub fn update_player_states(world: &mut World) {
                 player.state = PlayerState::Sliding;
             } else {
                 if controller.move_direction.x < 0.0 {
-                    body.velocity.x = -attributes.move_speed;
+                    body.velocity.x = -attributes.stats.move_speed;
                 } else if controller.move_direction.x > 0.0 {
-                    body.velocity.x = attributes.move_speed;
+                    body.velocity.x = attributes.stats.move_speed;
                 } else {
                     body.velocity.x = 0.0;
                 }
@@ -128,16 +178,16 @@ pub fn update_player_states(world: &mut World) {
                         body.velocity.x = 0.0;
                         player.state = PlayerState::Crouching;
                     } else {
-                        let mut collision_world = storage::get_mut::<CollisionWorld>();
+                        let mut collision_world = get_resource_mut::<CollisionWorld>(world);
                         collision_world.descent(body.actor);
                     }
                 }
 
                 if body.is_on_ground && controller.should_jump {
                     let jump_force = if controller.should_crouch && body.is_on_platform {
-                        attributes.jump_force * PLATFORM_JUMP_FORCE_MULTIPLIER
+                        attributes.stats.jump_force * PLATFORM_JUMP_FORCE_MULTIPLIER
                     } else {
-                        attributes.jump_force
+                        attributes.stats.jump_force
                     };
 
                     body.velocity.y = -jump_force;
@@ -162,7 +212,7 @@ pub fn update_player_states(world: &mut World) {
 
                 if !body.is_on_ground && body.velocity.y > 0.0 {
                     if controller.should_float {
-                        body.velocity.y *= attributes.float_gravity_factor;
+                        body.velocity.y *= attributes.stats.float_gravity_factor;
                         player.state = PlayerState::Floating;
                     }
                 } else if player.state == PlayerState::Floating {
@@ -187,20 +237,31 @@ pub fn update_player_states(world: &mut World) {
 
         player.was_on_ground = body.is_on_ground;
     }
+
+    drop(query);
+
+    for player in respawned {
+        publish_game_event(world, GameEvent::PlayerRespawned { player });
+    }
 }
 
 pub fn update_player_passive_effects(world: &mut World) {
+    let dt = get_delta_time(world);
+
     let mut function_calls = Vec::new();
 
     let mut sprites_to_spawn = Vec::new();
     let mut sprites_to_despawn = Vec::new();
 
     for (entity, (player, player_transform, player_drawable, events)) in world
-        .query::<(&mut Player, &Transform, &Drawable, &mut PlayerEventQueue)>()
+        .query::<(
+            &mut Player,
+            &Transform,
+            &mut Drawable,
+            &mut PlayerEventQueue,
+        )>()
         .iter()
     {
-        let dt = get_frame_time();
-
         for effect in &mut player.passive_effects {
             effect.duration_timer += dt;
 
@@ -237,6 +298,18 @@ pub fn update_player_passive_effects(world: &mut World) {
             }
         });
 
+        // Effects can also be removed when items are dropped or the player dies, so this checks
+        // whether any invisibility effect remains, rather than which effects were removed
+        let is_invisible = player.passive_effects.iter().any(|effect| {
+            effect.meta.function_id.as_deref() == Some(INVISIBILITY_EFFECT_FUNCTION_ID)
+        });
+
+        if player.is_invisible && !is_invisible {
+            restore_opacity(player_drawable);
+        }
+
+        player.is_invisible = is_invisible;
+
         events.queue.push(PlayerEvent::Update { dt });
 
         for event in events.queue.iter() {
@@ -253,15 +326,21 @@ pub fn update_player_passive_effects(world: &mut World) {
                     }
 
                     if let Some(f) = &effect.function {
-                        function_calls.push((*f, entity, effect.item, event.clone()));
+                        function_calls.push((
+                            *f,
+                            entity,
+                            effect.item,
+                            effect.meta.function_params.clone(),
+                            event.clone(),
+                        ));
                     }
                 }
             }
         }
     }
 
-    for (f, player_entity, item_entity, event) in function_calls.drain(0..) {
-        f(world, player_entity, item_entity, event);
+    for (f, player_entity, item_entity, params, event) in function_calls.drain(0..) {
+        f(world, player_entity, item_entity, &params, event);
     }
 
     for (sprite_entity, drawable, position) in sprites_to_spawn {
@@ -275,7 +354,14 @@ pub fn update_player_passive_effects(world: &mut World) {
     }
 }
 
-pub fn on_player_damage(world: &mut World, damage_from_entity: Entity, damage_to_entity: Entity) {
+/// Damage a player. `source_id` should be the id of the weapon or item that caused the damage, if
+/// any, so that kills can be attributed to it.
+pub fn on_player_damage(
+    world: &mut World,
+    damage_from_entity: Entity,
+    damage_to_entity: Entity,
+    source_id: Option<String>,
+) {
     let mut is_from_left = false;
 
     if let Ok(owner_transform) = world.get::<Transform>(damage_from_entity) {
@@ -294,12 +380,24 @@ pub fn on_player_damage(world: &mut World, damage_from_entity: Entity, damage_to
         });
     }
 
+    shatter_frozen_player(world, damage_to_entity);
+
     {
         let mut events = world.get_mut::<PlayerEventQueue>(damage_to_entity).unwrap();
 
         events.queue.push(PlayerEvent::ReceiveDamage {
             is_from_left,
             damage_from: Some(damage_from_entity),
+            source_id: source_id.clone(),
         });
     }
+
+    publish_game_event(
+        world,
+        GameEvent::PlayerHit {
+            player: damage_to_entity,
+            hit_by: damage_from_entity,
+            source_id,
+        },
+    );
 }
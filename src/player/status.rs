@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+
+use macroquad::prelude::*;
+
+use hecs::{Entity, World};
+
+use serde::{Deserialize, Serialize};
+
+use core::math::IsZero;
+use core::Transform;
+
+use crate::ecs::get_resource_mut;
+use crate::game::get_delta_time;
+use crate::particles::Particles;
+use crate::player::{Player, PlayerStat, PlayerState, StatModifierMetadata, StatModifierOperation};
+use crate::{AnimatedSprite, AnimatedSpriteMetadata, Drawable, DrawableKind, PhysicsBody};
+
+const FREEZE_TINT: Color = Color {
+    r: 0.6,
+    g: 0.8,
+    b: 1.0,
+    a: 1.0,
+};
+
+const DEFAULT_SLOW_FACTOR: f32 = 0.5;
+
+/// Status effects are applied to players that are hit by active effects or projectiles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StatusEffectKind {
+    /// Incapacitates the player
+    Stun,
+    /// Incapacitates the player. If the player is hit while frozen, the freeze will end and the
+    /// `shatter_particle_effect`, if any, will be spawned.
+    Freeze {
+        #[serde(
+            default,
+            rename = "shatter_particle_effect",
+            skip_serializing_if = "Option::is_none"
+        )]
+        shatter_particle_effect_id: Option<String>,
+    },
+    /// Multiplies the move speed of the player by `factor`
+    Slow {
+        #[serde(default = "StatusEffectKind::default_slow_factor")]
+        factor: f32,
+    },
+    /// Applies an impulse to the player, away from the source of the hit. The player will not be
+    /// in control of its movement for the duration of the effect.
+    Knockback {
+        #[serde(with = "core::json::vec2_def")]
+        force: Vec2,
+    },
+}
+
+impl StatusEffectKind {
+    /// This is used to find existing effects of the same kind, when applying stacking rules and
+    /// immunity windows
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Stun => "stun",
+            Self::Freeze { .. } => "freeze",
+            Self::Slow { .. } => "slow",
+            Self::Knockback { .. } => "knockback",
+        }
+    }
+
+    pub fn is_incapacitating(&self) -> bool {
+        matches!(self, Self::Stun | Self::Freeze { .. })
+    }
+
+    pub fn default_slow_factor() -> f32 {
+        DEFAULT_SLOW_FACTOR
+    }
+}
+
+/// This decides what happens when a status effect is applied to a player that already has an
+/// effect of the same kind
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusEffectStacking {
+    /// Replace the existing effect, restarting its duration
+    Refresh,
+    /// Add the duration of the new effect to the remaining duration of the existing effect
+    Extend,
+    /// Keep the existing effect and discard the new one
+    Ignore,
+    /// Add the new effect alongside the existing one
+    Stack,
+}
+
+impl Default for StatusEffectStacking {
+    fn default() -> Self {
+        StatusEffectStacking::Refresh
+    }
+}
+
+// NOTE: We would prefer to `serde(deny_unknown_fields)` here, but we are blocked by this issue:
+// https://github.com/serde-rs/serde/issues/1358
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusEffectMetadata {
+    #[serde(flatten)]
+    pub kind: StatusEffectKind,
+    pub duration: f32,
+    #[serde(default)]
+    pub stacking: StatusEffectStacking,
+    /// The time, after the effect has ended, that the player will be immune to effects of the
+    /// same kind
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub immunity_duration: f32,
+    /// The tint applied to the player while the effect is active. Freeze effects will use a
+    /// light blue tint, if this is not specified.
+    #[serde(
+        default,
+        with = "core::json::color_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub tint: Option<Color>,
+    /// An optional sprite that is drawn on top of the player while the effect is active
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sprite: Option<AnimatedSpriteMetadata>,
+}
+
+impl StatusEffectMetadata {
+    pub fn get_tint(&self) -> Option<Color> {
+        match self.kind {
+            StatusEffectKind::Freeze { .. } => self.tint.or(Some(FREEZE_TINT)),
+            _ => self.tint,
+        }
+    }
+
+    /// Returns the stat modifier that should be applied to the player while the effect is active
+    pub fn get_stat_modifier(&self) -> Option<StatModifierMetadata> {
+        if let StatusEffectKind::Slow { factor } = self.kind {
+            Some(StatModifierMetadata {
+                stat: PlayerStat::MoveSpeed,
+                operation: StatModifierOperation::Multiply,
+                value: factor,
+                priority: 0,
+                duration: None,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatusEffect {
+    pub meta: StatusEffectMetadata,
+    pub duration: f32,
+    pub timer: f32,
+    /// The entity of the overlay sprite. This is spawned by `update_player_status_effects`
+    #[serde(skip)]
+    pub sprite_entity: Option<Entity>,
+}
+
+impl StatusEffect {
+    pub fn new(meta: StatusEffectMetadata) -> Self {
+        StatusEffect {
+            duration: meta.duration,
+            meta,
+            timer: 0.0,
+            sprite_entity: None,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.timer >= self.duration
+    }
+}
+
+/// The status effects that are active on a player, as well as the remaining time of its
+/// immunities, by the id of the status effect kind
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlayerStatusEffects {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub active: Vec<StatusEffect>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub immunities: HashMap<String, f32>,
+}
+
+impl PlayerStatusEffects {
+    pub fn new() -> Self {
+        PlayerStatusEffects::default()
+    }
+
+    pub fn is_incapacitated(&self) -> bool {
+        self.active
+            .iter()
+            .any(|effect| effect.meta.kind.is_incapacitating())
+    }
+
+    pub fn is_knocked_back(&self) -> bool {
+        self.active
+            .iter()
+            .any(|effect| matches!(effect.meta.kind, StatusEffectKind::Knockback { .. }))
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.active
+            .iter()
+            .any(|effect| matches!(effect.meta.kind, StatusEffectKind::Freeze { .. }))
+    }
+
+    fn end_effect(&mut self, effect: &StatusEffect) {
+        if effect.meta.immunity_duration > 0.0 {
+            self.immunities.insert(
+                effect.meta.kind.id().to_string(),
+                effect.meta.immunity_duration,
+            );
+        }
+    }
+}
+
+/// Apply a status effect to a player. `is_from_left` is the direction of the hit, used for
+/// knockback.
+pub fn apply_status_effect(
+    world: &mut World,
+    player_entity: Entity,
+    is_from_left: bool,
+    meta: StatusEffectMetadata,
+) {
+    {
+        let player = world.get::<Player>(player_entity).unwrap();
+        if player.state == PlayerState::Dead {
+            return;
+        }
+    }
+
+    let mut status_effects = world.get_mut::<PlayerStatusEffects>(player_entity).unwrap();
+
+    let id = meta.kind.id();
+
+    if status_effects.immunities.contains_key(id) {
+        return;
+    }
+
+    let existing = status_effects
+        .active
+        .iter()
+        .position(|effect| effect.meta.kind.id() == id);
+
+    if let Some(i) = existing {
+        match meta.stacking {
+            StatusEffectStacking::Refresh => {
+                let sprite_entity = status_effects.active[i].sprite_entity.take();
+
+                let mut effect = StatusEffect::new(meta.clone());
+                effect.sprite_entity = sprite_entity;
+
+                status_effects.active[i] = effect;
+            }
+            StatusEffectStacking::Extend => {
+                status_effects.active[i].duration += meta.duration;
+            }
+            StatusEffectStacking::Ignore => return,
+            StatusEffectStacking::Stack => {
+                status_effects.active.push(StatusEffect::new(meta.clone()));
+            }
+        }
+    } else {
+        status_effects.active.push(StatusEffect::new(meta.clone()));
+    }
+
+    drop(status_effects);
+
+    if meta.kind.is_incapacitating() {
+        let mut player = world.get_mut::<Player>(player_entity).unwrap();
+
+        player.state = PlayerState::Incapacitated;
+        player.incapacitation_timer = 0.0;
+    }
+
+    if let StatusEffectKind::Knockback { force } = meta.kind {
+        let mut body = world.get_mut::<PhysicsBody>(player_entity).unwrap();
+
+        body.velocity = if is_from_left {
+            force
+        } else {
+            vec2(-force.x, force.y)
+        };
+    }
+}
+
+/// End any freeze effects on a player that is hit, spawning their shatter particle effects
+pub fn shatter_frozen_player(world: &mut World, player_entity: Entity) {
+    let mut ended = Vec::new();
+
+    if let Ok(mut status_effects) = world.get_mut::<PlayerStatusEffects>(player_entity) {
+        status_effects.active.retain(|effect| {
+            if let StatusEffectKind::Freeze { .. } = effect.meta.kind {
+                ended.push(effect.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        for effect in &ended {
+            status_effects.end_effect(effect);
+        }
+    }
+
+    if ended.is_empty() {
+        return;
+    }
+
+    let position = world.get::<Transform>(player_entity).unwrap().position;
+
+    for effect in ended {
+        if let Some(sprite_entity) = effect.sprite_entity {
+            let _ = world.despawn(sprite_entity);
+        }
+
+        if let StatusEffectKind::Freeze {
+            shatter_particle_effect_id: Some(id),
+        } = effect.meta.kind
+        {
+            let mut particles = get_resource_mut::<Particles>(world);
+
+            if let Some(cache) = particles.cache_map.get_mut(&id) {
+                cache.spawn(position);
+            } else {
+                #[cfg(debug_assertions)]
+                println!("WARNING: Invalid particle effect id '{}'", &id);
+            }
+        }
+    }
+}
+
+pub fn update_player_status_effects(world: &mut World) {
+    let dt = get_delta_time(world);
+
+    let mut sprites_to_spawn = Vec::new();
+    let mut sprites_to_despawn = Vec::new();
+
+    for (_, (player, transform, drawable, status_effects)) in world
+        .query::<(
+            &mut Player,
+            &Transform,
+            &mut Drawable,
+            &mut PlayerStatusEffects,
+        )>()
+        .iter()
+    {
+        let was_incapacitated = status_effects.is_incapacitated();
+
+        if player.state == PlayerState::Dead {
+            for effect in status_effects.active.drain(..) {
+                if let Some(sprite_entity) = effect.sprite_entity {
+                    sprites_to_despawn.push(sprite_entity);
+                }
+            }
+
+            status_effects.immunities.clear();
+        }
+
+        for timer in status_effects.immunities.values_mut() {
+            *timer -= dt;
+        }
+
+        status_effects.immunities.retain(|_, timer| *timer > 0.0);
+
+        let mut ended = Vec::new();
+
+        for effect in &mut status_effects.active {
+            effect.timer += dt;
+
+            if effect.is_expired() {
+                ended.push(effect.clone());
+            }
+        }
+
+        status_effects.active.retain(|effect| !effect.is_expired());
+
+        for effect in ended {
+            status_effects.end_effect(&effect);
+
+            if let Some(sprite_entity) = effect.sprite_entity {
+                sprites_to_despawn.push(sprite_entity);
+            }
+        }
+
+        for effect in &mut status_effects.active {
+            // Move the sprite to follow the player
+            if let Some(sprite_entity) = effect.sprite_entity {
+                if let Ok(mut sprite_transform) = world.get_mut::<Transform>(sprite_entity) {
+                    sprite_transform.position = transform.position;
+                }
+            } else if let Some(meta) = effect.meta.sprite.clone() {
+                let sprite_entity = world.reserve_entity();
+
+                let sprite_drawable = Drawable {
+                    draw_order: drawable.draw_order + 1,
+                    kind: DrawableKind::AnimatedSprite(AnimatedSprite::from(meta)),
+                };
+
+                sprites_to_spawn.push((sprite_entity, sprite_drawable, transform.position));
+
+                effect.sprite_entity = Some(sprite_entity);
+            }
+        }
+
+        if status_effects.is_incapacitated() {
+            player.state = PlayerState::Incapacitated;
+            player.incapacitation_timer = 0.0;
+        } else if was_incapacitated && player.state == PlayerState::Incapacitated {
+            player.state = PlayerState::None;
+        }
+
+        let tint = status_effects
+            .active
+            .iter()
+            .rev()
+            .find_map(|effect| effect.meta.get_tint())
+            .unwrap_or(WHITE);
+
+        // The status tint is applied on top of the tint of each sprite, so the original tint is
+        // restored once no status effect is tinting the player. Opacity is left untouched.
+        if let Some(sprite_set) = drawable.get_animated_sprite_set_mut() {
+            for sprite in sprite_set.map.values_mut() {
+                sprite.tint = Color {
+                    r: sprite.base_tint.r * tint.r,
+                    g: sprite.base_tint.g * tint.g,
+                    b: sprite.base_tint.b * tint.b,
+                    a: sprite.tint.a,
+                };
+            }
+        }
+    }
+
+    for (sprite_entity, drawable, position) in sprites_to_spawn {
+        world
+            .insert(sprite_entity, (Transform::from(position), drawable))
+            .unwrap();
+    }
+
+    for entity in sprites_to_despawn {
+        let _ = world.despawn(entity);
+    }
+}
@@ -1,5 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 /// A simple timer utility
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Timer {
     duration: f32,
     elapsed: f32,
@@ -39,9 +42,9 @@ impl Timer {
         self.elapsed = 0.0;
     }
 
-    /// Advanced the elapsed time by the macroquad frame time
-    pub fn tick_frame_time(&mut self) {
-        self.elapsed += macroquad::time::get_frame_time();
+    /// Advance the elapsed time by `dt`
+    pub fn tick(&mut self, dt: f32) {
+        self.elapsed += dt;
     }
 }
 